@@ -0,0 +1,162 @@
+//! `surf audit`: platform path references and broken paths, or the `--fix` totals, and
+//! `surf audit orphans`: a table of unreferenced documents.
+
+use anyhow::Result;
+use colored::Colorize;
+
+use surfcontext_cli::config;
+use surfcontext_cli::sync::audit::{self, Reference};
+use surfcontext_cli::sync::trash::{self, Trash};
+use surfcontext_cli::sync::{self, repo_lock, reporter};
+
+/// `surf audit`. Returns how many references are left, so the caller can fail on them.
+pub fn run_audit(opts: &audit::AuditOpts) -> Result<usize> {
+    let repo_root = sync::find_repo_root()?;
+    let config = config::load_config(&repo_root)?;
+
+    if !opts.quiet {
+        println!(
+            "{} {}",
+            "SurfContext Audit".bold(),
+            format!("— {}", repo_root.display()).dimmed()
+        );
+        println!("{}", "================================".dimmed());
+        if opts.fix && opts.dry_run {
+            println!("{}", "[DRY RUN] No files will be written.".yellow());
+        }
+    }
+
+    if !opts.fix {
+        let found = audit::platform_references(&repo_root, &config)?;
+        let broken = audit::find_broken_references(&repo_root, &config);
+        if !opts.quiet {
+            print_references(&found);
+            for reference in &broken {
+                println!("  {} {reference}", "MISSING".red());
+            }
+            if found.is_empty() && broken.is_empty() {
+                println!(
+                    "{}",
+                    "No platform path references or broken paths."
+                        .green()
+                        .bold()
+                );
+            } else if !found.is_empty() {
+                println!();
+                println!(
+                    "{} run `surf audit --fix` to rewrite the platform paths.",
+                    "Fix:".bold()
+                );
+            }
+        }
+        return Ok(found.len() + broken.len());
+    }
+
+    let mut reporter = reporter::console(opts.quiet);
+    let _lock = repo_lock::for_command(&repo_root, opts.dry_run, reporter.as_mut())?;
+    let mut trash = Trash::new(&repo_root, opts.dry_run);
+    let fixed = audit::fix(&repo_root, &config, opts, &mut trash, reporter.as_mut())?;
+
+    if !opts.quiet {
+        println!();
+        println!(
+            "{} {} reference(s) in {} file(s)",
+            if opts.dry_run { "Would fix:" } else { "Fixed:" }.bold(),
+            fixed.fixed,
+            fixed.files.len()
+        );
+        if !fixed.remaining.is_empty() {
+            println!(
+                "{} {} reference(s) left in code blocks (--include-code rewrites them too):",
+                "Kept:".bold(),
+                fixed.remaining.len()
+            );
+            print_references(&fixed.remaining);
+        }
+        if let Some((id, count)) = trash.saved() {
+            println!(
+                "Trash: {count} item(s) saved to {}/{id}/ {}",
+                trash::TRASH_DIR,
+                "(surf undo to restore)".dimmed()
+            );
+        }
+    }
+    Ok(fixed.remaining.len())
+}
+
+fn print_references(references: &[Reference]) {
+    for Reference {
+        file,
+        line,
+        pattern,
+        in_code,
+    } in references
+    {
+        println!(
+            "  {} {file}:{line} references `{pattern}`{}",
+            "WARN".yellow(),
+            if *in_code { " (in a code block)" } else { "" }.dimmed()
+        );
+    }
+}
+
+/// `surf audit orphans`. Returns the number of orphans.
+pub fn run_orphans(quiet: bool) -> Result<usize> {
+    let repo_root = sync::find_repo_root()?;
+    let config = config::load_config(&repo_root)?;
+    let orphans = audit::find_orphans(&repo_root, &config)?;
+    if quiet {
+        return Ok(orphans.len());
+    }
+
+    println!(
+        "{} {}",
+        "SurfContext Audit".bold(),
+        format!("— {}", repo_root.display()).dimmed()
+    );
+    println!("{}", "================================".dimmed());
+    if orphans.is_empty() {
+        println!(
+            "{}",
+            "Every document is reachable from the root context."
+                .green()
+                .bold()
+        );
+        return Ok(0);
+    }
+
+    println!(
+        "{}",
+        format!("Unreferenced documents ({}):", orphans.len()).bold()
+    );
+    let width = orphans.iter().map(|o| o.path.len()).max().unwrap_or(0);
+    for orphan in &orphans {
+        let age = match orphan.age_days {
+            Some(0) => "modified today".to_string(),
+            Some(1) => "modified yesterday".to_string(),
+            Some(days) => format!("modified {days} days ago"),
+            None => String::new(),
+        };
+        println!(
+            "  {:<width$}  {:>9}  {}",
+            orphan.path,
+            human_size(orphan.size),
+            age.dimmed()
+        );
+    }
+    println!();
+    println!(
+        "{} link them from {} or a document it leads to, or delete them.",
+        "Fix:".bold(),
+        config.canonical.root_context
+    );
+    Ok(orphans.len())
+}
+
+fn human_size(bytes: u64) -> String {
+    match bytes {
+        0..1_024 => format!("{bytes} B"),
+        1_024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1_024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
@@ -0,0 +1,63 @@
+//! `surf backport`: header, per-hunk progress and what to do next.
+
+use anyhow::Result;
+use colored::Colorize;
+
+use surfcontext_cli::config;
+use surfcontext_cli::sync::trash::{self, Trash};
+use surfcontext_cli::sync::{self, backport, repo_lock, reporter};
+
+/// `surf backport`
+pub fn run_backport(opts: &backport::BackportOpts) -> Result<()> {
+    let repo_root = sync::find_repo_root()?;
+    let config = config::load_config(&repo_root)?;
+
+    if !opts.quiet {
+        println!(
+            "{} {}",
+            "SurfContext Backport".bold(),
+            format!("— {}", repo_root.display()).dimmed()
+        );
+        println!("{}", "================================".dimmed());
+        if opts.dry_run {
+            println!("{}", "[DRY RUN] No files will be modified.".yellow());
+        }
+    }
+
+    let mut reporter = reporter::console(opts.quiet);
+    let _lock = repo_lock::for_command(&repo_root, opts.dry_run, reporter.as_mut())?;
+    let mut trash = Trash::new(&repo_root, opts.dry_run);
+    let report = backport::backport(&repo_root, &config, opts, &mut trash, reporter.as_mut())?;
+
+    if !opts.quiet {
+        println!();
+        println!(
+            "{} {} hunk(s), {} skipped",
+            if opts.dry_run {
+                "Would back-port:"
+            } else {
+                "Back-ported:"
+            }
+            .bold(),
+            report.applied,
+            report.skipped
+        );
+        if report.applied > 0 && !opts.dry_run {
+            println!("Run `surf sync` to regenerate the platform files.");
+        }
+        if report.skipped > 0 {
+            println!(
+                "Move skipped hunks into {} by hand, then `surf sync --force`.",
+                config.canonical.root_context
+            );
+        }
+        if let Some((id, count)) = trash.saved() {
+            println!(
+                "Trash: {count} item(s) saved to {}/{id}/ {}",
+                trash::TRASH_DIR,
+                "(surf undo to restore)".dimmed()
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+//! `surf check`: print what the verify run found as three short lists.
+
+use anyhow::Result;
+use colored::Colorize;
+
+use surfcontext_cli::sync::check::{
+    EXIT_ERRORS, EXIT_OK, EXIT_OUT_OF_DATE, check, exit_code, out_of_date,
+};
+use surfcontext_cli::sync::{self, SyncReport};
+
+pub struct CheckOpts {
+    /// Skip checking cross-repo targets.
+    pub local_only: bool,
+    /// Warnings fail the check like errors do.
+    pub strict: bool,
+    pub quiet: bool,
+}
+
+/// `surf check`. Returns the process exit code.
+pub fn run_check(opts: &CheckOpts) -> Result<i32> {
+    let repo_root = sync::find_repo_root()?;
+    if !opts.quiet {
+        println!(
+            "{} {}",
+            "SurfContext Check".bold(),
+            format!("— {}", repo_root.display()).dimmed()
+        );
+        println!("{}", "================================".dimmed());
+    }
+
+    let report = match check(&repo_root, opts.local_only) {
+        Ok(report) => report,
+        Err(e) => {
+            if !opts.quiet {
+                println!("{} {e:#}", "ERROR:".red());
+            }
+            return Ok(EXIT_ERRORS);
+        }
+    };
+
+    if !opts.quiet {
+        print_findings(&report, opts.strict);
+    }
+    Ok(exit_code(&report, opts.strict))
+}
+
+fn lines<'a, T: ToString + 'a>(items: impl IntoIterator<Item = &'a T>) -> Vec<String> {
+    items.into_iter().map(ToString::to_string).collect()
+}
+
+fn print_findings(report: &SyncReport, strict: bool) {
+    let sections = [
+        ("Errors", lines(&report.errors), "ERROR".red()),
+        ("Out of date", lines(out_of_date(report)), "SYNC".yellow()),
+        ("Warnings", lines(&report.warnings), "WARN".yellow()),
+    ];
+    for (title, items, tag) in sections {
+        if items.is_empty() {
+            continue;
+        }
+        println!();
+        println!("{}", format!("{title} ({}):", items.len()).bold());
+        for item in &items {
+            println!("  {tag} {item}");
+        }
+    }
+
+    println!();
+    match exit_code(report, strict) {
+        EXIT_OK => println!("{}", "Up to date.".green().bold()),
+        EXIT_ERRORS => println!(
+            "{} fix the errors above, then run `surf sync`.",
+            "Fix:".bold()
+        ),
+        EXIT_OUT_OF_DATE => println!("{} run `surf sync` and commit the result.", "Fix:".bold()),
+        _ => println!("{} see the warnings above.", "Fix:".bold()),
+    }
+}
@@ -0,0 +1,54 @@
+//! `surf clean`: header, per-output progress and a one-line total.
+
+use anyhow::Result;
+use colored::Colorize;
+
+use surfcontext_cli::config;
+use surfcontext_cli::sync::trash::{self, Trash};
+use surfcontext_cli::sync::{self, clean, repo_lock, reporter};
+
+/// `surf clean`
+pub fn run_clean(opts: &clean::CleanOpts) -> Result<()> {
+    let repo_root = sync::find_repo_root()?;
+    let config = config::load_config(&repo_root)?;
+
+    if !opts.quiet {
+        println!(
+            "{} {}",
+            "SurfContext Clean".bold(),
+            format!("— {}", repo_root.display()).dimmed()
+        );
+        println!("{}", "================================".dimmed());
+        if opts.dry_run {
+            println!("{}", "[DRY RUN] No files will be removed.".yellow());
+        }
+    }
+
+    let mut reporter = reporter::console(opts.quiet);
+    let _lock = repo_lock::for_command(&repo_root, opts.dry_run, reporter.as_mut())?;
+    let mut trash = Trash::new(&repo_root, opts.dry_run);
+    let report = clean::clean(&repo_root, &config, opts, &mut trash, reporter.as_mut())?;
+
+    if !opts.quiet {
+        println!();
+        println!(
+            "{} {} removed, {} kept",
+            if opts.dry_run {
+                "Would clean:"
+            } else {
+                "Cleaned:"
+            }
+            .bold(),
+            report.removed.len(),
+            report.kept.len()
+        );
+        if let Some((id, count)) = trash.saved() {
+            println!(
+                "Trash: {count} item(s) saved to {}/{id}/ {}",
+                trash::TRASH_DIR,
+                "(surf undo to restore)".dimmed()
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,13 @@
+//! Terminal frontends for the `surf` commands.
+//!
+//! The library returns what a command did as data; these modules print it. They live in the
+//! binary so nothing that embeds `surfcontext_cli` ever writes to stdout behind its back.
+
+pub mod audit;
+pub mod backport;
+pub mod check;
+pub mod clean;
+pub mod mv;
+pub mod queue;
+pub mod report;
+pub mod trash;
@@ -0,0 +1,111 @@
+//! `surf mv`: the planned move and rewrites, then the summary of the sync after it.
+
+use anyhow::{Context, Result, bail};
+use colored::Colorize;
+
+use surfcontext_cli::config;
+use surfcontext_cli::sync::trash::{self, Trash};
+use surfcontext_cli::sync::{self, SyncOpts, mv, repo_lock, reporter};
+
+/// `surf mv <old> <new>`, with both paths relative to the current directory.
+pub fn run_mv(old: &str, new: &str, opts: &mv::MoveOpts) -> Result<()> {
+    let repo_root = sync::find_repo_root()?;
+    let config = config::load_config(&repo_root)?;
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let old = mv::repo_path(&repo_root, &cwd, old)?;
+    let new = mv::repo_path(&repo_root, &cwd, new)?;
+
+    if !opts.quiet {
+        println!(
+            "{} {}",
+            "SurfContext Move".bold(),
+            format!("— {}", repo_root.display()).dimmed()
+        );
+        println!("{}", "================================".dimmed());
+        if opts.dry_run {
+            println!("{}", "[DRY RUN] No files will be written.".yellow());
+        }
+    }
+
+    // Hold the lock from planning to the last write so nothing changes in between.
+    // A dry run writes nothing and takes no lock.
+    let mut reporter = reporter::console(opts.quiet);
+    let lock = repo_lock::for_command(&repo_root, opts.dry_run, reporter.as_mut())?;
+
+    let plan = mv::plan(&repo_root, &config, &old, &new)?;
+    if !opts.quiet {
+        println!(
+            "  {} {} -> {}",
+            "Move".green(),
+            old.display(),
+            new.display()
+        );
+        for copy in &plan.copies {
+            println!(
+                "  {} {} {}",
+                "Remove".green(),
+                copy.display(),
+                "(mirror copy of the old path)".dimmed()
+            );
+        }
+        for rewrite in &plan.rewrites {
+            println!("  {} {rewrite}", "Rewrite".green());
+        }
+        for warning in &plan.warnings {
+            println!("  {} {warning}", "WARNING:".yellow());
+        }
+    }
+
+    if opts.dry_run {
+        if !opts.quiet {
+            println!();
+            println!(
+                "{} {} reference(s) in {} file(s), then a local sync to regenerate platform files",
+                "Would move:".bold(),
+                plan.rewrites.len(),
+                plan.files()
+            );
+        }
+        return Ok(());
+    }
+
+    let mut trash = Trash::new(&repo_root, false);
+    mv::apply(&repo_root, &plan, &mut trash)?;
+
+    if !opts.quiet {
+        println!();
+        println!(
+            "{} {} reference(s) in {} file(s)",
+            "Moved:".bold(),
+            plan.rewrites.len(),
+            plan.files()
+        );
+        if let Some((id, count)) = trash.saved() {
+            println!(
+                "Trash: {count} item(s) saved to {}/{id}/ {}",
+                trash::TRASH_DIR,
+                "(surf undo moves it back)".dimmed()
+            );
+        }
+        println!();
+    }
+
+    // The move may have changed surfcontext.json.
+    let config = config::load_config(&repo_root)?;
+    let sync_opts = SyncOpts {
+        // A move is a local edit; pushing it to other repos is left to the next full sync.
+        local_only: true,
+        quiet: opts.quiet,
+        ..Default::default()
+    };
+    let report = sync::run_stages(&repo_root, &config, &sync_opts, lock)?;
+    if !opts.quiet {
+        for line in report.summary_lines() {
+            println!("{line}");
+        }
+    }
+    if report.failed(false) {
+        bail!("{} was moved, but the sync after it failed", new.display());
+    }
+    Ok(())
+}
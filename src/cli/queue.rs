@@ -0,0 +1,99 @@
+//! `surf queue`: one line per edit, and the task list as text, JSON or NDJSON.
+
+use anyhow::Result;
+use colored::Colorize;
+use std::path::Path;
+
+use surfcontext_cli::sync::queue::{self, Status};
+use surfcontext_cli::sync::report::OutputFormat;
+use surfcontext_cli::sync::{self, Reporter, reporter};
+
+/// Run a task edit on the current repo, printing the line it returns.
+fn run_edit(
+    quiet: bool,
+    edit: impl FnOnce(&Path, &mut dyn Reporter) -> Result<String>,
+) -> Result<()> {
+    let repo_root = sync::find_repo_root()?;
+    let message = edit(&repo_root, reporter::console(quiet).as_mut())?;
+    if !quiet {
+        println!("{message}");
+    }
+    Ok(())
+}
+
+/// `surf queue add`
+pub fn handle_add(task: &queue::NewTask, quiet: bool) -> Result<()> {
+    run_edit(quiet, |root, reporter| {
+        queue::add_task(root, task, reporter)
+    })
+}
+
+/// `surf queue start`
+pub fn handle_start(id: &str, owner: Option<&str>, quiet: bool) -> Result<()> {
+    run_edit(quiet, |root, reporter| {
+        queue::start_task(root, id, owner, reporter)
+    })
+}
+
+/// `surf queue done`
+pub fn handle_done(id: &str, quiet: bool) -> Result<()> {
+    run_edit(quiet, |root, reporter| {
+        queue::finish_task(root, id, reporter)
+    })
+}
+
+/// `surf queue assign`
+pub fn handle_assign(id: &str, owner: &str, quiet: bool) -> Result<()> {
+    run_edit(quiet, |root, reporter| {
+        queue::assign_task(root, id, owner, reporter)
+    })
+}
+
+/// `surf queue rm`
+pub fn handle_rm(id: &str, quiet: bool) -> Result<()> {
+    run_edit(quiet, |root, reporter| {
+        queue::remove_task(root, id, reporter)
+    })
+}
+
+/// `surf queue list`
+pub fn handle_list(filter: &queue::Filter, format: OutputFormat) -> Result<()> {
+    let repo_root = sync::find_repo_root()?;
+    let tasks = queue::list_tasks(&repo_root, filter)?;
+
+    match format {
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&tasks.iter().map(|t| t.to_json()).collect::<Vec<_>>())?
+        ),
+        OutputFormat::Ndjson => {
+            for task in &tasks {
+                println!("{}", task.to_json());
+            }
+        }
+        OutputFormat::Text => {
+            if tasks.is_empty() {
+                println!("{}", "No tasks.".dimmed());
+            }
+            for task in &tasks {
+                let status = format!("{:<11}", task.status.as_str());
+                let status = match task.status {
+                    Status::Todo => status.normal(),
+                    Status::InProgress => status.yellow(),
+                    Status::Done => status.green(),
+                };
+                let mut meta: Vec<String> = Vec::new();
+                meta.extend(task.owner.iter().map(|o| format!("@{o}")));
+                meta.extend(task.priority.iter().map(|p| format!("!{p}")));
+                meta.extend(task.tags.iter().map(|t| format!("#{t}")));
+                println!(
+                    "{:<6} {status} {} {}",
+                    task.label().bold(),
+                    task.title,
+                    meta.join(" ").dimmed()
+                );
+            }
+        }
+    }
+    Ok(())
+}
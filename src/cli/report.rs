@@ -0,0 +1,24 @@
+//! `surf sync`'s closing report, in the format asked for.
+
+use surfcontext_cli::sync::SyncReport;
+use surfcontext_cli::sync::report::OutputFormat;
+
+/// Print `report` in `format`. The text summary goes after the progress output.
+pub fn print(report: &SyncReport, format: OutputFormat) {
+    match format {
+        OutputFormat::Text => {
+            for line in report.summary_lines() {
+                println!("{line}");
+            }
+        }
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&report.to_json()).expect("report is serializable")
+        ),
+        OutputFormat::Ndjson => {
+            for line in report.to_ndjson() {
+                println!("{line}");
+            }
+        }
+    }
+}
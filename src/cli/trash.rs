@@ -0,0 +1,67 @@
+//! `surf undo` and `surf trash`: what was restored, listed or pruned.
+
+use anyhow::Result;
+use colored::Colorize;
+
+use surfcontext_cli::sync::trash::{EntryKind, TRASH_DIR, Trash, list_runs, prune, undo};
+use surfcontext_cli::sync::{self, repo_lock, reporter};
+
+/// `surf undo`
+pub fn handle_undo(run: Option<&str>, quiet: bool) -> Result<()> {
+    let repo_root = sync::find_repo_root()?;
+    let _lock = repo_lock::for_command(&repo_root, false, reporter::console(quiet).as_mut())?;
+    let mut trash = Trash::new(&repo_root, false);
+    let journal = undo(&repo_root, run, &mut trash)?;
+    if !quiet {
+        println!("{} sync run {}", "Restored".green().bold(), journal.id);
+        for entry in &journal.entries {
+            if entry.kind == EntryKind::New {
+                println!("  {} {}", "removed".yellow(), entry.path);
+            } else {
+                println!("  {} {}", "restored".green(), entry.path);
+            }
+        }
+        if let Some((id, count)) = trash.saved() {
+            println!(
+                "Trash: {count} item(s) saved to {TRASH_DIR}/{id}/ {}",
+                "(surf undo to redo)".dimmed()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// `surf trash list`
+pub fn handle_list() -> Result<()> {
+    let repo_root = sync::find_repo_root()?;
+    let runs = list_runs(&repo_root)?;
+    if runs.is_empty() {
+        println!("{}", "Trash is empty.".dimmed());
+    }
+    for journal in &runs {
+        println!("{} ({} items)", journal.id.bold(), journal.entries.len());
+        for entry in &journal.entries {
+            if entry.kind == EntryKind::New {
+                println!("  {} {}", entry.path, "(created)".dimmed());
+            } else {
+                println!("  {}", entry.path);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `surf trash prune`
+pub fn handle_prune(keep: usize, quiet: bool) -> Result<()> {
+    let repo_root = sync::find_repo_root()?;
+    let removed = prune(&repo_root, keep)?;
+    if !quiet {
+        if removed.is_empty() {
+            println!("{}", "Nothing to prune.".dimmed());
+        }
+        for id in &removed {
+            println!("  {} {id}", "Pruned".yellow());
+        }
+    }
+    Ok(())
+}
@@ -28,6 +28,17 @@ pub struct SurfConfig {
 
     #[serde(default)]
     pub ip_safety: Option<IpSafety>,
+
+    #[serde(default)]
+    pub sweep: SweepConfig,
+
+    #[serde(default)]
+    pub audit: AuditConfig,
+
+    /// Per-code severity overrides: `"error"`, `"warning"` or `"off"`, keyed by code
+    /// (`SC101`) or name (`path-reference`).
+    #[serde(default)]
+    pub diagnostics: HashMap<String, String>,
 }
 
 fn default_version() -> String {
@@ -105,20 +116,130 @@ pub struct PlatformGen {
     #[serde(default)]
     pub docs_dir: Option<String>,
 
+    #[serde(default)]
+    pub skills_dir: Option<String>,
+
+    #[serde(default)]
+    pub guides_dir: Option<String>,
+
     #[serde(default = "default_method")]
     pub method: String,
 
     #[serde(default)]
     pub root_context_method: Option<String>,
 
+    /// Executable (relative to the repo root) that reads CONTEXT.md on stdin and
+    /// writes the platform file to stdout. Takes precedence over `rootContextMethod`.
     #[serde(default)]
     pub root_context_script: Option<String>,
+
+    /// Seconds before `rootContextScript` is killed (default 30).
+    #[serde(default)]
+    pub root_context_script_timeout: Option<u64>,
+
+    /// Headings to drop or keep and text to rewrite in the generated file.
+    #[serde(default)]
+    pub sections: SectionRules,
+
+    /// More entries in the platform's link dir that belong to the tool itself and are never
+    /// swept, on top of the ones surf knows about.
+    #[serde(default)]
+    pub native: Vec<String>,
+}
+
+/// Declarative section filtering for a generated platform file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SectionRules {
+    /// Heading patterns to keep; when non-empty, every other section is dropped.
+    #[serde(default)]
+    pub include: Vec<String>,
+
+    /// Heading patterns whose sections are dropped.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// Rewrites applied to the `#` title.
+    #[serde(default)]
+    pub title: Vec<TextRewrite>,
+
+    /// Paragraphs whose first line starts with `find` are replaced wholesale.
+    #[serde(default)]
+    pub replace: Vec<TextRewrite>,
+}
+
+/// A find/replace pair; `find` is a regex when written as `/.../`.
+#[derive(Debug, Deserialize)]
+pub struct TextRewrite {
+    pub find: String,
+    pub replace: String,
+}
+
+impl Default for PlatformGen {
+    fn default() -> Self {
+        Self {
+            root_context: None,
+            agents_dir: None,
+            docs_dir: None,
+            skills_dir: None,
+            guides_dir: None,
+            method: default_method(),
+            root_context_method: None,
+            root_context_script: None,
+            root_context_script_timeout: None,
+            sections: SectionRules::default(),
+            native: Vec::new(),
+        }
+    }
 }
 
 fn default_method() -> String {
     "symlink".to_string()
 }
 
+/// Defensive sweep behaviour for orphaned files in platform dirs.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SweepConfig {
+    #[serde(default)]
+    pub mode: SweepMode,
+}
+
+/// What the defensive sweep does with an orphan.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SweepMode {
+    /// Move orphan content into `.context/`, merging or writing `.conflict` copies.
+    #[default]
+    Migrate,
+    /// Legacy behaviour: overwrite orphan files with a redirect stub and replace orphan
+    /// dirs with links. Destroys whatever was written there.
+    Redirect,
+    /// Only warn.
+    Report,
+}
+
+/// What the `.context/` audits read.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditConfig {
+    /// File extensions scanned under `.context/`, without the dot.
+    #[serde(default = "default_audit_extensions")]
+    pub extensions: Vec<String>,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            extensions: default_audit_extensions(),
+        }
+    }
+}
+
+fn default_audit_extensions() -> Vec<String> {
+    vec!["md".to_string(), "txt".to_string()]
+}
+
 /// Cross-repo sync section (e.g. "skills", "docs").
 #[derive(Debug, Deserialize)]
 pub struct SyncSection {
@@ -183,6 +304,9 @@ pub fn load_config(repo_root: &Path) -> Result<SurfConfig> {
             sync: HashMap::new(),
             discovery_order: Vec::new(),
             ip_safety: None,
+            sweep: SweepConfig::default(),
+            audit: AuditConfig::default(),
+            diagnostics: HashMap::new(),
         })
     }
 }
@@ -243,6 +367,7 @@ mod tests {
             Some("CLAUDE.md")
         );
         assert_eq!(config.generation["codex"].method, "template-copy");
+        assert!(config.generation["codex"].skills_dir.is_none());
         assert!(config.sync.contains_key("skills"));
         assert_eq!(config.sync["skills"].targets.len(), 2);
         assert_eq!(
@@ -269,5 +394,14 @@ mod tests {
         assert_eq!(config.canonical.docs_dir, ".context/docs");
         assert_eq!(config.canonical.skills_dir, ".context/skills");
         assert_eq!(config.canonical.guides_dir, ".context/guides");
+        assert_eq!(config.sweep.mode, SweepMode::Migrate);
+    }
+
+    #[test]
+    fn test_sweep_mode() {
+        let json = r#"{ "sweep": { "mode": "report" } }"#;
+        let config: SurfConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.sweep.mode, SweepMode::Report);
+        assert!(serde_json::from_str::<SurfConfig>(r#"{ "sweep": { "mode": "nuke" } }"#).is_err());
     }
 }
@@ -3,6 +3,11 @@ use colored::Colorize;
 use std::fs;
 use std::path::Path;
 
+use crate::config::SurfConfig;
+use crate::sync::generate::{generate_claude_md, stamp};
+use crate::sync::platforms::PlatformRegistry;
+use crate::sync::rewrite::PathRewriter;
+
 /// Repo template types.
 #[derive(Debug, Clone, clap::ValueEnum)]
 pub enum RepoType {
@@ -43,8 +48,8 @@ pub fn init_repo(
     fs::create_dir_all(&context_dir)?;
 
     // Write surfcontext.json
-    let config = generate_surfcontext_json(&project_name, &repo_type, minimal);
-    fs::write(target.join("surfcontext.json"), config)?;
+    let config_json = generate_surfcontext_json(&project_name, &repo_type, minimal);
+    fs::write(target.join("surfcontext.json"), &config_json)?;
     if !quiet {
         println!("  {} surfcontext.json", "Created".green());
     }
@@ -99,9 +104,11 @@ pub fn init_repo(
         }
     }
 
-    // Generate CLAUDE.md from CONTEXT.md
+    // Generate CLAUDE.md from CONTEXT.md, with the same path rewrites `surf sync` uses
+    let config: SurfConfig = serde_json::from_str(&config_json)
+        .context("Generated surfcontext.json is invalid")?;
     let context_content = fs::read_to_string(target.join("CONTEXT.md"))?;
-    let claude_md = generate_claude_md_from_context(&context_content);
+    let claude_md = generate_claude_md_from_context(&config, &context_content);
     fs::write(target.join("CLAUDE.md"), claude_md)?;
     if !quiet {
         println!("  {} CLAUDE.md", "Generated".green());
@@ -117,7 +124,13 @@ pub fn init_repo(
     Ok(())
 }
 
-fn generate_surfcontext_json(name: &str, repo_type: &RepoType, minimal: bool) -> String {
+pub(crate) fn generate_surfcontext_json(name: &str, repo_type: &RepoType, minimal: bool) -> String {
+    // The project name ends up inside JSON strings, so it is serialized, not pasted.
+    let json_string = |text: &str| serde_json::to_string(text).expect("strings serialize");
+    let owner = json_string(name);
+    let persona_replacement =
+        json_string(&format!("This is the strategic command center for {name}."));
+
     let platforms = match repo_type {
         RepoType::Product => r#"["claude"]"#,
         RepoType::CommandCenter => r#"["claude", "codex"]"#,
@@ -125,7 +138,7 @@ fn generate_surfcontext_json(name: &str, repo_type: &RepoType, minimal: bool) ->
 
     let plans_line = match repo_type {
         RepoType::CommandCenter => {
-            r#"
+            r#",
     "checkpointsDir": "plans/sessions",
     "plansDir": "plans""#
         }
@@ -140,6 +153,7 @@ fn generate_surfcontext_json(name: &str, repo_type: &RepoType, minimal: bool) ->
       "rootContextMethod": "sed-copy"
     }
   }"#
+        .to_string()
     } else {
         match repo_type {
             RepoType::Product => {
@@ -152,22 +166,35 @@ fn generate_surfcontext_json(name: &str, repo_type: &RepoType, minimal: bool) ->
       "rootContextMethod": "sed-copy"
     }
   }"#
+                .to_string()
             }
-            RepoType::CommandCenter => {
-                r#"{
-    "claude": {
+            // AGENTS.md drops the Claude persona and slash commands; see sync/sections.rs.
+            RepoType::CommandCenter => format!(
+                r#"{{
+    "claude": {{
       "rootContext": "CLAUDE.md",
       "agentsDir": ".claude/agents",
       "docsDir": ".claude/docs",
       "method": "symlink",
       "rootContextMethod": "sed-copy"
-    },
-    "codex": {
+    }},
+    "codex": {{
       "rootContext": "AGENTS.md",
-      "method": "template-copy"
-    }
-  }"#
-            }
+      "method": "template-copy",
+      "sections": {{
+        "exclude": ["Your Expertise", "Commands"],
+        "title": [
+          {{ "find": "/Strategic (.*Advisor Agent)/", "replace": "Strategy $1" }},
+          {{ "find": "Advisor Agent", "replace": "Repository" }}
+        ],
+        "replace": [{{
+          "find": "You are the strategic advisor for",
+          "replace": {persona_replacement}
+        }}]
+      }}
+    }}
+  }}"#
+            ),
         }
     };
 
@@ -194,7 +221,7 @@ fn generate_surfcontext_json(name: &str, repo_type: &RepoType, minimal: bool) ->
   ],
   "ipSafety": {{
     "enabled": true,
-    "owner": "{name}",
+    "owner": {owner},
     "noAiCoAuthor": true,
     "noSecrets": true
   }}
@@ -293,20 +320,51 @@ fn generate_example_doc(name: &str) -> String {
     )
 }
 
-/// Simple CLAUDE.md generation (same as generate.rs but standalone for init).
-fn generate_claude_md_from_context(content: &str) -> String {
-    let mut output = String::with_capacity(content.len() + 200);
-    output.push_str("<!-- SurfContext ARDS v3.0 — surfcontext.org -->\n");
-    output.push_str("<!-- GENERATED — edit CONTEXT.md, then run scripts/surfcontext-sync.sh -->\n");
-
-    let transformed = content
-        .replace(".context/docs/", ".claude/docs/")
-        .replace(".context/agents/", ".claude/agents/")
-        .replace(".context/skills/", ".claude/skills/")
-        .replace(".context/guides/", ".claude/guides/")
-        .replace(".context/queue.md", ".claude/queue.md")
-        .replace(".context/", ".claude/");
-
-    output.push_str(&transformed);
-    output
+/// CLAUDE.md generation for a freshly scaffolded repo, matching what `surf sync` produces.
+fn generate_claude_md_from_context(config: &SurfConfig, content: &str) -> String {
+    let registry = PlatformRegistry::builtin();
+    let link_dir = registry
+        .get("claude")
+        .and_then(|g| g.link_dir())
+        .unwrap_or(".claude");
+    let rewriter = PathRewriter::for_platform(config, config.generation.get("claude"), link_dir);
+    stamp(&generate_claude_md(content, &rewriter, Some(link_dir)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_configs_parse() {
+        for repo_type in [RepoType::Product, RepoType::CommandCenter] {
+            for minimal in [false, true] {
+                let json = generate_surfcontext_json("demo", &repo_type, minimal);
+                let config: SurfConfig = serde_json::from_str(&json)
+                    .unwrap_or_else(|e| panic!("{repo_type:?} minimal={minimal}: {e}"));
+                assert!(config.generation.contains_key("claude"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_generated_config_escapes_the_project_name() {
+        let name = r#"Acme "Labs" \ Co"#;
+        let json = generate_surfcontext_json(name, &RepoType::CommandCenter, false);
+        let config: SurfConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            config.generation["codex"].sections.replace[0].replace,
+            format!("This is the strategic command center for {name}.")
+        );
+        assert_eq!(config.ip_safety.unwrap().owner.as_deref(), Some(name));
+    }
+
+    #[test]
+    fn test_init_claude_md_uses_config_rewrites() {
+        let json = generate_surfcontext_json("demo", &RepoType::Product, false);
+        let config: SurfConfig = serde_json::from_str(&json).unwrap();
+        let out = generate_claude_md_from_context(&config, "See `.context/docs/overview.md`.\n");
+        assert!(out.contains("`.claude/docs/overview.md`"));
+        assert!(out.contains("GENERATED — edit CONTEXT.md, then run surf sync"));
+    }
 }
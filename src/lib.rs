@@ -0,0 +1,39 @@
+//! SurfContext as a library: load `surfcontext.json`, run the sync pipeline and render
+//! platform files (CLAUDE.md, AGENTS.md, ...) from your own tools.
+//!
+//! The `surf` binary is a thin frontend over this crate. Sync never prints on its own: it
+//! reports through a [`Reporter`], so editor extensions and bots can collect progress,
+//! actions and diagnostics as events, and every command returns what it did as data (a
+//! [`SyncReport`], a clean or backport report, ...) for the caller to render.
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use surfcontext_cli::{SyncOpts, load_config, run_sync_at, sync::reporter::Silent};
+//!
+//! let root = Path::new("path/to/repo");
+//! let config = load_config(root)?;
+//! let opts = SyncOpts {
+//!     dry_run: true,
+//!     local_only: true,
+//!     quiet: true,
+//!     ..Default::default()
+//! };
+//! let report = run_sync_at(root, &config, &opts, Box::new(Silent))?;
+//! for action in &report.actions {
+//!     println!("{action}");
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod config;
+pub mod init;
+pub mod sync;
+
+#[cfg(test)]
+pub(crate) mod test_util;
+
+pub use config::{SurfConfig, load_config};
+pub use sync::generate::render_platform;
+pub use sync::platforms::{PlatformGenerator, PlatformRegistry};
+pub use sync::report::{Action, ActionKind, Code, Diagnostic, Stage};
+pub use sync::{Reporter, SyncOpts, SyncReport, run_sync, run_sync_at};
@@ -3,9 +3,9 @@ use clap::{Parser, Subcommand};
 use colored::Colorize;
 
 mod build;
-mod config;
-mod init;
-mod sync;
+mod cli;
+
+use surfcontext_cli::{init, sync};
 
 #[derive(Parser)]
 #[command(name = "surf", version, about = "CLI for the SurfContext/ARDS v3.0 standard")]
@@ -44,6 +44,113 @@ enum Commands {
         /// Skip cross-repo sync
         #[arg(long)]
         local_only: bool,
+
+        /// Print a unified diff of every file that changes (pair with --dry-run to preview)
+        #[arg(long)]
+        diff: bool,
+
+        /// Keep running and re-sync whatever a change affects
+        #[arg(long, conflicts_with = "format")]
+        watch: bool,
+
+        /// Exit 1 on any warning that surfcontext.json does not suppress, not only on errors
+        #[arg(long, conflicts_with = "watch")]
+        strict: bool,
+
+        /// Report format: text, or json/ndjson for tooling (implies --quiet)
+        #[arg(long, value_enum, default_value = "text")]
+        format: sync::report::OutputFormat,
+    },
+
+    /// Verify the repo is in sync without writing anything (for CI)
+    ///
+    /// Exit codes: 0 up to date, 1 errors, 2 out of date, 3 warnings only.
+    Check {
+        /// Skip cross-repo targets
+        #[arg(long)]
+        local_only: bool,
+
+        /// Treat warnings as errors (exit 1)
+        #[arg(long)]
+        strict: bool,
+    },
+
+    /// Move hand edits in generated files (CLAUDE.md, AGENTS.md, ...) back into CONTEXT.md
+    Backport {
+        /// Show which hunks would be back-ported without writing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Only back-port this platform's output (e.g. "claude")
+        #[arg(long)]
+        platform: Option<String>,
+    },
+
+    /// List platform-dir paths (.claude/docs/...) referenced from .context/ and CONTEXT.md
+    ///
+    /// Exits 1 while any are left.
+    #[command(args_conflicts_with_subcommands = true)]
+    Audit {
+        #[command(subcommand)]
+        check: Option<AuditCheck>,
+
+        /// Rewrite them to their canonical .context/ paths, showing a diff per file
+        #[arg(long)]
+        fix: bool,
+
+        /// With --fix: show the diffs without writing anything
+        #[arg(long, requires = "fix")]
+        dry_run: bool,
+
+        /// With --fix: also rewrite inside fenced code blocks
+        #[arg(long, requires = "fix")]
+        include_code: bool,
+    },
+
+    /// Move a context document or directory, rewriting every reference to it, then sync
+    ///
+    /// Rewrites backticked paths, links and `related:` front matter in CONTEXT.md and
+    /// .context/, plus discoveryOrder and cross-repo include/exclude in surfcontext.json.
+    Mv {
+        /// Path to move
+        old: String,
+
+        /// Where to move it
+        new: String,
+
+        /// List the move and every rewrite without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Read and edit the shared task queue (.context/queue.md)
+    Queue {
+        #[command(subcommand)]
+        action: QueueAction,
+    },
+
+    /// Remove everything `surf sync` generated (leaves .context/ alone)
+    Clean {
+        /// List what would be removed without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Also remove files pushed to cross-repo targets
+        #[arg(long)]
+        cross_repo: bool,
+    },
+
+    /// Restore files that a sync run removed or overwrote
+    Undo {
+        /// Trash run to restore (default: the most recent)
+        #[arg(long)]
+        run: Option<String>,
+    },
+
+    /// Inspect or prune the sync trash (.context/.surf-trash/)
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
     },
 
     /// Scaffold a new ARDS repo
@@ -95,6 +202,93 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum AuditCheck {
+    /// List .context/docs and .context/guides documents nothing links to from the root
+    /// context, discoveryOrder, agents or skills
+    Orphans,
+}
+
+#[derive(Subcommand)]
+enum QueueAction {
+    /// Add a task
+    Add {
+        /// Task title
+        #[arg(required = true)]
+        title: Vec<String>,
+
+        /// Who owns it
+        #[arg(long)]
+        owner: Option<String>,
+
+        /// Priority, e.g. high or p1
+        #[arg(long)]
+        priority: Option<String>,
+
+        /// Tag (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+    },
+    /// List tasks
+    List {
+        #[arg(long, value_enum)]
+        status: Option<sync::queue::Status>,
+
+        #[arg(long)]
+        owner: Option<String>,
+
+        #[arg(long)]
+        priority: Option<String>,
+
+        #[arg(long)]
+        tag: Option<String>,
+
+        /// Output format: text, or json/ndjson for tooling
+        #[arg(long, value_enum, default_value = "text")]
+        format: sync::report::OutputFormat,
+    },
+    /// Mark a task in progress (refused if someone else already is on it)
+    Start {
+        /// Task ID, e.g. Q-3
+        id: String,
+
+        /// Take the task as this owner
+        #[arg(long)]
+        owner: Option<String>,
+    },
+    /// Mark a task done
+    Done {
+        /// Task ID, e.g. Q-3
+        id: String,
+    },
+    /// Set a task's owner
+    Assign {
+        /// Task ID, e.g. Q-3
+        id: String,
+
+        /// New owner
+        owner: String,
+    },
+    /// Remove a task and the notes under it
+    Rm {
+        /// Task ID, e.g. Q-3
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TrashAction {
+    /// List trash runs and what each one saved
+    List,
+
+    /// Delete old trash runs
+    Prune {
+        /// Number of most recent runs to keep
+        #[arg(long, default_value_t = 5)]
+        keep: usize,
+    },
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
@@ -104,19 +298,139 @@ fn main() -> Result<()> {
             verbose,
             force,
             local_only,
+            diff,
+            watch,
+            strict,
+            format,
         } => {
+            let text = format == sync::report::OutputFormat::Text;
             let opts = sync::SyncOpts {
                 dry_run,
                 verbose,
                 force,
                 local_only,
+                quiet: cli.quiet || !text,
+                diff,
+            };
+            if watch {
+                sync::watch::run_watch(&opts)?;
+            } else {
+                let report = sync::run_sync(&opts)?;
+                if !text || !cli.quiet {
+                    cli::report::print(&report, format);
+                }
+                if report.failed(strict) {
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Check { local_only, strict } => {
+            let opts = cli::check::CheckOpts {
+                local_only,
+                strict,
                 quiet: cli.quiet,
             };
-            let report = sync::run_sync(&opts)?;
-            if !cli.quiet {
-                report.print_summary();
+            let code = cli::check::run_check(&opts)?;
+            if code != sync::check::EXIT_OK {
+                std::process::exit(code);
             }
         }
+        Commands::Backport { dry_run, platform } => {
+            let opts = sync::backport::BackportOpts {
+                dry_run,
+                platform,
+                quiet: cli.quiet,
+            };
+            cli::backport::run_backport(&opts)?;
+        }
+        Commands::Audit {
+            check: Some(AuditCheck::Orphans),
+            ..
+        } => {
+            if cli::audit::run_orphans(cli.quiet)? > 0 {
+                std::process::exit(1);
+            }
+        }
+        Commands::Audit {
+            check: None,
+            fix,
+            dry_run,
+            include_code,
+        } => {
+            let opts = sync::audit::AuditOpts {
+                fix,
+                dry_run,
+                include_code,
+                quiet: cli.quiet,
+            };
+            if cli::audit::run_audit(&opts)? > 0 {
+                std::process::exit(1);
+            }
+        }
+        Commands::Mv { old, new, dry_run } => {
+            let opts = sync::mv::MoveOpts {
+                dry_run,
+                quiet: cli.quiet,
+            };
+            cli::mv::run_mv(&old, &new, &opts)?;
+        }
+        Commands::Queue { action } => match action {
+            QueueAction::Add {
+                title,
+                owner,
+                priority,
+                tags,
+            } => {
+                let task = sync::queue::NewTask {
+                    title: title.join(" "),
+                    owner,
+                    priority,
+                    tags,
+                };
+                cli::queue::handle_add(&task, cli.quiet)?;
+            }
+            QueueAction::List {
+                status,
+                owner,
+                priority,
+                tag,
+                format,
+            } => {
+                let filter = sync::queue::Filter {
+                    status,
+                    owner,
+                    priority,
+                    tag,
+                };
+                cli::queue::handle_list(&filter, format)?;
+            }
+            QueueAction::Start { id, owner } => {
+                cli::queue::handle_start(&id, owner.as_deref(), cli.quiet)?
+            }
+            QueueAction::Done { id } => cli::queue::handle_done(&id, cli.quiet)?,
+            QueueAction::Assign { id, owner } => {
+                cli::queue::handle_assign(&id, &owner, cli.quiet)?
+            }
+            QueueAction::Rm { id } => cli::queue::handle_rm(&id, cli.quiet)?,
+        },
+        Commands::Clean {
+            dry_run,
+            cross_repo,
+        } => {
+            let opts = sync::clean::CleanOpts {
+                dry_run,
+                cross_repo,
+                quiet: cli.quiet,
+            };
+            cli::clean::run_clean(&opts)?;
+        }
+        Commands::Undo { run } => {
+            cli::trash::handle_undo(run.as_deref(), cli.quiet)?;
+        }
+        Commands::Trash { action } => match action {
+            TrashAction::List => cli::trash::handle_list()?,
+            TrashAction::Prune { keep } => cli::trash::handle_prune(keep, cli.quiet)?,
+        },
         Commands::Init {
             path,
             r#type,
@@ -0,0 +1,167 @@
+//! Writes that never leave a half-written file behind.
+//!
+//! Every file sync writes is first written to a temp file next to its destination and then
+//! renamed over it. A rename within one directory is atomic, so a reader (an agent loading
+//! CLAUDE.md, a concurrent `surf sync`) sees either the old file or the new one, and a crash
+//! mid-write leaves the old file untouched.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Marker in temp file names, so leftovers from a crashed run are recognizable.
+pub const TEMP_MARKER: &str = ".surf-tmp.";
+
+/// Replace `path` with `contents`.
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    replace_with(path, |tmp| {
+        let mut file = fs::File::create(tmp)?;
+        file.write_all(contents.as_ref())?;
+        file.sync_all()
+    })
+    .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Replace `dst` with a copy of `src`, permissions included.
+pub fn copy(src: &Path, dst: &Path) -> Result<()> {
+    replace_with(dst, |tmp| {
+        fs::copy(src, tmp)?;
+        fs::File::open(tmp)?.sync_all()
+    })
+    .with_context(|| format!("Failed to copy {} -> {}", src.display(), dst.display()))
+}
+
+/// Replace `dst` with a hardlink to `src`.
+pub fn hard_link(src: &Path, dst: &Path) -> Result<()> {
+    replace_with(dst, |tmp| fs::hard_link(src, tmp)).with_context(|| {
+        format!(
+            "Failed to hardlink {} -> {} (hardlinks cannot cross filesystems; try method \"copy\")",
+            dst.display(),
+            src.display()
+        )
+    })
+}
+
+/// Replace `link` (a symlink, a file, or nothing) with a symlink to `target`. A directory at
+/// `link` cannot be replaced in one step and has to be removed first.
+#[cfg(unix)]
+pub fn symlink(target: &Path, link: &Path) -> Result<()> {
+    replace_with(link, |tmp| std::os::unix::fs::symlink(target, tmp))
+        .with_context(|| format!("Failed to create symlink {} -> {}", link.display(), target.display()))
+}
+
+/// Replace `path` (a directory, a file, or nothing) with a directory built by `create` at a
+/// temp path. Two renames cannot swap directories, so the old one is removed just before the
+/// new one moves in; a reader may briefly see neither, but never a half-built tree.
+pub fn replace_dir(path: &Path, create: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+    let tmp = temp_path(path);
+    let result = create(&tmp).and_then(|()| {
+        match fs::symlink_metadata(path) {
+            Ok(meta) if meta.is_dir() => fs::remove_dir_all(path)?,
+            Ok(_) => fs::remove_file(path)?,
+            Err(_) => {}
+        }
+        fs::rename(&tmp, path)?;
+        Ok(())
+    });
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&tmp);
+    }
+    result.with_context(|| format!("Failed to replace {}", path.display()))
+}
+
+/// Whether `name` is one of our temp files.
+pub fn is_temp(name: &str) -> bool {
+    name.starts_with('.') && name.contains(TEMP_MARKER)
+}
+
+/// Create `path`'s replacement at a temp path with `create`, then rename it into place.
+fn replace_with(path: &Path, create: impl FnOnce(&Path) -> std::io::Result<()>) -> std::io::Result<()> {
+    let tmp = temp_path(path);
+    let result = create(&tmp).and_then(|()| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// A sibling of `path` unique to this process and call, e.g. `.CLAUDE.md.surf-tmp.812.0`.
+fn temp_path(path: &Path) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{name}{TEMP_MARKER}{}.{n}", std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestDir;
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_write_and_copy_replace_without_leftovers() {
+        let dir = TestDir::new("surfcontext-test-atomic-write");
+        let file = dir.join("CLAUDE.md");
+        write(&file, "old\n").unwrap();
+        write(&file, "new\n").unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "new\n");
+
+        let copy_dst = dir.join("copy.md");
+        copy(&file, &copy_dst).unwrap();
+        assert_eq!(fs::read_to_string(&copy_dst).unwrap(), "new\n");
+        assert_eq!(names(&dir), vec!["CLAUDE.md", "copy.md"]);
+
+        assert!(write(&dir.join("missing/CLAUDE.md"), "x").is_err());
+        assert_eq!(names(&dir), vec!["CLAUDE.md", "copy.md"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_replaces_existing_link() {
+        let dir = TestDir::new("surfcontext-test-atomic-symlink");
+        let link = dir.join("docs");
+        symlink(Path::new("a"), &link).unwrap();
+        symlink(Path::new("b"), &link).unwrap();
+        assert_eq!(fs::read_link(&link).unwrap(), Path::new("b"));
+        assert_eq!(names(&dir), vec!["docs"]);
+    }
+
+    #[test]
+    fn test_replace_dir_swaps_in_a_complete_tree() {
+        let dir = TestDir::new("surfcontext-test-atomic-replace-dir");
+        let target = dir.join("docs");
+        fs::create_dir_all(target.join("old")).unwrap();
+        replace_dir(&target, |tmp| {
+            fs::create_dir(tmp)?;
+            Ok(fs::write(tmp.join("new.md"), "x")?)
+        })
+        .unwrap();
+        assert_eq!(names(&target), vec!["new.md"]);
+
+        let failed = replace_dir(&target, |tmp| {
+            fs::create_dir(tmp)?;
+            anyhow::bail!("failed mid-build")
+        });
+        assert!(failed.is_err());
+        assert_eq!(names(&target), vec!["new.md"]);
+        assert_eq!(names(&dir), vec!["docs"]);
+    }
+
+    #[test]
+    fn test_is_temp() {
+        let name = temp_path(Path::new("x/CLAUDE.md"));
+        assert!(is_temp(&name.file_name().unwrap().to_string_lossy()));
+        assert!(!is_temp("CLAUDE.md"));
+    }
+}
@@ -0,0 +1,597 @@
+//! Platform path references in `.context/` sources: the sync-time audit and `surf audit`.
+//!
+//! CONTEXT.md and `.context/` files are the source of truth for every platform, so they
+//! should only point at canonical paths. A `.claude/docs/...` reference works for Claude and
+//! breaks for everyone else. Sync warns about them; `surf audit --fix` rewrites them to the
+//! canonical path the link came from, leaving fenced code blocks alone unless asked.
+//!
+//! The same scan also checks that referenced paths exist, and `surf audit orphans` walks it
+//! as a graph to find documents nothing leads an agent to.
+
+use anyhow::Result;
+use colored::Colorize;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::diff;
+use super::directives::{self, is_fence};
+use super::references::{self, PathIndex};
+use super::report::{Code, Diagnostic, Stage};
+use super::reporter::Reporter;
+use super::rewrite::PathRewriter;
+use super::trash::Trash;
+use super::{SyncReport, atomic, link_farms};
+use crate::config::SurfConfig;
+
+/// Directories under `.context/` the audits read.
+const SCAN_DIRS: [&str; 4] = ["docs", "agents", "guides", "skills"];
+
+pub struct AuditOpts {
+    /// Rewrite references instead of only listing them.
+    pub fix: bool,
+    /// With `fix`: show the diff, write nothing.
+    pub dry_run: bool,
+    /// With `fix`: rewrite inside fenced code blocks too.
+    pub include_code: bool,
+    pub quiet: bool,
+}
+
+/// A platform path found in a source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    /// Repo-relative path of the file.
+    pub file: String,
+    pub line: usize,
+    /// The platform path prefix that matched, e.g. `.claude/docs/`.
+    pub pattern: String,
+    /// Inside a fenced code block.
+    pub in_code: bool,
+}
+
+/// Every file the audits read, repo-relative: matching files under `.context/{docs,agents,
+/// guides,skills}` in path order, then the root context file.
+pub fn sources(repo_root: &Path, config: &SurfConfig) -> Vec<PathBuf> {
+    let extensions = &config.audit.extensions;
+    let mut files = Vec::new();
+    for subdir in SCAN_DIRS {
+        let dir = repo_root.join(".context").join(subdir);
+        if !dir.is_dir() {
+            continue;
+        }
+        for entry in walkdir::WalkDir::new(&dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .filter(|e| {
+                e.path()
+                    .extension()
+                    .is_some_and(|ext| extensions.iter().any(|x| ext == x.as_str()))
+            })
+        {
+            if let Ok(rel) = entry.path().strip_prefix(repo_root) {
+                files.push(rel.to_path_buf());
+            }
+        }
+    }
+    if repo_root.join(&config.canonical.root_context).is_file() {
+        files.push(PathBuf::from(&config.canonical.root_context));
+    }
+    files
+}
+
+/// Platform path prefixes to look for, each with the canonical path it stands for:
+/// every link-farm link (`.claude/docs/` -> `.context/docs/`) and each platform's queue.
+pub fn platform_paths(config: &SurfConfig) -> Result<Vec<(String, String)>> {
+    let mut paths = Vec::new();
+    for farm in link_farms(config) {
+        for (canonical, link) in &farm.links {
+            paths.push((format!("{link}/"), format!("{canonical}/")));
+        }
+        paths.push((
+            format!("{}/queue.md", farm.root),
+            ".context/queue.md".to_string(),
+        ));
+    }
+    Ok(paths)
+}
+
+/// Platform path references in `content`, one per line and pattern.
+fn scan(file: &str, content: &str, paths: &[(String, String)]) -> Vec<Reference> {
+    let mut found = Vec::new();
+    let mut in_fence = false;
+    for (line_num, line) in content.lines().enumerate() {
+        if is_fence(line) {
+            in_fence = !in_fence;
+        }
+        for (pattern, _) in paths {
+            if line.contains(pattern.as_str()) {
+                found.push(Reference {
+                    file: file.to_string(),
+                    line: line_num + 1,
+                    pattern: pattern.clone(),
+                    in_code: in_fence,
+                });
+            }
+        }
+    }
+    found
+}
+
+/// Every platform path reference in the audited sources, grouped by file with its content.
+fn find_references(
+    repo_root: &Path,
+    config: &SurfConfig,
+    paths: &[(String, String)],
+) -> BTreeMap<String, (String, Vec<Reference>)> {
+    let mut by_file = BTreeMap::new();
+    for rel in sources(repo_root, config) {
+        let Ok(content) = fs::read_to_string(repo_root.join(&rel)) else {
+            continue;
+        };
+        let file = rel.to_string_lossy().replace('\\', "/");
+        let found = scan(&file, &content, paths);
+        if !found.is_empty() {
+            by_file.insert(file, (content, found));
+        }
+    }
+    by_file
+}
+
+/// Every platform path reference in the audited sources, in file order.
+pub fn platform_references(repo_root: &Path, config: &SurfConfig) -> Result<Vec<Reference>> {
+    let paths = platform_paths(config)?;
+    Ok(find_references(repo_root, config, &paths)
+        .into_values()
+        .flat_map(|(_, found)| found)
+        .collect())
+}
+
+/// Scan .context/ docs for accidental platform-dir path references.
+/// Files that reference .claude/docs/, .codex/agents/, etc. are likely mistakes —
+/// the source of truth is .context/ and all references should use .context/ paths.
+pub fn audit_path_references(
+    repo_root: &Path,
+    config: &SurfConfig,
+    report: &mut SyncReport,
+) -> Result<()> {
+    if !repo_root.join(".context").exists() {
+        return Ok(());
+    }
+
+    let violations = platform_references(repo_root, config)?;
+
+    if violations.is_empty() {
+        report.progress(format!(
+            "  {}",
+            "Clean — no platform path references in .context/ files".green()
+        ));
+    } else {
+        for Reference {
+            file,
+            line,
+            pattern,
+            ..
+        } in &violations
+        {
+            let msg =
+                format!("{file}:{line} references `{pattern}` — should use .context/ path instead");
+            report.warn(
+                Diagnostic::new(Code::PlatformPathReference, Stage::Audit, msg).path(file.clone()),
+            );
+        }
+        report.progress(format!(
+            "  {}",
+            "Run `surf audit --fix` to rewrite them.".dimmed()
+        ));
+    }
+
+    Ok(())
+}
+
+/// A path a source file points at that does not exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenReference {
+    pub file: String,
+    pub line: usize,
+    /// The path as written.
+    pub raw: String,
+    /// Closest existing path, repo-relative, for a likely rename.
+    pub suggestion: Option<String>,
+}
+
+impl std::fmt::Display for BrokenReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{} points at `{}`, which does not exist",
+            self.file, self.line, self.raw
+        )?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (moved to `{suggestion}`?)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Every backticked path and relative link in the audited sources whose target is missing.
+pub fn find_broken_references(repo_root: &Path, config: &SurfConfig) -> Vec<BrokenReference> {
+    let mut broken = Vec::new();
+    let mut index = None;
+    for rel in sources(repo_root, config) {
+        let Ok(content) = fs::read_to_string(repo_root.join(&rel)) else {
+            continue;
+        };
+        for path_ref in references::extract(&rel, &content) {
+            if repo_root.join(&path_ref.target).exists()
+                || !references::is_checkable(repo_root, &path_ref)
+            {
+                continue;
+            }
+            let index = index.get_or_insert_with(|| PathIndex::build(repo_root, config));
+            broken.push(BrokenReference {
+                file: rel.to_string_lossy().replace('\\', "/"),
+                line: path_ref.line,
+                suggestion: index.suggest(&path_ref.target),
+                raw: path_ref.raw,
+            });
+        }
+    }
+    broken
+}
+
+/// Check that the paths CONTEXT.md and `.context/` documents point at exist. Agents that
+/// follow a dead pointer lose the whole session to it.
+pub fn audit_broken_references(
+    repo_root: &Path,
+    config: &SurfConfig,
+    report: &mut SyncReport,
+) -> Result<()> {
+    let broken = find_broken_references(repo_root, config);
+    if broken.is_empty() {
+        report.progress(format!(
+            "  {}",
+            "Clean — every referenced path exists".green()
+        ));
+    }
+    for reference in broken {
+        let file = reference.file.clone();
+        report.warn(
+            Diagnostic::new(Code::BrokenReference, Stage::Audit, reference.to_string()).path(file),
+        );
+    }
+    Ok(())
+}
+
+/// What `fix` rewrote (or, in dry-run mode, would rewrite).
+#[derive(Debug, Default)]
+pub struct FixReport {
+    /// Files rewritten.
+    pub files: Vec<String>,
+    /// References rewritten.
+    pub fixed: usize,
+    /// References still there afterwards: the ones in code blocks, without `include_code`.
+    pub remaining: Vec<Reference>,
+}
+
+/// Rewrite every platform path reference to its canonical path, giving `reporter` a diff
+/// per file.
+pub fn fix(
+    repo_root: &Path,
+    config: &SurfConfig,
+    opts: &AuditOpts,
+    trash: &mut Trash,
+    reporter: &mut dyn Reporter,
+) -> Result<FixReport> {
+    let paths = platform_paths(config)?;
+    let mut rewriter = PathRewriter::from_pairs(paths.clone());
+    if opts.include_code {
+        rewriter = rewriter.in_code_blocks();
+    }
+
+    let mut report = FixReport::default();
+    for (file, (content, found)) in find_references(repo_root, config, &paths) {
+        let rewritten = rewriter.apply(&content);
+        let remaining = scan(&file, &rewritten, &paths);
+        if rewritten != content {
+            for line in diff::change(&file, Some(content.as_bytes()), rewritten.as_bytes(), "  ") {
+                reporter.progress(&line);
+            }
+            if !opts.dry_run {
+                let path = repo_root.join(&file);
+                trash.preserve(&path)?;
+                atomic::write(&path, &rewritten)?;
+            }
+            report.files.push(file);
+            report.fixed += found.len().saturating_sub(remaining.len());
+        }
+        report.remaining.extend(remaining);
+    }
+    Ok(report)
+}
+
+/// A document in `.context/docs` or `.context/guides` that no chain of references reaches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Orphan {
+    /// Repo-relative path.
+    pub path: String,
+    pub size: u64,
+    /// Whole days since the last modification.
+    pub age_days: Option<u64>,
+}
+
+/// Documents under `.context/docs` and `.context/guides` that an agent cannot find by
+/// following references from its entry points: the root context file (with everything it
+/// `surf:include`s), `discoveryOrder`, and every agent and skill file. References are the
+/// backticked paths and links `references::extract` finds; a directory reference does not
+/// count as a reference to everything in it.
+pub fn find_orphans(repo_root: &Path, config: &SurfConfig) -> Result<Vec<Orphan>> {
+    let documents: BTreeSet<PathBuf> = sources(repo_root, config).into_iter().collect();
+    let in_dir = |path: &Path, dirs: &[&str]| {
+        dirs.iter()
+            .any(|dir| path.starts_with(Path::new(".context").join(dir)))
+    };
+
+    let root_context = PathBuf::from(&config.canonical.root_context);
+    let mut roots = vec![root_context.clone()];
+    if let Ok(content) = fs::read_to_string(repo_root.join(&root_context))
+        && let Ok(expanded) =
+            directives::expand_includes(repo_root, &config.canonical.root_context, &content)
+    {
+        roots.extend(expanded.includes);
+    }
+    roots.extend(config.discovery_order.iter().map(PathBuf::from));
+    roots.extend(
+        documents
+            .iter()
+            .filter(|doc| in_dir(doc, &["agents", "skills"]))
+            .cloned(),
+    );
+
+    // Platform paths (`.claude/docs/x.md`) lead to the canonical file all the same.
+    let platform_paths = platform_paths(config)?;
+    let canonical = |target: PathBuf| {
+        let text = target.to_string_lossy().replace('\\', "/");
+        platform_paths
+            .iter()
+            .find_map(|(link, canonical)| {
+                text.strip_prefix(link.as_str())
+                    .map(|rest| PathBuf::from(format!("{canonical}{rest}")))
+            })
+            .unwrap_or(target)
+    };
+
+    let mut reached: BTreeSet<PathBuf> = BTreeSet::new();
+    let mut queue: VecDeque<PathBuf> = roots.iter().cloned().collect();
+    while let Some(path) = queue.pop_front() {
+        if !reached.insert(path.clone()) {
+            continue;
+        }
+        // Only follow references out of documents, not out of the code they point at.
+        if !documents.contains(&path) && !roots.contains(&path) {
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(repo_root.join(&path)) else {
+            continue;
+        };
+        for path_ref in references::extract(&path, &content) {
+            let target = canonical(path_ref.target);
+            if !reached.contains(&target) {
+                queue.push_back(target);
+            }
+        }
+    }
+
+    let now = SystemTime::now();
+    let mut orphans = Vec::new();
+    for doc in &documents {
+        if !in_dir(doc, &["docs", "guides"]) || reached.contains(doc) {
+            continue;
+        }
+        let meta = fs::metadata(repo_root.join(doc))?;
+        orphans.push(Orphan {
+            path: doc.to_string_lossy().replace('\\', "/"),
+            size: meta.len(),
+            age_days: meta
+                .modified()
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .map(|age| age.as_secs() / 86_400),
+        });
+    }
+    Ok(orphans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::reporter::Silent;
+    use crate::test_util::TestDir;
+
+    fn fixture(name: &str) -> TestDir {
+        let dir = TestDir::new(name);
+        fs::create_dir_all(dir.join(".context/docs")).unwrap();
+        fs::write(
+            dir.join("CONTEXT.md"),
+            "# Repo\n\nSee .claude/docs/api.md and `.claude/queue.md`.\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join(".context/docs/guide.md"),
+            "Read .claude/agents/reviewer.md first.\n\n```sh\ncat .claude/docs/api.md\n```\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join(".context/docs/notes.rst"),
+            "See .claude/docs/api.md\n",
+        )
+        .unwrap();
+        dir
+    }
+
+    fn opts(include_code: bool, dry_run: bool) -> AuditOpts {
+        AuditOpts {
+            fix: true,
+            dry_run,
+            include_code,
+            quiet: true,
+        }
+    }
+
+    #[test]
+    fn test_fix_rewrites_to_canonical_paths_outside_code() {
+        let repo = fixture("surfcontext-test-audit-fix");
+        let config: SurfConfig = serde_json::from_str("{}").unwrap();
+
+        let dry = fix(
+            &repo,
+            &config,
+            &opts(false, true),
+            &mut Trash::default(),
+            &mut Silent,
+        )
+        .unwrap();
+        assert_eq!(dry.files, vec![".context/docs/guide.md", "CONTEXT.md"]);
+        assert!(
+            fs::read_to_string(repo.join("CONTEXT.md"))
+                .unwrap()
+                .contains(".claude/")
+        );
+
+        let report = fix(
+            &repo,
+            &config,
+            &opts(false, false),
+            &mut Trash::default(),
+            &mut Silent,
+        )
+        .unwrap();
+        assert_eq!(report.fixed, 3);
+        assert_eq!(
+            fs::read_to_string(repo.join("CONTEXT.md")).unwrap(),
+            "# Repo\n\nSee .context/docs/api.md and `.context/queue.md`.\n"
+        );
+        let guide = fs::read_to_string(repo.join(".context/docs/guide.md")).unwrap();
+        assert!(guide.starts_with("Read .context/agents/reviewer.md first."));
+        assert!(guide.contains("cat .claude/docs/api.md"));
+        assert_eq!(report.remaining.len(), 1);
+        assert!(report.remaining[0].in_code);
+
+        let report = fix(
+            &repo,
+            &config,
+            &opts(true, false),
+            &mut Trash::default(),
+            &mut Silent,
+        )
+        .unwrap();
+        assert_eq!((report.fixed, report.remaining.len()), (1, 0));
+    }
+
+    #[test]
+    fn test_broken_references_with_rename_suggestion() {
+        let repo = fixture("surfcontext-test-audit-broken");
+        fs::create_dir_all(repo.join("src/sync")).unwrap();
+        fs::write(repo.join("src/sync/report.rs"), "").unwrap();
+        fs::write(
+            repo.join("CONTEXT.md"),
+            "| `src/report.rs` | moved |\n| `.context/docs/guide.md` | exists |\n",
+        )
+        .unwrap();
+        fs::write(
+            repo.join(".context/docs/guide.md"),
+            "[CONTEXT](../../CONTEXT.md), [gone](missing.md), [web](https://example.com)\n",
+        )
+        .unwrap();
+        let config: SurfConfig = serde_json::from_str("{}").unwrap();
+
+        let broken = find_broken_references(&repo, &config);
+        let found: Vec<(&str, usize, &str, Option<&str>)> = broken
+            .iter()
+            .map(|b| {
+                (
+                    b.file.as_str(),
+                    b.line,
+                    b.raw.as_str(),
+                    b.suggestion.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (".context/docs/guide.md", 1, "missing.md", None),
+                ("CONTEXT.md", 1, "src/report.rs", Some("src/sync/report.rs")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_orphans_are_documents_no_reference_chain_reaches() {
+        let repo = fixture("surfcontext-test-audit-orphans");
+        for dir in ["guides", "agents", "skills/deploy"] {
+            fs::create_dir_all(repo.join(".context").join(dir)).unwrap();
+        }
+        let files = [
+            (
+                "CONTEXT.md",
+                "See [the guide](.context/docs/guide.md) and `.context/guides/`.\n",
+            ),
+            (
+                ".context/docs/guide.md",
+                "Next: [setup](../guides/setup.md)\n",
+            ),
+            (".context/guides/setup.md", "Done.\n"),
+            (
+                ".context/docs/via-platform.md",
+                "Only an agent points here.\n",
+            ),
+            (
+                ".context/agents/reviewer.md",
+                "Read `.claude/docs/via-platform.md`.\n",
+            ),
+            (".context/docs/from-skill.md", "x\n"),
+            (
+                ".context/skills/deploy/SKILL.md",
+                "Uses [notes](../../docs/from-skill.md).\n",
+            ),
+            (".context/docs/discovered.md", "x\n"),
+            (
+                ".context/docs/orphan.md",
+                "Links to [setup](../guides/setup.md).\n",
+            ),
+            (".context/guides/stale.md", "x\n"),
+        ];
+        for (path, content) in files {
+            fs::write(repo.join(path), content).unwrap();
+        }
+        let config: SurfConfig =
+            serde_json::from_str(r#"{ "discoveryOrder": [".context/docs/discovered.md"] }"#)
+                .unwrap();
+
+        let orphans = find_orphans(&repo, &config).unwrap();
+        let paths: Vec<&str> = orphans.iter().map(|o| o.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![".context/docs/orphan.md", ".context/guides/stale.md"]
+        );
+        assert_eq!(orphans[0].size, 38);
+        assert_eq!(orphans[0].age_days, Some(0));
+    }
+
+    #[test]
+    fn test_scanned_extensions_come_from_config() {
+        let repo = fixture("surfcontext-test-audit-extensions");
+        let config: SurfConfig =
+            serde_json::from_str(r#"{ "audit": { "extensions": ["rst"] } }"#).unwrap();
+        assert_eq!(
+            sources(&repo, &config),
+            vec![
+                PathBuf::from(".context/docs/notes.rst"),
+                PathBuf::from("CONTEXT.md")
+            ]
+        );
+    }
+}
@@ -0,0 +1,413 @@
+//! `surf backport`: carry hand edits to generated files back into CONTEXT.md.
+//!
+//! Each edited output is diffed against what CONTEXT.md currently generates. Every hunk is
+//! located in CONTEXT.md (or one of its `surf:include` files) by matching its old lines,
+//! verbatim or after the platform's path rewrites, and the new lines are written back with
+//! link-farm paths turned into canonical ones. Hunks that can't be placed unambiguously,
+//! such as edits to the injected path rule, are reported and left for a human.
+
+use anyhow::{Context, Result, bail};
+use colored::Colorize;
+use std::fs;
+use std::path::Path;
+
+use super::atomic;
+use super::cross_repo::content_hash;
+use super::diff::{self, Hunk, Line};
+use super::directives;
+use super::generate::{EditState, edit_state, render, rewriter_for, unstamp};
+use super::lockfile::Lockfile;
+use super::platforms::PlatformRegistry;
+use super::reporter::Reporter;
+use super::rewrite::PathRewriter;
+use super::trash::Trash;
+use crate::config::SurfConfig;
+
+pub struct BackportOpts {
+    pub dry_run: bool,
+    /// Only back-port this platform's output.
+    pub platform: Option<String>,
+    pub quiet: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct BackportReport {
+    /// Hunks written (or, in dry-run mode, that would be written) into a source file.
+    pub applied: usize,
+    /// Hunks that could not be placed.
+    pub skipped: usize,
+    /// Outputs that were not looked at, with the reason.
+    pub warnings: Vec<String>,
+}
+
+/// A file CONTEXT.md is assembled from.
+struct Source {
+    path: String,
+    text: String,
+}
+
+/// A replacement of `start..end` in one source's lines.
+#[derive(Debug, PartialEq)]
+struct Edit {
+    source: usize,
+    start: usize,
+    end: usize,
+    lines: Vec<String>,
+}
+
+/// Back-port hand edits in every (or one) platform output into the canonical sources,
+/// telling `reporter` about each output and hunk.
+pub fn backport(
+    repo_root: &Path,
+    config: &SurfConfig,
+    opts: &BackportOpts,
+    trash: &mut Trash,
+    reporter: &mut dyn Reporter,
+) -> Result<BackportReport> {
+    let root_context = &config.canonical.root_context;
+    let content = fs::read_to_string(repo_root.join(root_context))
+        .with_context(|| format!("Failed to read {root_context}"))?;
+    let expanded = directives::expand_includes(repo_root, root_context, &content)?;
+    let mut sources = vec![Source {
+        path: root_context.clone(),
+        text: content,
+    }];
+    for include in &expanded.includes {
+        sources.push(Source {
+            path: include.display().to_string(),
+            text: fs::read_to_string(repo_root.join(include))?,
+        });
+    }
+    let source_hash = content_hash(expanded.content.as_bytes());
+
+    let registry = PlatformRegistry::builtin();
+    let mut platforms: Vec<_> = config
+        .generation
+        .iter()
+        .filter(|(name, _)| match &opts.platform {
+            Some(p) => p == *name,
+            // Sync skips platforms it does not know, so they have nothing to back-port.
+            None => registry.get(name).is_some(),
+        })
+        .collect();
+    platforms.sort_by_key(|(name, _)| name.as_str());
+    if let Some(platform) = &opts.platform
+        && platforms.is_empty()
+    {
+        bail!("Platform `{platform}` is not configured under generation in surfcontext.json");
+    }
+
+    let lock = Lockfile::load(repo_root)?;
+    let mut report = BackportReport::default();
+    let mut edits: Vec<Edit> = Vec::new();
+
+    for (name, platform_gen) in platforms {
+        let platform = registry.resolve(name, platform_gen)?;
+        let output = platform.output.as_str();
+        let Ok(existing) = fs::read_to_string(repo_root.join(output)) else {
+            continue;
+        };
+        let entry = lock.outputs.get(output);
+        if edit_state(&existing, entry) != EditState::Edited {
+            reporter.progress(&format!("  {} {}", output, "(no hand edits)".dimmed()));
+            continue;
+        }
+
+        let reason = if platform_gen.root_context_script.is_some() {
+            Some("is produced by rootContextScript")
+        } else if entry.and_then(|e| e.source_hash.as_deref()) != Some(source_hash.as_str()) {
+            // Diffing against today's output would undo those changes.
+            Some("was generated from an older CONTEXT.md")
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            let msg = format!("{output} {reason}; merge its edits by hand");
+            reporter.progress(&format!("  {} {}", "WARNING:".yellow(), msg));
+            report.warnings.push(msg);
+            continue;
+        }
+
+        reporter.progress(&format!("  {}", output.bold()));
+        let generated = render(
+            repo_root,
+            config,
+            &platform,
+            platform_gen,
+            &expanded.content,
+        )?;
+        let existing = unstamp(&existing).0;
+        let (old, new): (Vec<&str>, Vec<&str>) =
+            (generated.lines().collect(), existing.lines().collect());
+        let rewriter = rewriter_for(config, &platform, platform_gen);
+
+        // One block per run of changed lines, so an edit to the injected boilerplate
+        // doesn't drag a nearby content edit down with it.
+        for hunk in diff::hunks(&old, &new, 0) {
+            let result = match locate(&sources, &rewriter, &old, &hunk) {
+                None => Err("not found in the canonical sources".to_string()),
+                Some(edit) if edits.contains(&edit) => Ok((edit, true)),
+                Some(edit) => match edits.iter().find(|e| overlaps(e, &edit)) {
+                    Some(_) => Err("overlaps an edit from another output".to_string()),
+                    None => Ok((edit, false)),
+                },
+            };
+            match result {
+                Ok((edit, duplicate)) => {
+                    report.applied += 1;
+                    reporter.progress(&format!(
+                        "    {} {hunk} → {}:{}{}",
+                        if opts.dry_run {
+                            "Would back-port".green()
+                        } else {
+                            "Back-ported".green()
+                        },
+                        sources[edit.source].path,
+                        edit.start + 1,
+                        if duplicate {
+                            " (same edit as another output)".dimmed()
+                        } else {
+                            "".normal()
+                        }
+                    ));
+                    if !duplicate {
+                        edits.push(edit);
+                    }
+                }
+                Err(reason) => {
+                    report.skipped += 1;
+                    reporter.progress(&format!(
+                        "    {} {}",
+                        "Skipped".yellow(),
+                        format!("({reason})").dimmed()
+                    ));
+                    for line in diff::styled(&hunk.to_text(), "      ") {
+                        reporter.progress(&line);
+                    }
+                }
+            }
+        }
+    }
+
+    if !opts.dry_run {
+        write_edits(repo_root, &sources, edits, trash)?;
+    }
+    Ok(report)
+}
+
+/// Find where a change block sits in the sources and build its replacement.
+///
+/// The block is matched together with up to `CONTEXT_LINES` of surrounding generated
+/// lines. Context next to boilerplate (the header, the path rule) will not be found, so
+/// smaller windows are tried until exactly one location matches.
+fn locate(
+    sources: &[Source],
+    rewriter: &PathRewriter,
+    generated: &[&str],
+    block: &Hunk,
+) -> Option<Edit> {
+    let removed: Vec<&str> = block
+        .lines
+        .iter()
+        .filter_map(|l| match l {
+            Line::Removed(t) => Some(*t),
+            _ => None,
+        })
+        .collect();
+    let (block_start, block_end) = (block.old_start, block.old_start + removed.len());
+    let lead = &generated[block_start.saturating_sub(diff::CONTEXT_LINES)..block_start];
+    let trail = &generated[block_end..(block_end + diff::CONTEXT_LINES).min(generated.len())];
+    let views: Vec<(Vec<&str>, Vec<String>)> = sources
+        .iter()
+        .map(|s| {
+            let rewritten = rewriter.apply(&s.text);
+            (
+                s.text.lines().collect(),
+                rewritten.lines().map(str::to_string).collect(),
+            )
+        })
+        .collect();
+
+    for before in (0..=lead.len()).rev() {
+        for after in (0..=trail.len()).rev() {
+            let old: Vec<&str> = lead[lead.len() - before..]
+                .iter()
+                .chain(&removed)
+                .chain(&trail[..after])
+                .copied()
+                .collect();
+            if old.is_empty() {
+                continue;
+            }
+
+            let old = &old;
+            let mut found = views
+                .iter()
+                .enumerate()
+                .flat_map(|(idx, (raw, rewritten))| {
+                    (0..(raw.len() + 1).saturating_sub(old.len()))
+                        .filter(move |&start| {
+                            old.iter().enumerate().all(|(k, line)| {
+                                raw[start + k] == *line || rewritten[start + k] == *line
+                            })
+                        })
+                        .map(move |start| (idx, start))
+                });
+            let (Some((source, start)), None) = (found.next(), found.next()) else {
+                continue;
+            };
+
+            let inverse = rewriter.inverse();
+            let lines = block
+                .lines
+                .iter()
+                .filter_map(|l| match l {
+                    Line::Added(text) => Some(inverse.apply(text)),
+                    _ => None,
+                })
+                .collect();
+            return Some(Edit {
+                source,
+                start: start + before,
+                end: start + before + removed.len(),
+                lines,
+            });
+        }
+    }
+    None
+}
+
+fn overlaps(a: &Edit, b: &Edit) -> bool {
+    a.source == b.source && a.start < b.end.max(b.start + 1) && b.start < a.end.max(a.start + 1)
+}
+
+/// Apply `edits` to the sources, bottom-up so earlier line numbers stay valid.
+fn write_edits(
+    repo_root: &Path,
+    sources: &[Source],
+    mut edits: Vec<Edit>,
+    trash: &mut Trash,
+) -> Result<()> {
+    edits.sort_by_key(|e| (e.source, std::cmp::Reverse(e.start)));
+    for (idx, source) in sources.iter().enumerate() {
+        let mut lines: Vec<String> = source.text.lines().map(str::to_string).collect();
+        let mut changed = false;
+        for edit in edits.iter().filter(|e| e.source == idx) {
+            lines.splice(edit.start..edit.end, edit.lines.iter().cloned());
+            changed = true;
+        }
+        if !changed {
+            continue;
+        }
+        let mut text = lines.join("\n");
+        if source.text.ends_with('\n') {
+            text.push('\n');
+        }
+        let path = repo_root.join(&source.path);
+        trash.preserve(&path)?;
+        atomic::write(&path, text)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::reporter::Silent;
+    use crate::sync::{SyncOpts, SyncReport, generate, lockfile};
+    use crate::test_util::TestDir;
+
+    fn fixture(name: &str, context: &str) -> (TestDir, SurfConfig) {
+        let dir = TestDir::new(name);
+        fs::create_dir_all(dir.join(".context")).unwrap();
+        fs::write(dir.join("CONTEXT.md"), context).unwrap();
+        let config: SurfConfig = serde_json::from_str(
+            r#"{ "generation": {
+                "claude": { "rootContext": "CLAUDE.md", "rootContextMethod": "sed-copy" },
+                "codex": { "rootContext": "AGENTS.md", "method": "template-copy" }
+            } }"#,
+        )
+        .unwrap();
+        sync(&dir, &config);
+        (dir, config)
+    }
+
+    fn sync(repo: &Path, config: &SurfConfig) -> SyncReport {
+        let opts = SyncOpts {
+            local_only: true,
+            quiet: true,
+            ..Default::default()
+        };
+        let mut report = SyncReport::default();
+        generate::generate_all(repo, config, &opts, &mut report).unwrap();
+        lockfile::prune_and_write(repo, &opts, &mut report).unwrap();
+        report
+    }
+
+    fn opts() -> BackportOpts {
+        BackportOpts {
+            dry_run: false,
+            platform: None,
+            quiet: true,
+        }
+    }
+
+    #[test]
+    fn test_backports_edits_with_canonical_paths() {
+        let context = "# Repo\n\n## Docs\n\nSee `.context/docs/a.md`.\n\n## Notes\n\nNone yet.\n";
+        let (repo, config) = fixture("surfcontext-test-backport", context);
+
+        let claude = fs::read_to_string(repo.join("CLAUDE.md")).unwrap();
+        let edited = claude
+            .replace(
+                "See `.claude/docs/a.md`.",
+                "See `.claude/docs/a.md` and `.claude/docs/b.md`.",
+            )
+            .replace("None yet.", "None yet.\n- Run tests before pushing.");
+        fs::write(repo.join("CLAUDE.md"), edited).unwrap();
+
+        let report = backport(&repo, &config, &opts(), &mut Trash::default(), &mut Silent).unwrap();
+        assert_eq!((report.applied, report.skipped), (2, 0));
+        assert_eq!(
+            fs::read_to_string(repo.join("CONTEXT.md")).unwrap(),
+            "# Repo\n\n## Docs\n\nSee `.context/docs/a.md` and `.context/docs/b.md`.\n\n\
+             ## Notes\n\nNone yet.\n- Run tests before pushing.\n"
+        );
+
+        // Sync now regenerates CLAUDE.md with the edits included, instead of refusing.
+        let report = sync(&repo, &config);
+        assert!(report.warnings.is_empty());
+        assert!(
+            fs::read_to_string(repo.join("AGENTS.md"))
+                .unwrap()
+                .contains("- Run tests before pushing.")
+        );
+    }
+
+    #[test]
+    fn test_skips_edits_to_boilerplate_and_stale_outputs() {
+        let (repo, config) = fixture("surfcontext-test-backport-skip", "# Repo\n\nBody.\n");
+
+        let agents = fs::read_to_string(repo.join("AGENTS.md")).unwrap();
+        fs::write(
+            repo.join("AGENTS.md"),
+            agents.replace("## Source of Truth", "## Where Files Live"),
+        )
+        .unwrap();
+        let preview = BackportOpts {
+            dry_run: true,
+            ..opts()
+        };
+        let report =
+            backport(&repo, &config, &preview, &mut Trash::default(), &mut Silent).unwrap();
+        assert_eq!((report.applied, report.skipped), (0, 1));
+
+        fs::write(repo.join("CONTEXT.md"), "# Repo\n\nNew body.\n").unwrap();
+        let report = backport(&repo, &config, &opts(), &mut Trash::default(), &mut Silent).unwrap();
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(
+            fs::read_to_string(repo.join("CONTEXT.md")).unwrap(),
+            "# Repo\n\nNew body.\n"
+        );
+    }
+}
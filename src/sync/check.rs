@@ -0,0 +1,128 @@
+//! `surf check`: the whole sync pipeline in verify mode, for CI.
+//!
+//! Every stage runs as a quiet dry run, so nothing is written and no progress output is
+//! mixed into the result. What sync would change, what it warned about and what it could
+//! not fix come back in the report, and the exit code says which one is the worst.
+
+use anyhow::Result;
+use std::path::Path;
+
+use super::report::Action;
+use super::{SyncOpts, SyncReport};
+use crate::config;
+
+/// Nothing to do.
+pub const EXIT_OK: i32 = 0;
+/// Sync failed, or found something it cannot fix on its own.
+pub const EXIT_ERRORS: i32 = 1;
+/// `surf sync` would change files.
+pub const EXIT_OUT_OF_DATE: i32 = 2;
+/// Up to date, but with warnings.
+pub const EXIT_WARNINGS: i32 = 3;
+
+/// Run every sync stage against `repo_root` without writing anything.
+pub fn check(repo_root: &Path, local_only: bool) -> Result<SyncReport> {
+    let config = config::load_config(repo_root)?;
+    let opts = SyncOpts {
+        dry_run: true,
+        local_only,
+        quiet: true,
+        ..Default::default()
+    };
+    super::run_stages(repo_root, &config, &opts, None)
+}
+
+/// Exit code for a check report: errors (and with `strict`, warnings), then out of date,
+/// then warnings. A lockfile that is missing or behind is not out of date on its own.
+pub fn exit_code(report: &SyncReport, strict: bool) -> i32 {
+    if report.failed(strict) {
+        EXIT_ERRORS
+    } else if out_of_date(report).next().is_some() {
+        EXIT_OUT_OF_DATE
+    } else if !report.warnings.is_empty() {
+        EXIT_WARNINGS
+    } else {
+        EXIT_OK
+    }
+}
+
+/// Actions on outputs, leaving out lockfile bookkeeping.
+pub fn out_of_date(report: &SyncReport) -> impl Iterator<Item = &Action> {
+    report.actions.iter().filter(|a| !a.is_bookkeeping())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::lockfile::LOCK_FILE;
+    use crate::sync::report::{ActionKind, Code, Diagnostic, Stage};
+    use crate::test_util::TestDir;
+    use std::fs;
+
+    fn fixture(name: &str) -> TestDir {
+        let dir = TestDir::new(name);
+        fs::write(dir.join("CONTEXT.md"), "# Repo\n").unwrap();
+        fs::write(
+            dir.join("surfcontext.json"),
+            r#"{ "generation": { "claude": { "method": "copy" } } }"#,
+        )
+        .unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_check_writes_nothing_and_reports_out_of_date() {
+        let repo = fixture("surfcontext-test-check");
+        let report = check(&repo, true).unwrap();
+        assert_eq!(exit_code(&report, false), EXIT_OUT_OF_DATE);
+        assert!(report.actions.iter().any(|a| a.path == "CLAUDE.md"));
+        assert!(!repo.join("CLAUDE.md").exists());
+        assert!(!repo.join(".context").exists());
+
+        let config = config::load_config(&repo).unwrap();
+        let sync = SyncOpts {
+            local_only: true,
+            quiet: true,
+            ..Default::default()
+        };
+        super::super::run_stages(&repo, &config, &sync, None).unwrap();
+        assert_eq!(exit_code(&check(&repo, true).unwrap(), false), EXIT_OK);
+
+        fs::write(repo.join("CONTEXT.md"), "# Repo\n\nNew section.\n").unwrap();
+        let report = check(&repo, true).unwrap();
+        assert_eq!(exit_code(&report, false), EXIT_OUT_OF_DATE);
+    }
+
+    #[test]
+    fn test_missing_lockfile_alone_is_not_out_of_date() {
+        let repo = fixture("surfcontext-test-check-no-lock");
+        let config = config::load_config(&repo).unwrap();
+        let sync = SyncOpts {
+            local_only: true,
+            quiet: true,
+            ..Default::default()
+        };
+        super::super::run_stages(&repo, &config, &sync, None).unwrap();
+        fs::remove_file(repo.join(LOCK_FILE)).unwrap();
+
+        let report = check(&repo, true).unwrap();
+        assert!(report.actions.iter().any(|a| a.path == LOCK_FILE));
+        assert_eq!(exit_code(&report, false), EXIT_OK);
+    }
+
+    #[test]
+    fn test_exit_code_precedence() {
+        let mut report = SyncReport::default();
+        report
+            .warnings
+            .push(Diagnostic::new(Code::OrphanFile, Stage::Sweep, "w"));
+        assert_eq!(exit_code(&report, false), EXIT_WARNINGS);
+        report.record(Action::new(Stage::Generate, ActionKind::Update, "CLAUDE.md", "c"));
+        assert_eq!(exit_code(&report, false), EXIT_OUT_OF_DATE);
+        assert_eq!(exit_code(&report, true), EXIT_ERRORS);
+        report
+            .errors
+            .push(Diagnostic::new(Code::LinkBlocked, Stage::Links, "e"));
+        assert_eq!(exit_code(&report, false), EXIT_ERRORS);
+    }
+}
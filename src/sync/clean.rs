@@ -0,0 +1,372 @@
+//! `surf clean`: back a repo out of SurfContext by removing what `surf sync` produced.
+//!
+//! Only outputs surf can prove it made are deleted: files listed in `surf-lock.json` whose
+//! hash still matches, files the lockfile has no hash for that carry the generated header,
+//! and symlinks that point at their canonical directory. `.context/` and platform-native
+//! files such as `.claude/settings*.json` are never candidates. Everything removed goes
+//! through the trash, so `surf undo` brings it back.
+
+use anyhow::Result;
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use super::generate::{EditState, edit_state, is_generated_header};
+use super::local::QUEUE_HEADER;
+use super::lockfile::{self, LockEntry, Lockfile, OutputKind};
+use super::materialize::LinkMethod;
+use super::platforms::PlatformRegistry;
+use super::reporter::Reporter;
+use super::trash::Trash;
+use super::{is_symlink_to, link_farms, relative_link_target};
+use crate::config::SurfConfig;
+
+pub struct CleanOpts {
+    pub dry_run: bool,
+    /// Also remove files that cross-repo sync pushed into other repos.
+    pub cross_repo: bool,
+    pub quiet: bool,
+}
+
+/// What `clean` did (or, in dry-run mode, would do).
+#[derive(Debug, Default)]
+pub struct CleanReport {
+    pub removed: Vec<String>,
+    /// (path, reason it was kept)
+    pub kept: Vec<(String, String)>,
+}
+
+/// Remove every provably generated output under `repo_root`, telling `reporter` about each
+/// output removed or kept.
+pub fn clean(
+    repo_root: &Path,
+    config: &SurfConfig,
+    opts: &CleanOpts,
+    trash: &mut Trash,
+    reporter: &mut dyn Reporter,
+) -> Result<CleanReport> {
+    let mut lock = Lockfile::load(repo_root)?;
+    let mut report = CleanReport::default();
+
+    for (path, entry) in candidates(config, &lock)? {
+        if entry
+            .as_ref()
+            .is_some_and(|e| e.kind == OutputKind::CrossRepo && !opts.cross_repo)
+        {
+            continue;
+        }
+        let full = repo_root.join(&path);
+        if fs::symlink_metadata(&full).is_err() {
+            lock.outputs.remove(&path);
+            continue;
+        }
+
+        match proof(repo_root, &path, entry.as_ref())? {
+            Ok(reason) => {
+                let verb = if opts.dry_run {
+                    "Would remove"
+                } else {
+                    "Removed"
+                };
+                reporter.progress(&format!(
+                    "  {} {path} {}",
+                    verb.green(),
+                    format!("({reason})").dimmed()
+                ));
+                if !opts.dry_run {
+                    trash.preserve(&full)?;
+                    if full.is_dir() && !full.is_symlink() {
+                        fs::remove_dir_all(&full)?;
+                    } else {
+                        fs::remove_file(&full)?;
+                    }
+                    remove_empty_parents(repo_root, &full);
+                }
+                lock.outputs.remove(&path);
+                report.removed.push(path);
+            }
+            Err(reason) => {
+                reporter.progress(&format!(
+                    "  {} {path} {}",
+                    "Kept".yellow(),
+                    format!("({reason})").dimmed()
+                ));
+                report.kept.push((path, reason));
+            }
+        }
+    }
+
+    if !opts.dry_run {
+        lock.save(repo_root)?;
+    }
+    Ok(report)
+}
+
+/// Every path surf may have produced: the lockfile, plus what the current config declares
+/// (so repos synced before the lockfile existed can be cleaned too).
+fn candidates(config: &SurfConfig, lock: &Lockfile) -> Result<BTreeMap<String, Option<LockEntry>>> {
+    let mut candidates: BTreeMap<String, Option<LockEntry>> = lock
+        .outputs
+        .iter()
+        .map(|(path, entry)| (path.clone(), Some(entry.clone())))
+        .collect();
+
+    let registry = PlatformRegistry::builtin();
+    for (platform, platform_gen) in &config.generation {
+        if let Ok(resolved) = registry.resolve(platform, platform_gen) {
+            candidates.entry(resolved.output).or_insert(None);
+        }
+    }
+    for farm in link_farms(config) {
+        candidates
+            .entry(format!("{}/queue.md", farm.root))
+            .or_insert(None);
+        if farm.method == LinkMethod::Symlink {
+            for (context_dir, link) in &farm.links {
+                candidates
+                    .entry(link.clone())
+                    .or_insert_with(|| Some(LockEntry::new(OutputKind::Symlink, context_dir)));
+            }
+        }
+    }
+    Ok(candidates)
+}
+
+/// Why `path` is safe to delete, or why it isn't.
+fn proof(
+    repo_root: &Path,
+    path: &str,
+    entry: Option<&LockEntry>,
+) -> Result<std::result::Result<&'static str, String>> {
+    let full = repo_root.join(path);
+    let meta = fs::symlink_metadata(&full)?;
+
+    if meta.is_symlink() {
+        return Ok(match entry {
+            Some(e) if is_symlink_to(&full, &relative_link_target(path, &e.source)) => {
+                Ok("symlink to canonical dir")
+            }
+            _ => Err("symlink not created by surf".to_string()),
+        });
+    }
+    if meta.is_dir() {
+        return Ok(match entry {
+            Some(e)
+                if e.kind == OutputKind::Mirror && lockfile::is_pristine(repo_root, path, e)? =>
+            {
+                Ok("unchanged mirror")
+            }
+            Some(e) if e.kind == OutputKind::Mirror => {
+                Err("mirror has changed since sync".to_string())
+            }
+            _ => Err("directory not created by surf".to_string()),
+        });
+    }
+
+    // The header only vouches for files the lockfile knows nothing about: an edited file
+    // keeps the header it was generated with. A cross-repo file without a hash was never
+    // written by surf as it is now (its target was newer). Without a lockfile hash, the
+    // hash stamped into the header still tells an edited file apart.
+    if let Some(e) = entry
+        && (e.output_hash.is_some() || e.kind == OutputKind::CrossRepo)
+    {
+        return Ok(if lockfile::is_pristine(repo_root, path, e)? {
+            Ok("matches surf-lock.json")
+        } else {
+            Err("modified since sync".to_string())
+        });
+    }
+    let head = fs::read_to_string(&full).unwrap_or_default();
+    if head
+        .lines()
+        .take(3)
+        .any(|l| is_generated_header(l) || l == QUEUE_HEADER)
+    {
+        return Ok(if edit_state(&head, None) == EditState::Edited {
+            Err("modified since sync (header hash no longer matches)".to_string())
+        } else {
+            Ok("generated header")
+        });
+    }
+    Ok(Err("no generated header and not in surf-lock.json".to_string()))
+}
+
+/// Drop directories that cleaning left empty (e.g. `.claude/` with nothing native in it).
+/// Paths are canonicalized first, so a cross-repo output (`../other-repo/...`) never walks
+/// up into another repo.
+fn remove_empty_parents(repo_root: &Path, path: &Path) {
+    let Ok(repo_root) = repo_root.canonicalize() else {
+        return;
+    };
+    let context_dir = repo_root.join(".context");
+    let mut dir = path.parent().and_then(|d| d.canonicalize().ok());
+    while let Some(d) = dir {
+        if d == repo_root || d.starts_with(&context_dir) || !d.starts_with(&repo_root) {
+            break;
+        }
+        if fs::remove_dir(&d).is_err() {
+            break;
+        }
+        dir = d.parent().map(Path::to_path_buf);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_util::TestDir;
+    use crate::sync::cross_repo::file_hash;
+    use crate::sync::generate::{GENERATED_HEADER, stamp};
+    use crate::sync::reporter::Silent;
+
+    fn fixture(name: &str) -> TestDir {
+        let dir = TestDir::new(name);
+        fs::create_dir_all(dir.join(".context/docs")).unwrap();
+        fs::create_dir_all(dir.join(".claude")).unwrap();
+        fs::create_dir_all(dir.join(".codex")).unwrap();
+        fs::write(
+            dir.join("CLAUDE.md"),
+            format!("<!-- x -->\n{GENERATED_HEADER}\n\nBody\n"),
+        )
+        .unwrap();
+        fs::write(dir.join("AGENTS.md"), "# Hand-written agents file\n").unwrap();
+        fs::write(
+            dir.join(".claude/queue.md"),
+            format!("{QUEUE_HEADER}\n\n# Q\n"),
+        )
+        .unwrap();
+        fs::write(dir.join(".claude/settings.local.json"), "{}").unwrap();
+        std::os::unix::fs::symlink("../.context/docs", dir.join(".claude/docs")).unwrap();
+        std::os::unix::fs::symlink("/tmp", dir.join(".codex/docs")).unwrap();
+        dir
+    }
+
+    fn config() -> SurfConfig {
+        serde_json::from_str(
+            r#"{ "generation": {
+                "claude": { "rootContext": "CLAUDE.md", "rootContextMethod": "sed-copy" },
+                "codex": { "rootContext": "AGENTS.md", "method": "template-copy" }
+            } }"#,
+        )
+        .unwrap()
+    }
+
+    fn opts(dry_run: bool) -> CleanOpts {
+        CleanOpts {
+            dry_run,
+            cross_repo: false,
+            quiet: true,
+        }
+    }
+
+    #[test]
+    fn test_clean_removes_only_provable_outputs() {
+        let repo = fixture("surfcontext-test-clean");
+        let report =
+            clean(&repo, &config(), &opts(false), &mut Trash::default(), &mut Silent).unwrap();
+
+        assert_eq!(
+            report.removed,
+            vec![".claude/docs", ".claude/queue.md", "CLAUDE.md"]
+        );
+        let kept: Vec<&str> = report.kept.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(kept, vec![".codex/docs", "AGENTS.md"]);
+
+        assert!(!repo.join("CLAUDE.md").exists());
+        assert!(repo.join("AGENTS.md").exists());
+        assert!(repo.join(".claude/settings.local.json").exists());
+        assert!(repo.join(".context/docs").is_dir());
+    }
+
+    #[test]
+    fn test_dry_run_removes_nothing() {
+        let repo = fixture("surfcontext-test-clean-dry");
+        let report =
+            clean(&repo, &config(), &opts(true), &mut Trash::default(), &mut Silent).unwrap();
+        assert_eq!(report.removed.len(), 3);
+        assert!(repo.join("CLAUDE.md").exists());
+        assert!(repo.join(".claude/docs").is_symlink());
+    }
+
+    #[test]
+    fn test_edited_output_keeps_its_header_but_is_kept() {
+        let repo = fixture("surfcontext-test-clean-edited");
+        let mut lock = Lockfile::default();
+        lock.outputs.insert(
+            "CLAUDE.md".to_string(),
+            LockEntry::new(OutputKind::Generated, "CONTEXT.md").hashes(
+                "source".to_string(),
+                file_hash(&repo.join("CLAUDE.md")).unwrap(),
+            ),
+        );
+        lock.save(&repo).unwrap();
+        fs::write(
+            repo.join("CLAUDE.md"),
+            format!("<!-- x -->\n{GENERATED_HEADER}\n\nBody, edited by hand\n"),
+        )
+        .unwrap();
+
+        let report =
+            clean(&repo, &config(), &opts(false), &mut Trash::default(), &mut Silent).unwrap();
+        assert!(
+            report
+                .kept
+                .iter()
+                .any(|(p, why)| p == "CLAUDE.md" && why == "modified since sync"),
+            "{:?}",
+            report.kept
+        );
+        assert!(repo.join("CLAUDE.md").exists());
+    }
+
+    #[test]
+    fn test_edited_stamped_output_without_a_lock_entry_is_kept() {
+        let repo = fixture("surfcontext-test-clean-stamped");
+        let generated = stamp(&format!("<!-- x -->\n{GENERATED_HEADER}\n\nBody\n"));
+        fs::write(repo.join("CLAUDE.md"), &generated).unwrap();
+        let report =
+            clean(&repo, &config(), &opts(true), &mut Trash::default(), &mut Silent).unwrap();
+        assert!(report.removed.contains(&"CLAUDE.md".to_string()));
+
+        fs::write(repo.join("CLAUDE.md"), generated.replace("Body", "Body, edited")).unwrap();
+        let report =
+            clean(&repo, &config(), &opts(false), &mut Trash::default(), &mut Silent).unwrap();
+        assert!(
+            report
+                .kept
+                .iter()
+                .any(|(p, why)| p == "CLAUDE.md" && why.starts_with("modified since sync")),
+            "{:?}",
+            report.kept
+        );
+        assert!(repo.join("CLAUDE.md").exists());
+    }
+
+    #[test]
+    fn test_cross_repo_output_leaves_the_other_repos_dirs() {
+        let workspace = TestDir::new("surfcontext-test-clean-cross-repo");
+        let repo = workspace.join("repo");
+        fs::create_dir_all(repo.join(".context")).unwrap();
+        let target = workspace.join("other/docs/nested/a.md");
+        fs::create_dir_all(target.parent().unwrap()).unwrap();
+        fs::write(&target, "pushed\n").unwrap();
+
+        let mut lock = Lockfile::default();
+        lock.outputs.insert(
+            "../other/docs/nested/a.md".to_string(),
+            LockEntry::new(OutputKind::CrossRepo, ".context/docs/a.md")
+                .hashes("source".to_string(), file_hash(&target).unwrap()),
+        );
+        lock.save(&repo).unwrap();
+
+        let opts = CleanOpts {
+            cross_repo: true,
+            ..opts(false)
+        };
+        let report = clean(&repo, &config(), &opts, &mut Trash::default(), &mut Silent).unwrap();
+        assert_eq!(report.removed, vec!["../other/docs/nested/a.md"]);
+        assert!(!target.exists());
+        assert!(workspace.join("other/docs/nested").is_dir());
+    }
+}
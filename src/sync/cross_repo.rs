@@ -3,11 +3,23 @@ use colored::Colorize;
 use sha2::{Digest, Sha256};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use walkdir::WalkDir;
 
+use super::{atomic, diff};
+use super::lockfile::{LockEntry, Lockfile, OutputKind};
+use super::repo_lock::RepoLock;
+use super::report::{Action, ActionKind, Code, Diagnostic, Stage};
+use super::trash::Trash;
 use super::{SyncOpts, SyncReport};
 use crate::config::SurfConfig;
 
+/// How long to wait for a target repo's lock. Tests hold it on purpose, so they don't wait.
+#[cfg(not(test))]
+const LOCK_TIMEOUT: Duration = super::repo_lock::DEFAULT_TIMEOUT;
+#[cfg(test)]
+const LOCK_TIMEOUT: Duration = Duration::ZERO;
+
 /// Result status for a single file sync operation.
 #[derive(Debug, PartialEq)]
 enum FileStatus {
@@ -25,24 +37,21 @@ pub fn sync_repos(
     report: &mut SyncReport,
 ) -> Result<()> {
     if config.sync.is_empty() {
-        if !opts.quiet {
-            println!("  {}", "No cross-repo sync targets configured.".dimmed());
-        }
+        report.progress(format!("  {}", "No cross-repo sync targets configured.".dimmed()));
         return Ok(());
     }
 
+    let previous = Lockfile::load(repo_root)?;
     for (section_name, section) in &config.sync {
         let source_dir = repo_root.join(&section.source);
         let source_files = list_files_recursive(&source_dir)?;
 
-        if !opts.quiet {
-            println!(
-                "\n  [{}] Source: {} ({} files)",
-                section_name,
-                section.source,
-                source_files.len()
-            );
-        }
+        report.progress(format!(
+            "\n  [{}] Source: {} ({} files)",
+            section_name,
+            section.source,
+            source_files.len()
+        ));
 
         for target in &section.targets {
             let target_repo_dir = repo_root.join(&target.repo);
@@ -50,20 +59,46 @@ pub fn sync_repos(
             let label = format!("{}/{}", target.repo, target.dest);
 
             if !target_repo_dir.exists() {
-                if !opts.quiet {
-                    println!("  -> {}", label);
-                    println!(
-                        "     {} Repo not found: {} -- skipping",
-                        "[WARN]".yellow(),
+                report.progress(format!("  -> {}", label));
+                report.warn(Diagnostic::new(
+                    Code::CrossRepoMissing,
+                    Stage::CrossRepo,
+                    format!(
+                        "Cross-repo target {label}: repo not found at {}",
                         target_repo_dir.display()
-                    );
-                }
+                    ),
+                ));
+                keep_target_outputs(&previous, repo_root, &target_dir, report);
                 continue;
             }
 
-            if !opts.quiet {
-                println!("  -> {}", label);
-            }
+            report.progress(format!("  -> {}", label));
+
+            // Writing into another repo, so hold its lock as well.
+            let _lock = if opts.dry_run || same_dir(repo_root, &target_repo_dir) {
+                None
+            } else {
+                let lock = RepoLock::acquire(&target_repo_dir, LOCK_TIMEOUT, |who| {
+                    report.progress(format!(
+                        "     {} {} is syncing {}, waiting...",
+                        "[Lock]".dimmed(),
+                        who,
+                        target.repo
+                    ))
+                });
+                match lock {
+                    Ok(lock) => Some(lock),
+                    Err(e) => {
+                        report.warn(Diagnostic::new(
+                            Code::CrossRepoLocked,
+                            Stage::CrossRepo,
+                            format!("Cross-repo target {label}: {e:#}"),
+                        ));
+                        keep_target_outputs(&previous, repo_root, &target_dir, report);
+                        continue;
+                    }
+                }
+            };
 
             // Filter source files by include/exclude
             let filtered: Vec<&PathBuf> = source_files
@@ -72,8 +107,6 @@ pub fn sync_repos(
                 .filter(|f| !matches_exclude(f, target.exclude.as_deref()))
                 .collect();
 
-            let mut section_new = 0;
-            let mut section_updated = 0;
             let mut section_unchanged = 0;
             let mut section_skipped = 0;
 
@@ -81,63 +114,112 @@ pub fn sync_repos(
                 let src = source_dir.join(rel_path);
                 let dst = target_dir.join(rel_path);
 
-                let status = sync_single_file(&src, &dst, opts)?;
+                let old = if dst.exists() { Some(fs::read(&dst)?) } else { None };
+                let before = old.as_deref().map(content_hash);
+                let status = sync_single_file(&src, &dst, opts, &mut report.trash)?;
+
+                let src_hash = file_hash(&src)?;
+                let dst_rel = dst.strip_prefix(repo_root).unwrap_or(&dst);
+                let dst_key = dst_rel.to_string_lossy().into_owned();
+                let kind = match status {
+                    FileStatus::New => Some(ActionKind::Create),
+                    FileStatus::Updated => Some(ActionKind::Update),
+                    _ => None,
+                };
+                if let Some(kind) = kind {
+                    report.record(
+                        Action::new(
+                            Stage::CrossRepo,
+                            kind,
+                            dst_rel.to_string_lossy(),
+                            format!("copy from {}", section.source),
+                        )
+                        .hashes(before, Some(src_hash.clone())),
+                    );
+                }
+                let mut entry = LockEntry::new(
+                    OutputKind::CrossRepo,
+                    &format!("{}/{}", section.source.trim_end_matches('/'), rel_path.display()),
+                )
+                .hashes(src_hash.clone(), src_hash);
+                if status == FileStatus::SkippedNewer {
+                    // Surf did not write what is there now, so it must not look pristine to
+                    // a prune or `surf clean`; keep what surf last wrote, if anything.
+                    entry.output_hash = previous
+                        .outputs
+                        .get(&dst_key)
+                        .and_then(|e| e.output_hash.clone());
+                }
+                report.outputs.insert(dst_key.clone(), entry);
 
                 match status {
                     FileStatus::New => {
-                        section_new += 1;
-                        if !opts.quiet {
-                            println!(
-                                "     {} {} (new)",
-                                "+".green(),
-                                rel_path.display()
-                            );
-                        }
+                        report.progress(format!(
+                            "     {} {} (new)",
+                            "+".green(),
+                            rel_path.display()
+                        ));
                     }
                     FileStatus::Updated => {
-                        section_updated += 1;
-                        if !opts.quiet {
-                            println!(
-                                "     {} {} (updated)",
-                                "~".yellow(),
-                                rel_path.display()
-                            );
-                        }
+                        report.progress(format!(
+                            "     {} {} (updated)",
+                            "~".yellow(),
+                            rel_path.display()
+                        ));
                     }
                     FileStatus::Unchanged => {
                         section_unchanged += 1;
-                        if opts.verbose && !opts.quiet {
-                            println!(
+                        if opts.verbose {
+                            report.progress(format!(
                                 "     {} {} (unchanged)",
                                 "-".dimmed(),
                                 rel_path.display()
-                            );
+                            ));
                         }
                     }
                     FileStatus::SkippedNewer => {
                         section_skipped += 1;
-                        if !opts.quiet {
-                            println!(
-                                "     {} {} (skipped — target newer)",
-                                "!".yellow(),
-                                rel_path.display()
-                            );
-                        }
+                        report.progress(format!(
+                            "     {} {} (skipped — target newer)",
+                            "!".yellow(),
+                            rel_path.display()
+                        ));
+                        report.warn(
+                            Diagnostic::new(
+                                Code::CrossRepoTargetNewer,
+                                Stage::CrossRepo,
+                                format!(
+                                    "{dst_key} is newer than {}/{}; skipped \
+                                     (surf sync --force overwrites it)",
+                                    section.source.trim_end_matches('/'),
+                                    rel_path.display()
+                                ),
+                            )
+                            .path(dst_key.clone()),
+                        );
+                    }
+                }
+                if kind.is_some() && opts.diff {
+                    for line in diff::change(
+                        &dst_key,
+                        old.as_deref(),
+                        &fs::read(&src)?,
+                        "       ",
+                    ) {
+                        report.progress(line);
                     }
                 }
             }
 
             // Print summary for this target if not verbose
-            if !opts.verbose && !opts.quiet && section_unchanged > 0 {
-                println!(
+            if !opts.verbose && section_unchanged > 0 {
+                report.progress(format!(
                     "     {} {} file(s) unchanged",
                     "-".dimmed(),
                     section_unchanged
-                );
+                ));
             }
 
-            report.cross_repo_new += section_new;
-            report.cross_repo_updated += section_updated;
             report.cross_repo_unchanged += section_unchanged;
             report.cross_repo_skipped += section_skipped;
         }
@@ -146,8 +228,41 @@ pub fn sync_repos(
     Ok(())
 }
 
+/// Carry the last run's outputs under `target_dir` over for a target this run skipped, so
+/// the prune does not delete files in a repo that is missing or that another sync is writing.
+fn keep_target_outputs(
+    previous: &Lockfile,
+    repo_root: &Path,
+    target_dir: &Path,
+    report: &mut SyncReport,
+) {
+    let prefix = target_dir.strip_prefix(repo_root).unwrap_or(target_dir);
+    let prefix = prefix.to_string_lossy();
+    let prefix = prefix.trim_end_matches('/');
+    for (path, entry) in &previous.outputs {
+        if entry.kind == OutputKind::CrossRepo
+            && (path == prefix || path.starts_with(&format!("{prefix}/")))
+        {
+            report.outputs.insert(path.clone(), entry.clone());
+            report.cross_repo_skipped += 1;
+        }
+    }
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
 /// Sync a single file: compare hashes, check modification time, copy if needed.
-fn sync_single_file(src: &Path, dst: &Path, opts: &SyncOpts) -> Result<FileStatus> {
+fn sync_single_file(
+    src: &Path,
+    dst: &Path,
+    opts: &SyncOpts,
+    trash: &mut Trash,
+) -> Result<FileStatus> {
     let src_hash = file_hash(src)?;
 
     if !dst.exists() {
@@ -156,9 +271,8 @@ fn sync_single_file(src: &Path, dst: &Path, opts: &SyncOpts) -> Result<FileStatu
             if let Some(parent) = dst.parent() {
                 fs::create_dir_all(parent)?;
             }
-            fs::copy(src, dst).with_context(|| {
-                format!("Failed to copy {} -> {}", src.display(), dst.display())
-            })?;
+            trash.before_write(dst)?;
+            atomic::copy(src, dst)?;
         }
         return Ok(FileStatus::New);
     }
@@ -182,19 +296,25 @@ fn sync_single_file(src: &Path, dst: &Path, opts: &SyncOpts) -> Result<FileStatu
         if let Some(parent) = dst.parent() {
             fs::create_dir_all(parent)?;
         }
-        fs::copy(src, dst)?;
+        trash.preserve(dst)?;
+        atomic::copy(src, dst)?;
     }
 
     Ok(FileStatus::Updated)
 }
 
 /// Compute SHA-256 hash of file contents.
-fn file_hash(path: &Path) -> Result<String> {
+pub(super) fn file_hash(path: &Path) -> Result<String> {
     let content = fs::read(path)
         .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(content_hash(&content))
+}
+
+/// SHA-256 of in-memory content, formatted like `file_hash`.
+pub(super) fn content_hash(content: &[u8]) -> String {
     let mut hasher = Sha256::new();
-    hasher.update(&content);
-    Ok(format!("{:x}", hasher.finalize()))
+    hasher.update(content);
+    format!("{:x}", hasher.finalize())
 }
 
 /// Recursively list all files under a directory, returning relative paths.
@@ -270,6 +390,8 @@ fn matches_exclude(rel_path: &Path, exclude: Option<&[String]>) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sync::lockfile;
+    use crate::test_util::TestDir;
 
     #[test]
     fn test_matches_include_none() {
@@ -311,11 +433,49 @@ mod tests {
         assert!(matches_exclude(&path, Some(&exclude)));
     }
 
+    #[test]
+    fn test_locked_target_keeps_its_outputs() {
+        let workspace = TestDir::new("surfcontext-test-cross-repo-locked");
+        let repo = workspace.join("repo");
+        let other = workspace.join("other");
+        fs::create_dir_all(repo.join(".context/skills")).unwrap();
+        fs::create_dir_all(&other).unwrap();
+        fs::write(repo.join(".context/skills/a.md"), "skill\n").unwrap();
+        let config: SurfConfig = serde_json::from_str(
+            r#"{ "sync": { "skills": {
+                "source": ".context/skills/",
+                "targets": [{ "repo": "../other", "dest": "skills" }]
+            } } }"#,
+        )
+        .unwrap();
+        let opts = SyncOpts::default();
+        let sync = || {
+            let mut report = SyncReport::default();
+            sync_repos(&repo, &config, &opts, &mut report).unwrap();
+            lockfile::prune_and_write(&repo, &opts, &mut report).unwrap();
+            report
+        };
+
+        sync();
+        assert!(other.join("skills/a.md").exists());
+
+        let _held = RepoLock::acquire(&other, Duration::ZERO, |_| {}).unwrap();
+        let report = sync();
+        assert_eq!(report.warnings[0].code, Code::CrossRepoLocked);
+        assert_eq!(report.cross_repo_skipped, 1);
+        assert!(other.join("skills/a.md").exists());
+        assert!(
+            Lockfile::load(&repo)
+                .unwrap()
+                .outputs
+                .contains_key("../other/skills/a.md")
+        );
+    }
+
     #[test]
     fn test_file_hash_consistency() {
         // Write a temp file, hash it twice, ensure same result
-        let dir = std::env::temp_dir().join("surfcontext-test-hash");
-        let _ = fs::create_dir_all(&dir);
+        let dir = TestDir::new("surfcontext-test-hash");
         let file = dir.join("test.txt");
         fs::write(&file, "hello world").unwrap();
 
@@ -323,7 +483,5 @@ mod tests {
         let h2 = file_hash(&file).unwrap();
         assert_eq!(h1, h2);
         assert_eq!(h1.len(), 64); // SHA-256 hex length
-
-        let _ = fs::remove_dir_all(&dir);
     }
 }
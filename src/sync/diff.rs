@@ -0,0 +1,337 @@
+//! Line diffs between a generated file and what is on disk.
+//!
+//! Used to show hand edits to generated files, to preview writes with `surf sync --diff`
+//! and to carry edits back into CONTEXT.md.
+//! The LCS table only spans the lines between the common prefix and suffix, which for a
+//! typical edit is small. A rewrite of a large file would need a table of its line count
+//! squared, so above `MAX_LCS_CELLS` the changed region counts as one replaced block and a
+//! rendered diff just says `file changed (N → M lines)`.
+
+use colored::Colorize;
+use std::fmt;
+
+/// Context lines kept around each change.
+pub const CONTEXT_LINES: usize = 3;
+
+/// Largest LCS table (changed old lines × changed new lines) diffed line by line; 16 MB.
+pub const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// One line of a hunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Line<'a> {
+    Context(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// A run of changes with surrounding context, as in a unified diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk<'a> {
+    /// 0-based index of the hunk's first line in the old text.
+    pub old_start: usize,
+    /// 0-based index of the hunk's first line in the new text.
+    pub new_start: usize,
+    pub lines: Vec<Line<'a>>,
+}
+
+impl Hunk<'_> {
+    pub fn old_len(&self) -> usize {
+        self.lines
+            .iter()
+            .filter(|l| !matches!(l, Line::Added(_)))
+            .count()
+    }
+
+    pub fn new_len(&self) -> usize {
+        self.lines
+            .iter()
+            .filter(|l| !matches!(l, Line::Removed(_)))
+            .count()
+    }
+
+    /// The hunk in unified-diff form: header line, then prefixed lines.
+    pub fn to_text(&self) -> String {
+        let mut out = format!("{self}\n");
+        for line in &self.lines {
+            let (prefix, text) = match line {
+                Line::Context(t) => (' ', t),
+                Line::Removed(t) => ('-', t),
+                Line::Added(t) => ('+', t),
+            };
+            out.push(prefix);
+            out.push_str(text);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+impl fmt::Display for Hunk<'_> {
+    /// The `@@ -a,b +c,d @@` header line. As in `diff -u`, an empty side is numbered by
+    /// the line it follows, so a new file starts `@@ -0,0`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (old_len, new_len) = (self.old_len(), self.new_len());
+        let line = |start: usize, len: usize| if len == 0 { start } else { start + 1 };
+        write!(
+            f,
+            "@@ -{},{old_len} +{},{new_len} @@",
+            line(self.old_start, old_len),
+            line(self.new_start, new_len)
+        )
+    }
+}
+
+/// Hunks turning `old` into `new`, with `context` lines around each change.
+pub fn hunks<'a>(old: &[&'a str], new: &[&'a str], context: usize) -> Vec<Hunk<'a>> {
+    let script = edit_script(old, new);
+    let mut hunks: Vec<Hunk<'a>> = Vec::new();
+
+    let changes: Vec<usize> = script
+        .iter()
+        .enumerate()
+        .filter(|(_, step)| !matches!(step.line, Line::Context(_)))
+        .map(|(i, _)| i)
+        .collect();
+    let mut i = 0;
+    while i < changes.len() {
+        // Extend the group while the gap between changes fits in the shared context.
+        let mut last = changes[i];
+        let mut j = i + 1;
+        while j < changes.len() && changes[j] - last <= 2 * context + 1 {
+            last = changes[j];
+            j += 1;
+        }
+        let start = changes[i].saturating_sub(context);
+        let end = (last + context + 1).min(script.len());
+        hunks.push(Hunk {
+            old_start: script[start].old,
+            new_start: script[start].new,
+            lines: script[start..end].iter().map(|s| s.line).collect(),
+        });
+        i = j;
+    }
+    hunks
+}
+
+/// Unified diff of `old` -> `new`, or an empty string when they are equal.
+pub fn unified(old_name: &str, new_name: &str, old: &str, new: &str) -> String {
+    let (old_lines, new_lines): (Vec<&str>, Vec<&str>) =
+        (old.lines().collect(), new.lines().collect());
+    if old != new && too_large(&old_lines, &new_lines) {
+        return format!(
+            "--- {old_name}\n+++ {new_name}\nfile changed ({} → {} lines)\n",
+            old_lines.len(),
+            new_lines.len()
+        );
+    }
+    let hunks = hunks(&old_lines, &new_lines, CONTEXT_LINES);
+    if hunks.is_empty() {
+        return String::new();
+    }
+    let mut out = format!("--- {old_name}\n+++ {new_name}\n");
+    for hunk in &hunks {
+        out.push_str(&hunk.to_text());
+    }
+    out
+}
+
+/// A unified diff's lines, indented and colored.
+pub fn styled(diff: &str, indent: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    for line in diff.lines() {
+        let styled = if line.starts_with("+++") || line.starts_with("---") {
+            line.bold()
+        } else if line.starts_with('+') {
+            line.green()
+        } else if line.starts_with('-') {
+            line.red()
+        } else if line.starts_with("@@") {
+            line.cyan()
+        } else {
+            line.normal()
+        };
+        lines.push(format!("{indent}{styled}"));
+    }
+    lines
+}
+
+/// What writing `new` to `path` changes, styled and git-style: `a/` and `b/` prefixes, and
+/// `/dev/null` when the file does not exist yet (`old` is `None`).
+pub fn change(path: &str, old: Option<&[u8]>, new: &[u8], indent: &str) -> Vec<String> {
+    let old_text = old.map(std::str::from_utf8).unwrap_or(Ok(""));
+    let (Ok(old_text), Ok(new_text)) = (old_text, std::str::from_utf8(new)) else {
+        return vec![format!(
+            "{indent}{}",
+            format!("Binary file {path} differs").dimmed()
+        )];
+    };
+    let old_name = match old {
+        Some(_) => format!("a/{path}"),
+        None => "/dev/null".to_string(),
+    };
+    styled(
+        &unified(&old_name, &format!("b/{path}"), old_text, new_text),
+        indent,
+    )
+}
+
+/// One step of the edit script, with the old/new line indices it starts at.
+#[derive(Clone, Copy)]
+struct Step<'a> {
+    old: usize,
+    new: usize,
+    line: Line<'a>,
+}
+
+/// Lengths of the common prefix and suffix of `old` and `new`, which never overlap.
+fn common_ends(old: &[&str], new: &[&str]) -> (usize, usize) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    (prefix, suffix)
+}
+
+/// Whether the lines between the common prefix and suffix are too many for an LCS table.
+pub fn too_large(old: &[&str], new: &[&str]) -> bool {
+    let (prefix, suffix) = common_ends(old, new);
+    let changed = |len: usize| len - prefix - suffix;
+    changed(old.len()).saturating_mul(changed(new.len())) > MAX_LCS_CELLS
+}
+
+/// Full edit script (every line of both texts) from an LCS table. Without one (see
+/// `MAX_LCS_CELLS`) the changed region is removed and re-added rather than aligned.
+fn edit_script<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Step<'a>> {
+    let (prefix, suffix) = common_ends(old, new);
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    // lcs[i][j] = LCS length of a[i..] and b[j..]
+    let lcs = (!too_large(old, new)).then(|| {
+        let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i][j] = if a[i] == b[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        lcs
+    });
+    let removal_first = |i: usize, j: usize| {
+        j == b.len()
+            || lcs
+                .as_ref()
+                .is_none_or(|lcs| lcs[i + 1][j] >= lcs[i][j + 1])
+    };
+
+    let mut script = Vec::with_capacity(old.len() + new.len());
+    for (k, line) in old[..prefix].iter().enumerate() {
+        script.push(Step {
+            old: k,
+            new: k,
+            line: Line::Context(line),
+        });
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        let (old_idx, new_idx) = (prefix + i, prefix + j);
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            script.push(Step {
+                old: old_idx,
+                new: new_idx,
+                line: Line::Context(a[i]),
+            });
+            i += 1;
+            j += 1;
+        } else if i < a.len() && removal_first(i, j) {
+            // Removals before additions, as in `diff -u`.
+            script.push(Step {
+                old: old_idx,
+                new: new_idx,
+                line: Line::Removed(a[i]),
+            });
+            i += 1;
+        } else {
+            script.push(Step {
+                old: old_idx,
+                new: new_idx,
+                line: Line::Added(b[j]),
+            });
+            j += 1;
+        }
+    }
+    for k in 0..suffix {
+        let (old_idx, new_idx) = (old.len() - suffix + k, new.len() - suffix + k);
+        script.push(Step {
+            old: old_idx,
+            new: new_idx,
+            line: Line::Context(old[old_idx]),
+        });
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nb\nc\nd\nE\nf\ng\nh\ni\nj\nk\n";
+        assert_eq!(
+            unified("old", "new", old, new),
+            "--- old\n+++ new\n\
+             @@ -2,9 +2,10 @@\n b\n c\n d\n-e\n+E\n f\n g\n h\n i\n j\n+k\n"
+        );
+        assert_eq!(unified("old", "new", old, old), "");
+        assert_eq!(
+            unified("/dev/null", "new", "", "a\n"),
+            "--- /dev/null\n+++ new\n@@ -0,0 +1,1 @@\n+a\n"
+        );
+    }
+
+    #[test]
+    fn test_large_rewrites_are_summarized() {
+        let old: String = (0..2100).map(|n| format!("old {n}\n")).collect();
+        let new: String = (0..2100).map(|n| format!("new {n}\n")).collect();
+        assert_eq!(
+            unified("old", "new", &old, &new),
+            "--- old\n+++ new\nfile changed (2100 → 2100 lines)\n"
+        );
+
+        // A small edit to the same large file still gets a real hunk.
+        let edited = old.replacen("old 1000\n", "edited\n", 1);
+        assert!(unified("old", "new", &old, &edited).contains("@@ -998,7 +998,7 @@"));
+
+        let (old, new): (Vec<&str>, Vec<&str>) = (old.lines().collect(), new.lines().collect());
+        let hunks = hunks(&old, &new, 0);
+        assert_eq!(
+            (hunks.len(), hunks[0].old_len(), hunks[0].new_len()),
+            (1, 2100, 2100)
+        );
+    }
+
+    #[test]
+    fn test_distant_changes_get_separate_hunks() {
+        let old: Vec<String> = (0..20).map(|n| n.to_string()).collect();
+        let old: Vec<&str> = old.iter().map(String::as_str).collect();
+        let mut new = old.clone();
+        new[1] = "one";
+        new.insert(15, "extra");
+
+        let hunks = hunks(&old, &new, CONTEXT_LINES);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(hunks[0].to_string(), "@@ -1,5 +1,5 @@");
+        assert_eq!(hunks[1].to_string(), "@@ -13,6 +13,7 @@");
+        assert_eq!(hunks[1].lines[3], Line::Added("extra"));
+    }
+}
@@ -0,0 +1,470 @@
+//! `<!-- surf:... -->` directives embedded in CONTEXT.md.
+//!
+//! Conditional regions limit content to some platforms:
+//!
+//! ```text
+//! <!-- surf:only claude,cursor -->
+//! Only in CLAUDE.md and .cursorrules.
+//! <!-- surf:end -->
+//!
+//! <!-- surf:exclude codex -->
+//! Everywhere except AGENTS.md.
+//! <!-- surf:end -->
+//! ```
+//!
+//! Shared content can be pulled in from another file, optionally a single section of it:
+//!
+//! ```text
+//! <!-- surf:include .context/docs/stack.md#rust-toolchain -->
+//! ```
+//!
+//! Include paths are relative to the repo root and may not leave it. Includes nest,
+//! and cycles are an error. Expansion happens before any platform transform, so included
+//! text gets the same path rewriting as CONTEXT.md itself.
+//!
+//! Markers must sit on their own line and may nest. They are stripped from generated
+//! output. Markers inside fenced code blocks are treated as plain text.
+
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A problem found while checking directives, with a 1-based line number.
+#[derive(Debug, PartialEq)]
+pub struct DirectiveIssue {
+    pub line: usize,
+    pub message: String,
+}
+
+/// A parsed directive line.
+enum Directive<'a> {
+    Only(Vec<&'a str>),
+    Exclude(Vec<&'a str>),
+    End,
+    Include(&'a str),
+    Unknown(&'a str),
+}
+
+/// Parse a `<!-- surf:... -->` line, if it is one.
+fn parse_directive(line: &str) -> Option<Directive<'_>> {
+    let inner = line
+        .trim()
+        .strip_prefix("<!--")?
+        .strip_suffix("-->")?
+        .trim()
+        .strip_prefix("surf:")?;
+    let (keyword, args) = inner.split_once(char::is_whitespace).unwrap_or((inner, ""));
+    let platforms = || {
+        args.split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .collect()
+    };
+    Some(match keyword {
+        "only" => Directive::Only(platforms()),
+        "exclude" => Directive::Exclude(platforms()),
+        "end" => Directive::End,
+        "include" => Directive::Include(args.trim()),
+        other => Directive::Unknown(other),
+    })
+}
+
+/// Whether `line` opens or closes a fenced code block.
+pub(crate) fn is_fence(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with("```") || trimmed.starts_with("~~~")
+}
+
+/// Report unbalanced regions, unknown directives and unknown platform names.
+pub fn check(content: &str, known_platforms: &[&str]) -> Vec<DirectiveIssue> {
+    let mut issues = Vec::new();
+    let mut open: Vec<usize> = Vec::new();
+    let mut in_fence = false;
+
+    for (idx, line) in content.lines().enumerate() {
+        let line_num = idx + 1;
+        if is_fence(line) {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        let Some(directive) = parse_directive(line) else {
+            continue;
+        };
+        match directive {
+            Directive::Only(platforms) | Directive::Exclude(platforms) => {
+                if platforms.is_empty() {
+                    issues.push(DirectiveIssue {
+                        line: line_num,
+                        message: "directive lists no platforms".to_string(),
+                    });
+                }
+                for p in platforms {
+                    if !known_platforms.contains(&p) {
+                        issues.push(DirectiveIssue {
+                            line: line_num,
+                            message: format!("unknown platform `{p}`"),
+                        });
+                    }
+                }
+                open.push(line_num);
+            }
+            Directive::End => {
+                if open.pop().is_none() {
+                    issues.push(DirectiveIssue {
+                        line: line_num,
+                        message: "`surf:end` without a matching `surf:only`/`surf:exclude`"
+                            .to_string(),
+                    });
+                }
+            }
+            Directive::Include(target) => {
+                if target.is_empty() || target.starts_with('#') {
+                    issues.push(DirectiveIssue {
+                        line: line_num,
+                        message: "`surf:include` needs a file path".to_string(),
+                    });
+                }
+            }
+            Directive::Unknown(keyword) => {
+                issues.push(DirectiveIssue {
+                    line: line_num,
+                    message: format!("unknown directive `surf:{keyword}`"),
+                });
+            }
+        }
+    }
+
+    for line in open {
+        issues.push(DirectiveIssue {
+            line,
+            message: "region is never closed with `<!-- surf:end -->`".to_string(),
+        });
+    }
+
+    issues.sort_by_key(|i| i.line);
+    issues
+}
+
+/// Keep only the content visible to `platform` and strip the region markers.
+/// Unclosed regions run to the end of the file; stray `surf:end` lines are dropped.
+pub fn apply_conditionals(content: &str, platform: &str) -> String {
+    let mut output = String::with_capacity(content.len());
+    // One entry per open region: whether it admits this platform.
+    let mut regions: Vec<bool> = Vec::new();
+    let mut in_fence = false;
+
+    for line in content.lines() {
+        if is_fence(line) {
+            in_fence = !in_fence;
+        } else if !in_fence {
+            match parse_directive(line) {
+                Some(Directive::Only(platforms)) => {
+                    regions.push(platforms.contains(&platform));
+                    continue;
+                }
+                Some(Directive::Exclude(platforms)) => {
+                    regions.push(!platforms.contains(&platform));
+                    continue;
+                }
+                Some(Directive::End) => {
+                    regions.pop();
+                    continue;
+                }
+                Some(Directive::Include(_) | Directive::Unknown(_)) | None => {}
+            }
+        }
+
+        if regions.iter().all(|visible| *visible) {
+            output.push_str(line);
+            output.push('\n');
+        }
+    }
+
+    output
+}
+
+/// Content after `surf:include` expansion, plus every file that was pulled in.
+#[derive(Debug)]
+pub struct Expanded {
+    pub content: String,
+    /// Included files, relative to the repo root, in first-use order.
+    pub includes: Vec<PathBuf>,
+}
+
+/// Expand all `surf:include` directives in `content`, which was read from `source`
+/// (a repo-relative path used for error messages and cycle detection).
+pub fn expand_includes(repo_root: &Path, source: &str, content: &str) -> Result<Expanded> {
+    let root = repo_root
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {}", repo_root.display()))?;
+    let mut includes = Vec::new();
+    let mut stack = vec![source.to_string()];
+    let content = expand_recursive(&root, source, content, &mut stack, &mut includes)?;
+    Ok(Expanded { content, includes })
+}
+
+fn expand_recursive(
+    root: &Path,
+    source: &str,
+    content: &str,
+    stack: &mut Vec<String>,
+    includes: &mut Vec<PathBuf>,
+) -> Result<String> {
+    let mut output = String::with_capacity(content.len());
+    let mut in_fence = false;
+
+    for (idx, line) in content.lines().enumerate() {
+        if is_fence(line) {
+            in_fence = !in_fence;
+        }
+        let target = match parse_directive(line) {
+            Some(Directive::Include(target)) if !in_fence => target,
+            _ => {
+                output.push_str(line);
+                output.push('\n');
+                continue;
+            }
+        };
+
+        let at = format!("{source}:{}", idx + 1);
+        let (file, anchor) = match target.split_once('#') {
+            Some((file, anchor)) => (file, Some(anchor)),
+            None => (target, None),
+        };
+        if file.is_empty() {
+            bail!("{at}: `surf:include` needs a file path");
+        }
+
+        let full = root.join(file);
+        let canonical = full
+            .canonicalize()
+            .with_context(|| format!("{at}: included file `{file}` not found"))?;
+        let rel = match canonical.strip_prefix(root) {
+            Ok(rel) if !Path::new(file).is_absolute() => rel.to_path_buf(),
+            _ => bail!("{at}: included file `{file}` is outside the repository"),
+        };
+
+        let key = match anchor {
+            Some(anchor) => format!("{}#{anchor}", rel.display()),
+            None => rel.display().to_string(),
+        };
+        if stack.contains(&key) {
+            bail!("{at}: include cycle: {} -> {key}", stack.join(" -> "));
+        }
+
+        let text = fs::read_to_string(&canonical)
+            .with_context(|| format!("{at}: failed to read `{file}`"))?;
+        let text = match anchor {
+            Some(anchor) => extract_section(&text, anchor)
+                .with_context(|| format!("{at}: no heading matching `#{anchor}` in `{file}`"))?,
+            None => text,
+        };
+
+        stack.push(key);
+        let expanded = expand_recursive(root, &rel.display().to_string(), &text, stack, includes)?;
+        stack.pop();
+
+        if !includes.contains(&rel) {
+            includes.push(rel);
+        }
+        output.push_str(&expanded);
+    }
+
+    Ok(output)
+}
+
+/// GitHub-style heading anchor: lowercase, spaces to dashes, punctuation dropped.
+fn slugify(heading: &str) -> String {
+    heading
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Return the section under the heading whose anchor is `anchor`, heading included,
+/// up to the next heading of the same or higher level.
+fn extract_section(content: &str, anchor: &str) -> Option<String> {
+    let mut output = String::new();
+    let mut level: Option<usize> = None;
+    let mut in_fence = false;
+
+    for line in content.lines() {
+        if is_fence(line) {
+            in_fence = !in_fence;
+        }
+        let heading_level = line.chars().take_while(|c| *c == '#').count();
+        let is_heading =
+            !in_fence && (1..=6).contains(&heading_level) && line[heading_level..].starts_with(' ');
+
+        match level {
+            None if is_heading && slugify(&line[heading_level..]) == anchor => {
+                level = Some(heading_level);
+            }
+            None => continue,
+            Some(l) if is_heading && heading_level <= l => break,
+            Some(_) => {}
+        }
+        output.push_str(line);
+        output.push('\n');
+    }
+
+    level.map(|_| output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestDir;
+
+    const KNOWN: &[&str] = &["claude", "codex", "cursor"];
+
+    #[test]
+    fn test_only_and_exclude_regions() {
+        let input = "a\n<!-- surf:only claude,cursor -->\nb\n<!-- surf:end -->\n\
+                     <!-- surf:exclude codex -->\nc\n<!-- surf:end -->\nd\n";
+        assert_eq!(apply_conditionals(input, "claude"), "a\nb\nc\nd\n");
+        assert_eq!(apply_conditionals(input, "codex"), "a\nd\n");
+        assert_eq!(apply_conditionals(input, "cursor"), "a\nb\nc\nd\n");
+    }
+
+    #[test]
+    fn test_nested_regions() {
+        let input = "<!-- surf:exclude codex -->\nx\n<!-- surf:only cursor -->\ny\n\
+                     <!-- surf:end -->\nz\n<!-- surf:end -->\n";
+        assert_eq!(apply_conditionals(input, "claude"), "x\nz\n");
+        assert_eq!(apply_conditionals(input, "cursor"), "x\ny\nz\n");
+        assert_eq!(apply_conditionals(input, "codex"), "");
+    }
+
+    #[test]
+    fn test_markers_in_code_fences_are_literal() {
+        let input = "```\n<!-- surf:only codex -->\n```\nkept\n";
+        assert_eq!(apply_conditionals(input, "claude"), input);
+        assert!(check(input, KNOWN).is_empty());
+    }
+
+    #[test]
+    fn test_check_reports_problems_with_lines() {
+        let input = "<!-- surf:end -->\n<!-- surf:only claud -->\nx\n<!-- surf:frobnicate -->\n";
+        let issues = check(input, KNOWN);
+        let summary: Vec<(usize, &str)> = issues
+            .iter()
+            .map(|i| (i.line, i.message.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    1,
+                    "`surf:end` without a matching `surf:only`/`surf:exclude`"
+                ),
+                (2, "unknown platform `claud`"),
+                (2, "region is never closed with `<!-- surf:end -->`"),
+                (4, "unknown directive `surf:frobnicate`"),
+            ]
+        );
+    }
+
+    fn include_fixture(name: &str, files: &[(&str, &str)]) -> TestDir {
+        TestDir::new(name).with_files(files)
+    }
+
+    #[test]
+    fn test_include_whole_file_and_section() {
+        let dir = include_fixture(
+            "surfcontext-test-include",
+            &[(
+                ".context/docs/stack.md",
+                "# Stack\n\n## Rust Toolchain\n\nUse 1.93.\n\n### Lints\n\nclippy\n\n## Node\n\nnope\n",
+            )],
+        );
+
+        let input = "# Repo\n<!-- surf:include .context/docs/stack.md#rust-toolchain -->\nEnd\n";
+        let expanded = expand_includes(&dir, "CONTEXT.md", input).unwrap();
+        assert_eq!(
+            expanded.content,
+            "# Repo\n## Rust Toolchain\n\nUse 1.93.\n\n### Lints\n\nclippy\n\nEnd\n"
+        );
+        assert_eq!(
+            expanded.includes,
+            vec![PathBuf::from(".context/docs/stack.md")]
+        );
+
+        let input = "<!-- surf:include .context/docs/stack.md -->\n";
+        let expanded = expand_includes(&dir, "CONTEXT.md", input).unwrap();
+        assert!(expanded.content.contains("## Node"));
+    }
+
+    #[test]
+    fn test_include_nested_and_cycles() {
+        let dir = include_fixture(
+            "surfcontext-test-include-cycle",
+            &[
+                ("a.md", "A\n<!-- surf:include b.md -->\n"),
+                ("b.md", "B\n"),
+                ("loop.md", "L\n<!-- surf:include CONTEXT.md -->\n"),
+                ("CONTEXT.md", "<!-- surf:include loop.md -->\n"),
+            ],
+        );
+
+        let expanded = expand_includes(&dir, "CONTEXT.md", "<!-- surf:include a.md -->\n").unwrap();
+        assert_eq!(expanded.content, "A\nB\n");
+        assert_eq!(
+            expanded.includes,
+            vec![PathBuf::from("b.md"), PathBuf::from("a.md")]
+        );
+
+        let err = expand_includes(&dir, "CONTEXT.md", "<!-- surf:include loop.md -->\n")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("include cycle: CONTEXT.md -> loop.md -> CONTEXT.md"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_include_rejects_escape_and_missing() {
+        let outer = include_fixture(
+            "surfcontext-test-include-escape",
+            &[("repo/x.md", "x\n"), ("secret.md", "secret\n")],
+        );
+        let dir = outer.join("repo");
+
+        let err = expand_includes(&dir, "CONTEXT.md", "<!-- surf:include ../secret.md -->\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("outside the repository"), "{err}");
+
+        let err = expand_includes(&dir, "CONTEXT.md", "<!-- surf:include nope.md -->\n")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("CONTEXT.md:1: included file `nope.md` not found"),
+            "{err}"
+        );
+
+        let err = expand_includes(&dir, "CONTEXT.md", "<!-- surf:include x.md#missing -->\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("no heading matching `#missing`"), "{err}");
+    }
+
+    #[test]
+    fn test_check_clean() {
+        let input =
+            "<!-- surf:only claude -->\nx\n<!-- surf:end -->\n<!-- surf:include a.md#b -->\n";
+        assert!(check(input, KNOWN).is_empty());
+        let issues = check("<!-- surf:include #b -->\n", KNOWN);
+        assert_eq!(issues[0].message, "`surf:include` needs a file path");
+    }
+}
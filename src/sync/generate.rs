@@ -2,9 +2,35 @@ use anyhow::{Context, Result};
 use colored::Colorize;
 use std::fs;
 use std::path::Path;
-
+use std::time::Duration;
+
+use super::cross_repo::content_hash;
+use super::lockfile::{LockEntry, Lockfile, OutputKind};
+use super::platforms::{PlatformRegistry, ResolvedPlatform};
+use super::report::{Action, ActionKind, Code, Diagnostic, Stage};
+use super::rewrite::PathRewriter;
+use super::{atomic, diff, directives, script, sections};
 use super::{SyncOpts, SyncReport};
-use crate::config::SurfConfig;
+use crate::config::{PlatformGen, SurfConfig};
+
+/// Marker line at the top of every file generated from CONTEXT.md.
+pub(crate) const GENERATED_HEADER: &str = "<!-- GENERATED — edit CONTEXT.md, then run surf sync -->";
+
+/// Separates the header text from the body hash stamped into it.
+const HASH_MARKER: &str = " · sha256:";
+
+/// Hex digits of the body hash kept in the header.
+const HASH_LEN: usize = 16;
+
+/// Whether a generated output on disk still matches what surf last wrote there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EditState {
+    Pristine,
+    /// Someone changed the file after surf generated it.
+    Edited,
+    /// No header hash and no lockfile entry to compare against.
+    Unknown,
+}
 
 /// Generate all platform-specific files based on config.
 pub fn generate_all(
@@ -15,12 +41,20 @@ pub fn generate_all(
 ) -> Result<()> {
     let context_path = repo_root.join(&config.canonical.root_context);
     if !context_path.exists() {
-        if !opts.quiet {
-            println!(
-                "  {} {} not found, skipping generation",
-                "[skip]".dimmed(),
-                config.canonical.root_context
-            );
+        report.progress(format!(
+            "  {} {} not found, skipping generation",
+            "[skip]".dimmed(),
+            config.canonical.root_context
+        ));
+        // The platforms are still configured, so their outputs must not be pruned as stale.
+        let previous = Lockfile::load(repo_root)?;
+        let registry = PlatformRegistry::builtin();
+        for (platform, platform_gen) in &config.generation {
+            if let Ok(resolved) = registry.resolve(platform, platform_gen)
+                && let Some(entry) = previous.outputs.get(&resolved.output)
+            {
+                report.outputs.insert(resolved.output, entry.clone());
+            }
         }
         return Ok(());
     }
@@ -28,55 +62,153 @@ pub fn generate_all(
     let context_content = fs::read_to_string(&context_path)
         .with_context(|| format!("Failed to read {}", context_path.display()))?;
 
-    for (platform, platform_gen) in &config.generation {
-        let output_name = platform_gen.root_context.as_deref().unwrap_or(
-            match platform.as_str() {
-                "claude" => "CLAUDE.md",
-                "codex" => "AGENTS.md",
-                "cursor" => ".cursorrules",
-                _ => "CLAUDE.md",
-            },
-        );
+    // Resolve every entry up front so a config error aborts before anything is written.
+    // Unknown platforms stop the run before any stage; they only get here, to be skipped,
+    // when `unknown-platform` is demoted in `diagnostics`.
+    let registry = PlatformRegistry::builtin();
+    let mut platforms: Vec<_> = config
+        .generation
+        .iter()
+        .filter(|(name, _)| registry.get(name).is_some())
+        .collect();
+    platforms.sort_by_key(|(name, _)| name.as_str());
+    let resolved = platforms
+        .into_iter()
+        .map(|(platform, platform_gen)| {
+            Ok((registry.resolve(platform, platform_gen)?, platform_gen))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Check directives in CONTEXT.md and everything it includes, then expand includes.
+    let expanded =
+        directives::expand_includes(repo_root, &config.canonical.root_context, &context_content)?;
+    let mut sources = vec![(config.canonical.root_context.clone(), context_content)];
+    for include in &expanded.includes {
+        let content = fs::read_to_string(repo_root.join(include))?;
+        sources.push((include.display().to_string(), content));
+    }
+    for (source, content) in &sources {
+        for issue in directives::check(content, &registry.names()) {
+            let msg = format!("{source}:{}: {}", issue.line, issue.message);
+            report
+                .warn(Diagnostic::new(Code::Directive, Stage::Generate, msg).path(source.clone()));
+        }
+    }
+    if opts.verbose && !expanded.includes.is_empty() {
+        let names: Vec<_> = sources.iter().skip(1).map(|(s, _)| s.as_str()).collect();
+        report.progress(format!("  {} {}", "Includes:".dimmed(), names.join(", ")));
+    }
+    let context_content = expanded.content;
+    // Covers CONTEXT.md and everything it includes.
+    let source_hash = content_hash(context_content.as_bytes());
 
+    let previous = Lockfile::load(repo_root)?;
+    for (platform, platform_gen) in resolved {
+        let output_name = platform.output.as_str();
         let output_path = repo_root.join(output_name);
-        let method = platform_gen
-            .root_context_method
-            .as_deref()
-            .unwrap_or(&platform_gen.method);
-
-        let generated = match method {
-            "sed-copy" => generate_claude_md(&context_content),
-            "template-copy" => generate_agents_md(&context_content),
-            _ => generate_claude_md(&context_content),
-        };
+        let generated = stamp(&render(
+            repo_root,
+            config,
+            &platform,
+            platform_gen,
+            &context_content,
+        )?);
 
         // Check if output already matches
-        let needs_write = if output_path.exists() {
-            let existing = fs::read_to_string(&output_path)?;
-            existing != generated
+        let existing = if output_path.exists() {
+            Some(fs::read_to_string(&output_path)?)
         } else {
-            true
+            None
         };
+        let needs_write = existing.as_deref() != Some(generated.as_str());
+
+        // Never silently overwrite hand edits; they belong in CONTEXT.md.
+        if let Some(existing) = existing.as_deref()
+            && needs_write
+            && !opts.force
+            && unstamp(existing).0 != unstamp(&generated).0
+            && edit_state(existing, previous.outputs.get(output_name)) == EditState::Edited
+        {
+            let msg = format!(
+                "{output_name} was edited by hand since the last sync; not overwriting \
+                 (surf backport moves the edits into {}, surf sync --force discards them)",
+                config.canonical.root_context
+            );
+            report.warn(Diagnostic::new(Code::HandEdited, Stage::Generate, msg).path(output_name));
+            let diff = diff::unified(
+                &format!("{output_name} (generated)"),
+                &format!("{output_name} (on disk)"),
+                &unstamp(&generated).0,
+                &unstamp(existing).0,
+            );
+            for line in diff::styled(&diff, "    ") {
+                report.progress(line);
+            }
+            // The file on disk is still the hand-edited one, so keep the old entry.
+            let entry = previous.outputs.get(output_name).cloned().unwrap_or_else(|| {
+                LockEntry::new(OutputKind::Generated, &config.canonical.root_context)
+            });
+            report.outputs.insert(output_name.to_string(), entry);
+            continue;
+        }
+
+        report.outputs.insert(
+            output_name.to_string(),
+            LockEntry::new(OutputKind::Generated, &config.canonical.root_context)
+                .hashes(source_hash.clone(), content_hash(generated.as_bytes())),
+        );
 
         if needs_write {
             if !opts.dry_run {
-                fs::write(&output_path, &generated)?;
+                if let Some(parent) = output_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                report.trash.before_write(&output_path)?;
+                atomic::write(&output_path, &generated)?;
             }
             let line_count = generated.lines().count();
-            if !opts.quiet {
-                println!(
-                    "  {} {} ({} lines) {}",
-                    "Generated".green(),
+            report.progress(format!(
+                "  {} {} ({} lines) {}",
+                "Generated".green(),
+                output_name,
+                line_count,
+                if opts.dry_run { "(dry run)" } else { "" }
+            ));
+            if opts.diff {
+                // Compare bodies; the header hash changes with every edit anyway.
+                let old_body = existing.as_deref().map(|e| unstamp(e).0);
+                for line in diff::change(
                     output_name,
-                    line_count,
-                    if opts.dry_run { "(dry run)" } else { "" }
-                );
+                    old_body.as_deref().map(str::as_bytes),
+                    unstamp(&generated).0.as_bytes(),
+                    "    ",
+                ) {
+                    report.progress(line);
+                }
+            } else if opts.dry_run && platform_gen.root_context_script.is_some() {
+                // Script output is opaque, so show exactly what would be written.
+                for line in generated.lines() {
+                    report.progress(format!("    {} {}", "|".dimmed(), line));
+                }
             }
-            report.updated += 1;
+            report.record(
+                Action::new(
+                    Stage::Generate,
+                    if existing.is_some() {
+                        ActionKind::Update
+                    } else {
+                        ActionKind::Create
+                    },
+                    output_name,
+                    format!("regenerate from {}", config.canonical.root_context),
+                )
+                .hashes(
+                    existing.as_deref().map(|e| content_hash(e.as_bytes())),
+                    Some(content_hash(generated.as_bytes())),
+                ),
+            );
         } else {
-            if !opts.quiet {
-                println!("  {} {}", output_name, "(unchanged)".dimmed());
-            }
+            report.progress(format!("  {} {}", output_name, "(unchanged)".dimmed()));
             report.unchanged += 1;
         }
     }
@@ -84,141 +216,359 @@ pub fn generate_all(
     Ok(())
 }
 
-/// Generate CLAUDE.md from CONTEXT.md by path-substituting .context/ -> .claude/.
-fn generate_claude_md(context_content: &str) -> String {
-    let mut output = String::with_capacity(context_content.len() + 500);
+/// Render `platform`'s root context file from `context` (CONTEXT.md as written, includes
+/// not yet expanded) exactly as `surf sync` would write it, without writing anything.
+///
+/// For previews in editors and the playground. Includes are read relative to `repo_root`.
+pub fn render_platform(
+    repo_root: &Path,
+    config: &SurfConfig,
+    platform: &str,
+    context: &str,
+) -> Result<String> {
+    let default_gen = PlatformGen::default();
+    let platform_gen = config.generation.get(platform).unwrap_or(&default_gen);
+    let registry = PlatformRegistry::builtin();
+    let resolved = registry.resolve(platform, platform_gen)?;
+    let expanded = directives::expand_includes(repo_root, &config.canonical.root_context, context)?;
+    Ok(stamp(&render(
+        repo_root,
+        config,
+        &resolved,
+        platform_gen,
+        &expanded.content,
+    )?))
+}
 
-    output.push_str("<!-- SurfContext ARDS v3.0 — surfcontext.org -->\n");
-    output.push_str("<!-- GENERATED — edit CONTEXT.md, then run surf sync -->\n");
+/// Produce one platform's root context file from the expanded CONTEXT.md, unstamped.
+pub(super) fn render(
+    repo_root: &Path,
+    config: &SurfConfig,
+    platform: &ResolvedPlatform,
+    platform_gen: &PlatformGen,
+    context_content: &str,
+) -> Result<String> {
+    let context_content =
+        directives::apply_conditionals(context_content, platform.generator.name());
+    match &platform_gen.root_context_script {
+        Some(script_path) => {
+            let timeout = Duration::from_secs(
+                platform_gen
+                    .root_context_script_timeout
+                    .unwrap_or(script::DEFAULT_TIMEOUT_SECS),
+            );
+            script::run_context_script(
+                repo_root,
+                script_path,
+                platform.generator.name(),
+                &context_content,
+                timeout,
+            )
+        }
+        None => {
+            let filtered = sections::apply(&context_content, &platform_gen.sections)
+                .with_context(|| {
+                    format!("Invalid sections for platform `{}`", platform.generator.name())
+                })?;
+            platform.generator.transform(
+                &platform.method,
+                &filtered,
+                &rewriter_for(config, platform, platform_gen),
+            )
+        }
+    }
+}
 
-    let transformed = context_content
-        .replace(".context/docs/", ".claude/docs/")
-        .replace(".context/agents/", ".claude/agents/")
-        .replace(".context/skills/", ".claude/skills/")
-        .replace(".context/guides/", ".claude/guides/")
-        .replace(".context/queue.md", ".claude/queue.md")
-        .replace(".context/", ".claude/");
+/// Path rewrites applied to a platform's output.
+pub(super) fn rewriter_for(
+    config: &SurfConfig,
+    platform: &ResolvedPlatform,
+    platform_gen: &PlatformGen,
+) -> PathRewriter {
+    // Without a link farm there is nothing to point at, so paths stay canonical.
+    match platform.generator.link_dir() {
+        Some(link_dir) => PathRewriter::for_platform(config, Some(platform_gen), link_dir),
+        None => PathRewriter::default(),
+    }
+}
 
-    output.push_str(&transformed);
-    output.push_str(PATH_ENFORCEMENT_RULE);
-    output
+/// Stamp the generated header with a hash of the whole file (taken with the plain header),
+/// so later syncs can tell whether someone edited it. Files without the header, such as
+/// `rootContextScript` output, are returned unchanged.
+pub(crate) fn stamp(content: &str) -> String {
+    if !content.lines().take(3).any(|l| l == GENERATED_HEADER) {
+        return content.to_string();
+    }
+    let hash = content_hash(content.as_bytes());
+    content.replacen(
+        GENERATED_HEADER,
+        &format!(
+            "{}{HASH_MARKER}{} -->",
+            header_prefix(),
+            &hash[..HASH_LEN]
+        ),
+        1,
+    )
+}
+
+/// Reverse `stamp`: the content with a plain header, and the hash the header carried.
+pub(crate) fn unstamp(content: &str) -> (String, Option<String>) {
+    let stamped = content.lines().take(3).find_map(|line| {
+        let hash = line
+            .strip_prefix(header_prefix())?
+            .strip_prefix(HASH_MARKER)?
+            .strip_suffix(" -->")?;
+        Some((line, hash))
+    });
+    match stamped {
+        Some((line, hash)) => (
+            content.replacen(line, GENERATED_HEADER, 1),
+            Some(hash.to_string()),
+        ),
+        None => (content.to_string(), None),
+    }
 }
 
-/// Generate AGENTS.md from CONTEXT.md by stripping persona and some sections.
-fn generate_agents_md(context_content: &str) -> String {
+/// Whether `line` is the generated header, stamped or not.
+pub(crate) fn is_generated_header(line: &str) -> bool {
+    line == GENERATED_HEADER
+        || line
+            .strip_prefix(header_prefix())
+            .is_some_and(|rest| rest.starts_with(HASH_MARKER))
+}
+
+/// Compare an output on disk against its header hash, falling back to the lockfile for
+/// files written before headers carried one. A stamp that is not a full `HASH_LEN` hash
+/// was edited by hand.
+pub(crate) fn edit_state(existing: &str, entry: Option<&LockEntry>) -> EditState {
+    let (plain, hash) = unstamp(existing);
+    let pristine = match (hash, entry.and_then(|e| e.output_hash.as_deref())) {
+        (Some(hash), _) => {
+            hash.len() == HASH_LEN && content_hash(plain.as_bytes()).starts_with(&hash)
+        }
+        (None, Some(output_hash)) => content_hash(existing.as_bytes()) == output_hash,
+        (None, None) => return EditState::Unknown,
+    };
+    if pristine {
+        EditState::Pristine
+    } else {
+        EditState::Edited
+    }
+}
+
+fn header_prefix() -> &'static str {
+    GENERATED_HEADER.trim_end_matches(" -->")
+}
+
+/// Generate CLAUDE.md from CONTEXT.md by rewriting canonical paths into the link farm.
+pub(crate) fn generate_claude_md(
+    context_content: &str,
+    rewriter: &PathRewriter,
+    link_dir: Option<&str>,
+) -> String {
     let mut output = String::with_capacity(context_content.len() + 500);
 
     output.push_str("<!-- SurfContext ARDS v3.0 — surfcontext.org -->\n");
-    output.push_str("<!-- GENERATED — edit CONTEXT.md, then run surf sync -->\n");
+    output.push_str(GENERATED_HEADER);
     output.push('\n');
 
-    let mut state = AgentsState::Normal;
-    let lines: Vec<&str> = context_content.lines().collect();
-    let mut i = 0;
-
-    while i < lines.len() {
-        let line = lines[i];
-
-        match state {
-            AgentsState::Normal => {
-                // Replace persona title
-                if line.starts_with("# ") && line.contains("Advisor Agent") {
-                    // Replace with neutral title, derive from the line
-                    let repo_name = line
-                        .trim_start_matches("# ")
-                        .replace("Advisor Agent", "Repository")
-                        .replace("Strategic ", "Strategy ");
-                    output.push_str(&format!("# {repo_name}\n"));
-                    i += 1;
-                    continue;
-                }
-
-                // Replace persona paragraph ("You are the strategic advisor...")
-                if line.starts_with("You are the strategic advisor for") {
-                    output.push_str("This is the strategic command center for CloudSurf Software LLC, a bootstrapped software company. Contains business strategy, agent definitions, knowledge docs, patent drafts, academic research, and plans. Markdown and LaTeX only -- no application code.\n");
-                    output.push('\n');
-                    // Skip until next blank line
-                    i += 1;
-                    while i < lines.len() && !lines[i].is_empty() {
-                        i += 1;
-                    }
-                    continue;
-                }
-
-                // Skip "## Your Expertise" through "## Core Principles"
-                if line == "## Your Expertise" {
-                    state = AgentsState::SkippingSection("## Core Principles");
-                    i += 1;
-                    continue;
-                }
+    let transformed = rewriter.apply(context_content);
 
-                // Skip "## Commands" through "## Key Repositories"
-                if line == "## Commands" {
-                    state = AgentsState::SkippingSection("## Key Repositories");
-                    i += 1;
-                    continue;
-                }
+    output.push_str(&transformed);
+    output.push_str(&path_enforcement_rule(link_dir));
+    output
+}
 
-                output.push_str(line);
-                output.push('\n');
-            }
-            AgentsState::SkippingSection(target) => {
-                if line == target {
-                    // Found the target heading — emit it and return to normal
-                    output.push_str(line);
-                    output.push('\n');
-                    state = AgentsState::Normal;
-                }
-                // Otherwise skip the line
-            }
-        }
-        i += 1;
-    }
+/// Generate AGENTS.md from CONTEXT.md, keeping `.context/` paths as-is.
+/// Persona and section stripping is configured per platform (see `sections`).
+pub(super) fn generate_agents_md(context_content: &str, link_dir: Option<&str>) -> String {
+    let mut output = String::with_capacity(context_content.len() + 500);
 
-    output.push_str(PATH_ENFORCEMENT_RULE);
+    output.push_str("<!-- SurfContext ARDS v3.0 — surfcontext.org -->\n");
+    output.push_str(GENERATED_HEADER);
+    output.push('\n');
+    output.push('\n');
+    output.push_str(context_content);
+    output.push_str(&path_enforcement_rule(link_dir));
     output
 }
 
 /// Rule injected into ALL generated platform files (CLAUDE.md, AGENTS.md, .cursorrules).
-/// Tells agents to write to .context/ (source of truth), not .claude/ (symlinked).
-const PATH_ENFORCEMENT_RULE: &str = "\n\n\
+/// Tells agents to write to .context/ (source of truth), not the platform dir (symlinked).
+/// Platforms without a link dir (Copilot) get the rule in terms of `.context/` alone.
+fn path_enforcement_rule(link_dir: Option<&str>) -> String {
+    let Some(dir) = link_dir else {
+        return "\n\n\
 <!-- surf sync: path enforcement rule (injected automatically) -->\n\
 ## Source of Truth: `.context/`\n\
 \n\
-The `.claude/` directory is **generated** — its subdirectories (`docs/`, `agents/`, `guides/`, `skills/`) \
-are symlinks to `.context/`. The queue file (`.claude/queue.md`) is a copy.\n\
+Docs, agents, guides, skills and the queue live in `.context/`.\n\
 \n\
 **Rules for all agents (Claude, Codex, Cursor)**:\n\
 - When **creating or editing** files in docs, agents, guides, or skills: always use `.context/` paths \
-(e.g., `.context/docs/foo.md`, NOT `.claude/docs/foo.md`)\n\
+(e.g., `.context/docs/foo.md`)\n\
+- When **telling the user** or **telling subagents** where a file is: always say `.context/`\n\
+- When **referencing paths in documents** you write: always use `.context/`\n\
+- To edit the queue: edit `.context/queue.md`, then run `surf sync`\n\
+"
+        .to_string();
+    };
+    format!(
+        "\n\n\
+<!-- surf sync: path enforcement rule (injected automatically) -->\n\
+## Source of Truth: `.context/`\n\
+\n\
+The `{dir}/` directory is **generated** — its subdirectories (`docs/`, `agents/`, `guides/`, `skills/`) \
+are symlinks to `.context/`. The queue file (`{dir}/queue.md`) is a copy.\n\
+\n\
+**Rules for all agents (Claude, Codex, Cursor)**:\n\
+- When **creating or editing** files in docs, agents, guides, or skills: always use `.context/` paths \
+(e.g., `.context/docs/foo.md`, NOT `{dir}/docs/foo.md`)\n\
 - When **reading** files: either path works (symlinks resolve the same), but prefer `.context/`\n\
 - When **telling the user** or **telling subagents** where a file is: always say `.context/`\n\
 - When **referencing paths in documents** you write: always use `.context/`\n\
-- **Never** create new directories inside `.claude/` — create them in `.context/` and run `surf sync`\n\
-- To edit the queue: edit `.context/queue.md` (`.claude/queue.md` is overwritten by sync)\n\
+- **Never** create new directories inside `{dir}/` — create them in `.context/` and run `surf sync`\n\
+- To edit the queue: edit `.context/queue.md` (`{dir}/queue.md` is overwritten by sync)\n\
 \n\
-This rule is enforced by `surf sync` which audits `.context/` files for accidental `.claude/` references.\n\
-";
-
-#[derive(Clone, Copy)]
-enum AgentsState<'a> {
-    Normal,
-    SkippingSection(&'a str),
+This rule is enforced by `surf sync` which audits `.context/` files for accidental `{dir}/` references.\n\
+"
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::TestDir;
+    use crate::config::SectionRules;
 
     #[test]
     fn test_claude_md_path_transform() {
         let input = "See `.context/docs/foo.md` and `.context/agents/bar.md`.\n";
-        let result = generate_claude_md(input);
+        let config: crate::config::SurfConfig = serde_json::from_str("{}").unwrap();
+        let rewriter = PathRewriter::for_platform(&config, None, ".claude");
+        let result = generate_claude_md(input, &rewriter, Some(".claude"));
         assert!(result.contains(".claude/docs/foo.md"));
         assert!(result.contains(".claude/agents/bar.md"));
         assert!(result.starts_with("<!-- SurfContext ARDS v3.0"));
     }
 
+    #[test]
+    fn test_stamped_header_detects_edits() {
+        let generated = stamp(&format!("<!-- x -->\n{GENERATED_HEADER}\n\nBody\n"));
+        assert!(generated.lines().nth(1).is_some_and(is_generated_header));
+        assert_ne!(generated.lines().nth(1), Some(GENERATED_HEADER));
+        assert_eq!(edit_state(&generated, None), EditState::Pristine);
+        assert_eq!(
+            edit_state(&generated.replace("Body", "Edited"), None),
+            EditState::Edited
+        );
+        assert_eq!(edit_state("# no header\n", None), EditState::Unknown);
+
+        let (_, hash) = unstamp(&generated);
+        let hash = hash.unwrap();
+        for stamp in ["", &hash[..4]] {
+            let truncated = generated.replace(&hash, stamp);
+            assert_eq!(edit_state(&truncated, None), EditState::Edited);
+        }
+    }
+
+    #[test]
+    fn test_sync_keeps_hand_edits_unless_forced() {
+        let repo = TestDir::new("surfcontext-test-generate-edits");
+        fs::write(repo.join("CONTEXT.md"), "# Repo\n").unwrap();
+        let config: SurfConfig =
+            serde_json::from_str(r#"{ "generation": { "claude": {} } }"#).unwrap();
+        let mut opts = SyncOpts {
+            local_only: true,
+            quiet: true,
+            ..Default::default()
+        };
+        let sync = |opts: &SyncOpts| {
+            let mut report = SyncReport::default();
+            generate_all(&repo, &config, opts, &mut report).unwrap();
+            report
+        };
+
+        sync(&opts);
+        let claude = repo.join("CLAUDE.md");
+        let edited = fs::read_to_string(&claude).unwrap() + "\nHand-written note.\n";
+        fs::write(&claude, &edited).unwrap();
+
+        let report = sync(&opts);
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(fs::read_to_string(&claude).unwrap(), edited);
+
+        opts.force = true;
+        let report = sync(&opts);
+        assert!(report.warnings.is_empty());
+        assert!(!fs::read_to_string(&claude).unwrap().contains("Hand-written"));
+    }
+
+    #[test]
+    fn test_missing_context_keeps_declared_outputs() {
+        let repo = TestDir::new("surfcontext-test-generate-missing-context");
+        fs::write(repo.join("CONTEXT.md"), "# Repo\n").unwrap();
+        let config: SurfConfig =
+            serde_json::from_str(r#"{ "generation": { "claude": {} } }"#).unwrap();
+        let opts = SyncOpts {
+            local_only: true,
+            quiet: true,
+            ..Default::default()
+        };
+        let sync = || {
+            let mut report = SyncReport::default();
+            generate_all(&repo, &config, &opts, &mut report).unwrap();
+            super::super::lockfile::prune_and_write(&repo, &opts, &mut report).unwrap();
+            report
+        };
+
+        sync();
+        fs::remove_file(repo.join("CONTEXT.md")).unwrap();
+        let report = sync();
+        assert_eq!(report.summary().removed, 0);
+        assert!(repo.join("CLAUDE.md").exists());
+        assert!(
+            Lockfile::load(&repo)
+                .unwrap()
+                .outputs
+                .contains_key("CLAUDE.md")
+        );
+    }
+
+    /// The persona stripping that used to be hardcoded, as `surf init --type command-center`
+    /// configures it for codex.
+    fn cloudsurf_rules() -> SectionRules {
+        let json = crate::init::generate_surfcontext_json(
+            "CloudSurf Software LLC",
+            &crate::init::RepoType::CommandCenter,
+            false,
+        );
+        let mut config: crate::config::SurfConfig = serde_json::from_str(&json).unwrap();
+        config.generation.remove("codex").unwrap().sections
+    }
+
+    fn agents_md(input: &str) -> String {
+        generate_agents_md(&sections::apply(input, &cloudsurf_rules()).unwrap(), None)
+    }
+
+    #[test]
+    fn test_agents_md_keeps_content_without_rules() {
+        let input = "# Repo\n\n## Your Expertise\n\n- Item 1\n";
+        let result = generate_agents_md(input, Some(".codex"));
+        assert!(result.starts_with("<!-- SurfContext ARDS v3.0"));
+        assert!(result.contains("## Your Expertise"));
+        assert!(result.contains("Item 1"));
+        assert!(result.contains("The `.codex/` directory is **generated**"));
+    }
+
+    #[test]
+    fn test_path_rule_without_link_dir_names_no_platform_dir() {
+        let result = generate_agents_md("# Repo\n", None);
+        assert!(result.contains("## Source of Truth: `.context/`"));
+        assert!(result.contains("`.context/docs/foo.md`"));
+        assert!(!result.contains(".claude"));
+    }
+
     #[test]
     fn test_agents_md_strips_persona() {
         let input = "# CloudSurf Strategic Advisor Agent\n\n\
@@ -226,20 +576,27 @@ mod tests {
             Provide expert guidance.\n\n\
             ## Key Files\n\nSome content.\n";
 
-        let result = generate_agents_md(input);
+        let result = agents_md(input);
         assert!(result.contains("# CloudSurf Strategy Repository"));
         assert!(!result.contains("You are the strategic advisor"));
+        assert!(!result.contains("Provide expert guidance"));
         assert!(result.contains("This is the strategic command center"));
         assert!(result.contains("## Key Files"));
     }
 
+    #[test]
+    fn test_agents_md_keeps_unrelated_titles() {
+        let result = agents_md("# Strategic Planning Toolkit\n\nBody.\n");
+        assert!(result.contains("# Strategic Planning Toolkit\n"));
+    }
+
     #[test]
     fn test_agents_md_strips_expertise() {
         let input = "## Some Section\n\nKeep this.\n\n\
             ## Your Expertise\n\n- Item 1\n- Item 2\n\n\
             ## Core Principles\n\nKeep this too.\n";
 
-        let result = generate_agents_md(input);
+        let result = agents_md(input);
         assert!(result.contains("## Some Section"));
         assert!(!result.contains("## Your Expertise"));
         assert!(!result.contains("Item 1"));
@@ -253,7 +610,7 @@ mod tests {
             ## Commands\n\n- `/strategy` — foo\n- `/review` — bar\n\n\
             ## Key Repositories\n\nKeep repos.\n";
 
-        let result = generate_agents_md(input);
+        let result = agents_md(input);
         assert!(result.contains("## Working Style"));
         assert!(!result.contains("## Commands"));
         assert!(!result.contains("/strategy"));
@@ -1,10 +1,20 @@
 use anyhow::{Context, Result};
 use colored::Colorize;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
 
-use super::{SyncOpts, SyncReport, canonical_dirs, is_symlink_to, link_mappings};
-use crate::config::SurfConfig;
+use super::cross_repo::{content_hash, file_hash};
+use super::{atomic, diff};
+use super::lockfile::{self, LockEntry, OutputKind};
+use super::materialize::{self, LinkMethod};
+use super::migrate::{self, Outcome};
+use super::report::{Action, ActionKind, Code, Diagnostic, Retarget, Stage};
+use super::trash::Trash;
+use super::{
+    LinkFarm, SyncOpts, SyncReport, canonical_dirs, is_symlink_to, link_farms, relative_link_target,
+};
+use crate::config::{SurfConfig, SweepMode};
 
 /// Ensure all canonical .context/ directories exist.
 pub fn ensure_structure(
@@ -19,10 +29,13 @@ pub fn ensure_structure(
             fs::create_dir_all(&context_dir)
                 .with_context(|| format!("Failed to create {}", context_dir.display()))?;
         }
-        if !opts.quiet {
-            println!("  {} .context/", "Created".green());
-        }
-        report.created += 1;
+        report.progress(format!("  {} .context/", "Created".green()));
+        report.record(Action::new(
+            Stage::Setup,
+            ActionKind::Create,
+            ".context/",
+            "create directory",
+        ));
     }
 
     for dir in canonical_dirs(config) {
@@ -32,233 +45,479 @@ pub fn ensure_structure(
                 fs::create_dir_all(&full)
                     .with_context(|| format!("Failed to create {}", full.display()))?;
             }
-            if !opts.quiet {
-                println!("  {} {}/", "Created".green(), dir);
-            }
-            report.created += 1;
-        } else if opts.verbose && !opts.quiet {
-            println!("  {} {}/", "Exists".dimmed(), dir);
+            report.progress(format!("  {} {}/", "Created".green(), dir));
+            report.record(Action::new(
+                Stage::Setup,
+                ActionKind::Create,
+                format!("{dir}/"),
+                "create directory",
+            ));
+        } else if opts.verbose {
+            report.progress(format!("  {} {}/", "Exists".dimmed(), dir));
         }
     }
 
     Ok(())
 }
 
-/// Setup symlinks (unix) or copies (windows) from .claude/ -> .context/.
+/// Setup symlinks (unix) or copies (windows) from every platform dir -> .context/.
 pub fn setup_links(
     repo_root: &Path,
     config: &SurfConfig,
     opts: &SyncOpts,
     report: &mut SyncReport,
 ) -> Result<()> {
-    let claude_dir = repo_root.join(".claude");
-    if !claude_dir.exists() && !opts.dry_run {
-        fs::create_dir_all(&claude_dir)?;
-    }
+    let lock = lockfile::Lockfile::load(repo_root)?;
+    for farm in link_farms(config) {
+        if opts.verbose {
+            report.progress(format!(
+                "  {} {} -> {}/",
+                "[platform]".dimmed(),
+                farm.platform,
+                farm.root
+            ));
+        }
+        let farm_dir = repo_root.join(&farm.root);
+        if !farm_dir.exists() && !opts.dry_run {
+            report.trash.before_write(&farm_dir)?;
+            fs::create_dir_all(&farm_dir)?;
+        }
 
-    for (context_dir, name) in link_mappings(config) {
-        let source_full = repo_root.join(context_dir);
-        if !source_full.exists() {
-            if opts.verbose && !opts.quiet {
-                println!("  {} {context_dir} does not exist", "[skip]".dimmed());
+        for (context_dir, link) in &farm.links {
+            let source_full = repo_root.join(context_dir);
+            if !source_full.exists() {
+                if opts.verbose {
+                    report.progress(format!(
+                        "  {} {context_dir} does not exist",
+                        "[skip]".dimmed()
+                    ));
+                }
+                continue;
             }
-            continue;
-        }
 
-        let claude_path = claude_dir.join(name);
-        // Relative symlink target: ../.context/<name>
-        let symlink_target = std::path::Path::new("..").join(context_dir);
-
-        setup_single_link(
-            repo_root,
-            &claude_path,
-            &symlink_target,
-            &format!(".claude/{name}"),
-            context_dir,
-            opts,
-            report,
-        )?;
+            let link_path = repo_root.join(link);
+            if let Some(parent) = link_path.parent()
+                && !parent.exists()
+                && !opts.dry_run
+            {
+                fs::create_dir_all(parent)?;
+            }
+            if farm.method != LinkMethod::Symlink && config.sweep.mode == SweepMode::Migrate {
+                let recorded = lock
+                    .outputs
+                    .get(link)
+                    .filter(|entry| entry.kind == OutputKind::Mirror)
+                    .map(|entry| entry.files.clone())
+                    .unwrap_or_default();
+                rescue_mirror_edits(
+                    repo_root,
+                    context_dir,
+                    link,
+                    farm.method,
+                    &recorded,
+                    opts,
+                    report,
+                )?;
+            }
+
+            // Relative symlink target, e.g. ../.context/docs
+            let symlink_target = relative_link_target(link, context_dir);
+
+            setup_single_link(
+                farm.method,
+                &link_path,
+                &symlink_target,
+                link,
+                context_dir,
+                opts,
+                report,
+            )?;
+
+            let entry = if farm.method == LinkMethod::Symlink {
+                LockEntry::new(OutputKind::Symlink, context_dir)
+            } else {
+                let hash = lockfile::tree_hash(&source_full)?;
+                LockEntry::new(OutputKind::Mirror, context_dir)
+                    .hashes(hash.clone(), hash)
+                    .files(lockfile::file_hashes(&source_full)?)
+            };
+            report.outputs.insert(link.clone(), entry);
+        }
     }
 
     Ok(())
 }
 
-/// Setup a single symlink or copy, handling existing states.
+/// Setup a single symlink or mirror, handling existing states.
 fn setup_single_link(
-    _repo_root: &Path,
-    claude_path: &Path,
+    method: LinkMethod,
+    link_path: &Path,
     symlink_target: &Path,
-    display_claude: &str,
+    display_link: &str,
     display_context: &str,
     opts: &SyncOpts,
     report: &mut SyncReport,
 ) -> Result<()> {
-    if claude_path.is_symlink() {
+    if method != LinkMethod::Symlink {
+        let source = link_path.parent().unwrap_or(Path::new(".")).join(symlink_target);
+        return setup_mirror(&source, link_path, method, display_link, display_context, opts, report);
+    }
+
+    if link_path.is_symlink() {
         // Already a symlink — check if it points to the right place
-        if is_symlink_to(claude_path, symlink_target) {
-            if !opts.quiet {
-                println!(
-                    "  {} -> {} {}",
-                    display_claude,
-                    display_context,
-                    "(already linked)".dimmed()
-                );
-            }
+        if is_symlink_to(link_path, symlink_target) {
+            report.progress(format!(
+                "  {} -> {} {}",
+                display_link,
+                display_context,
+                "(already linked)".dimmed()
+            ));
             report.unchanged += 1;
         } else {
             // Wrong target — fix it
+            report.retargets.push(Retarget {
+                link: display_link.to_string(),
+                from: fs::read_link(link_path)
+                    .ok()
+                    .map(|t| t.display().to_string()),
+                to: symlink_target.display().to_string(),
+            });
             if !opts.dry_run {
-                fs::remove_file(claude_path)?;
-                create_symlink(symlink_target, claude_path)?;
+                report.trash.preserve(link_path)?;
+                create_symlink(symlink_target, link_path)?;
             }
-            if !opts.quiet {
-                println!(
-                    "  {} -> {} {}",
-                    display_claude,
-                    display_context,
-                    "(fixed link)".yellow()
-                );
-            }
-            report.updated += 1;
-        }
-    } else if claude_path.is_dir() {
-        // Real directory — convert to symlink
-        if !opts.dry_run {
-            fs::remove_dir_all(claude_path)?;
-            create_symlink(symlink_target, claude_path)?;
-        }
-        if !opts.quiet {
-            println!(
+            report.progress(format!(
                 "  {} -> {} {}",
-                display_claude,
+                display_link,
                 display_context,
-                "(converted dir to link)".yellow()
-            );
+                "(fixed link)".yellow()
+            ));
+            report.record(Action::new(
+                Stage::Links,
+                ActionKind::Update,
+                display_link,
+                format!("repoint symlink at {display_context}"),
+            ));
         }
-        report.updated += 1;
-    } else if claude_path.is_file() {
-        let msg = format!("{display_claude} is a file, expected directory or symlink");
-        report.warnings.push(msg.clone());
-        if !opts.quiet {
-            println!("  {} {}", "ERROR:".red(), msg);
+    } else if link_path.is_dir() {
+        // Real directory — convert to symlink
+        report.retargets.push(Retarget {
+            link: display_link.to_string(),
+            from: Some("directory".to_string()),
+            to: symlink_target.display().to_string(),
+        });
+        if !opts.dry_run {
+            report.trash.preserve(link_path)?;
+            fs::remove_dir_all(link_path)?;
+            create_symlink(symlink_target, link_path)?;
         }
+        report.progress(format!(
+            "  {} -> {} {}",
+            display_link,
+            display_context,
+            "(converted dir to link)".yellow()
+        ));
+        report.record(Action::new(
+            Stage::Links,
+            ActionKind::Update,
+            display_link,
+            format!("replace directory with symlink to {display_context}"),
+        ));
+    } else if link_path.is_file() {
+        let msg = format!("{display_link} is a file, expected directory or symlink");
+        report.error(
+            Diagnostic::new(Code::LinkBlocked, Stage::Links, msg.clone()).path(display_link),
+        );
     } else {
         // Doesn't exist — create
+        report.retargets.push(Retarget {
+            link: display_link.to_string(),
+            from: None,
+            to: symlink_target.display().to_string(),
+        });
         if !opts.dry_run {
-            create_symlink(symlink_target, claude_path)?;
+            report.trash.before_write(link_path)?;
+            create_symlink(symlink_target, link_path)?;
         }
-        if !opts.quiet {
-            println!(
-                "  {} -> {} {}",
-                display_claude,
-                display_context,
-                "(created)".green()
+        report.progress(format!(
+            "  {} -> {} {}",
+            display_link,
+            display_context,
+            "(created)".green()
+        ));
+        report.record(Action::new(
+            Stage::Links,
+            ActionKind::Create,
+            display_link,
+            format!("create symlink to {display_context}"),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Setup a copy or hardlink mirror of `source`, refreshing it if it already exists.
+fn setup_mirror(
+    source: &Path,
+    link_path: &Path,
+    method: LinkMethod,
+    display_link: &str,
+    display_context: &str,
+    opts: &SyncOpts,
+    report: &mut SyncReport,
+) -> Result<()> {
+    let was_symlink = link_path.is_symlink();
+    if !was_symlink && link_path.is_file() {
+        let msg = format!("{display_link} is a file, expected directory or symlink");
+        report.error(
+            Diagnostic::new(Code::LinkBlocked, Stage::Links, msg.clone()).path(display_link),
+        );
+        return Ok(());
+    }
+    let existed = link_path.is_dir();
+    if was_symlink {
+        report.retargets.push(Retarget {
+            link: display_link.to_string(),
+            from: fs::read_link(link_path)
+                .ok()
+                .map(|t| t.display().to_string()),
+            to: format!("{} mirror of {display_context}", method.as_str()),
+        });
+    }
+
+    let stats = materialize::mirror_dir(source, link_path, method, opts.dry_run, &mut report.trash)?;
+    let status = if was_symlink {
+        report.record(Action::new(
+            Stage::Links,
+            ActionKind::Update,
+            display_link,
+            format!("convert symlink to {} mirror", method.as_str()),
+        ));
+        format!("(converted link to {})", method.as_str()).yellow()
+    } else if !existed {
+        report.record(Action::new(
+            Stage::Links,
+            ActionKind::Create,
+            display_link,
+            format!("create {} mirror of {display_context}", method.as_str()),
+        ));
+        format!("({} {} files)", method.as_str(), stats.added).green()
+    } else if stats.changed() {
+        report.record(Action::new(
+            Stage::Links,
+            ActionKind::Update,
+            display_link,
+            format!("refresh mirror ({stats})"),
+        ));
+        format!("(refreshed {stats})").yellow()
+    } else {
+        report.unchanged += 1;
+        "(up to date)".dimmed()
+    };
+    report.progress(format!("  {display_link} -> {display_context} {status}"));
+
+    Ok(())
+}
+
+/// Before a copy mirror is refreshed, move anything written into it back into `.context/`.
+///
+/// `recorded` holds the hash of every file the last sync left in the mirror. A file that no
+/// longer matches it was edited and is rescued; one that still does is left for the refresh,
+/// which also prunes it when its canonical file was deleted. Files without a record (new
+/// ones, or a lockfile from before the hashes were kept) are rescued when there is no
+/// canonical file, or when they are newer than it and differ. A hardlink mirror file that is
+/// still the canonical file was not edited on its own, so only broken links are considered.
+fn rescue_mirror_edits(
+    repo_root: &Path,
+    context_dir: &str,
+    link: &str,
+    method: LinkMethod,
+    recorded: &BTreeMap<String, String>,
+    opts: &SyncOpts,
+    report: &mut SyncReport,
+) -> Result<()> {
+    let mirror = repo_root.join(link);
+    if mirror.is_symlink() || !mirror.is_dir() {
+        return Ok(());
+    }
+
+    for entry in walkdir::WalkDir::new(&mirror).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let rel = entry.path().strip_prefix(&mirror)?;
+        let canonical = repo_root.join(context_dir).join(rel);
+        if method == LinkMethod::Hardlink
+            && canonical.is_file()
+            && materialize::same_file(entry.path(), &canonical)?
+        {
+            continue;
+        }
+        let edited = match (recorded.get(&rel.display().to_string()), fs::metadata(&canonical)) {
+            (Some(hash), _) => file_hash(entry.path())? != *hash,
+            (None, Err(_)) => true,
+            (None, Ok(meta)) => {
+                meta.modified()? < entry.metadata()?.modified()?
+                    && fs::read(&canonical)? != fs::read(entry.path())?
+            }
+        };
+        if edited {
+            let rel = rel.to_string_lossy();
+            let outcome =
+                migrate::migrate_file(entry.path(), &canonical, opts.dry_run, &mut report.trash)?;
+            record_migration(
+                repo_root,
+                Stage::Links,
+                &format!("{link}/{rel}"),
+                &format!("{context_dir}/{rel}"),
+                &outcome,
+                report,
             );
         }
-        report.created += 1;
     }
 
     Ok(())
 }
 
-/// Create a symlink (unix) or copy directory (windows).
+/// Create or atomically repoint a symlink (unix), or copy directory (windows).
 #[cfg(unix)]
 fn create_symlink(target: &Path, link: &Path) -> Result<()> {
-    std::os::unix::fs::symlink(target, link)
-        .with_context(|| format!("Failed to create symlink {} -> {}", link.display(), target.display()))
+    atomic::symlink(target, link)
 }
 
 #[cfg(windows)]
 fn create_symlink(target: &Path, link: &Path) -> Result<()> {
-    // Windows: symlinks require admin/dev mode, so copy instead
+    // Windows: symlinks require admin/dev mode, so mirror instead
     let resolved = link.parent().unwrap_or(Path::new(".")).join(target);
-    copy_dir_recursive(&resolved, link)
-}
-
-#[cfg(windows)]
-fn copy_dir_recursive(src: &Path, dst: &Path) -> Result<()> {
-    fs::create_dir_all(dst)?;
-    for entry in walkdir::WalkDir::new(src).min_depth(1) {
-        let entry = entry?;
-        let rel = entry.path().strip_prefix(src)?;
-        let dest_path = dst.join(rel);
-        if entry.file_type().is_dir() {
-            fs::create_dir_all(&dest_path)?;
-        } else {
-            if let Some(parent) = dest_path.parent() {
-                fs::create_dir_all(parent)?;
-            }
-            fs::copy(entry.path(), &dest_path)?;
-        }
-    }
-    Ok(())
+    materialize::mirror_dir(&resolved, link, LinkMethod::Copy, false, &mut Trash::default())
+        .map(|_| ())
 }
 
-/// Sync .context/queue.md to .claude/queue.md with a redirect header.
+/// Sync .context/queue.md into every platform dir with a redirect header.
 pub fn sync_queue(
     repo_root: &Path,
+    config: &SurfConfig,
     opts: &SyncOpts,
     report: &mut SyncReport,
 ) -> Result<()> {
     let source = repo_root.join(".context/queue.md");
-    let target = repo_root.join(".claude/queue.md");
 
     if !source.exists() {
         return Ok(());
     }
 
-    if !opts.quiet {
-        println!();
-        println!("{}", "[Queue] Syncing .context/queue.md -> .claude/queue.md...".bold());
-    }
-
     let source_content = fs::read_to_string(&source)?;
     let output = format!(
-        "<!-- DO NOT EDIT — generated from .context/queue.md by surf sync -->\n\
+        "{QUEUE_HEADER}\n\
          <!-- Source of truth: .context/queue.md -->\n\
          \n\
          {source_content}"
     );
 
-    // Check if target already matches
-    if target.exists() {
-        let existing = fs::read_to_string(&target)?;
-        if existing == output {
-            if !opts.quiet {
-                println!("  {}", "unchanged".dimmed());
-            }
+    for farm in link_farms(config) {
+        let target = repo_root.join(&farm.root).join("queue.md");
+        report.outputs.insert(
+            format!("{}/queue.md", farm.root),
+            LockEntry::new(OutputKind::Queue, ".context/queue.md").hashes(
+                content_hash(source_content.as_bytes()),
+                content_hash(output.as_bytes()),
+            ),
+        );
+
+        report.stage(
+            Stage::Queue,
+            &format!(
+                "[Queue] Syncing .context/queue.md -> {}/queue.md...",
+                farm.root
+            ),
+        );
+
+        // Check if target already matches
+        let existing = if target.exists() {
+            Some(fs::read_to_string(&target)?)
+        } else {
+            None
+        };
+        if existing.as_deref() == Some(output.as_str()) {
+            report.progress(format!("  {}", "unchanged".dimmed()));
             report.unchanged += 1;
-            return Ok(());
+            continue;
         }
-    }
+        let before = existing.as_deref().map(|e| content_hash(e.as_bytes()));
 
-    if !opts.dry_run {
-        fs::write(&target, output)?;
-    }
-    if !opts.quiet {
-        println!("  {}", "Done".green());
+        if !opts.dry_run {
+            fs::create_dir_all(repo_root.join(&farm.root))?;
+            report.trash.before_write(&target)?;
+            atomic::write(&target, &output)?;
+        }
+        report.progress(format!("  {}", "Done".green()));
+        if opts.diff {
+            for line in diff::change(
+                &format!("{}/queue.md", farm.root),
+                existing.as_deref().map(str::as_bytes),
+                output.as_bytes(),
+                "    ",
+            ) {
+                report.progress(line);
+            }
+        }
+        report.record(
+            Action::new(
+                Stage::Queue,
+                if before.is_some() {
+                    ActionKind::Update
+                } else {
+                    ActionKind::Create
+                },
+                format!("{}/queue.md", farm.root),
+                "copy from .context/queue.md",
+            )
+            .hashes(before, Some(content_hash(output.as_bytes()))),
+        );
     }
-    report.updated += 1;
 
     Ok(())
 }
 
-/// Scan .claude/ for orphaned files/dirs and redirect them.
+/// Scan every platform dir for orphaned files/dirs and handle them per `sweep.mode`.
 pub fn defensive_sweep(
     repo_root: &Path,
+    config: &SurfConfig,
     opts: &SyncOpts,
     report: &mut SyncReport,
 ) -> Result<()> {
-    let claude_dir = repo_root.join(".claude");
-    if !claude_dir.exists() {
-        return Ok(());
+    let mut found_orphan = false;
+    for farm in link_farms(config) {
+        found_orphan |= sweep_farm(repo_root, &farm, config.sweep.mode, opts, report)?;
+    }
+
+    if !found_orphan {
+        report.progress(format!("  {}", "Clean".green()));
     }
 
-    let entries = fs::read_dir(&claude_dir)?;
+    Ok(())
+}
+
+/// Sweep one platform dir. Returns whether any orphan was found.
+fn sweep_farm(
+    repo_root: &Path,
+    farm: &LinkFarm,
+    mode: SweepMode,
+    opts: &SyncOpts,
+    report: &mut SyncReport,
+) -> Result<bool> {
+    let farm_dir = repo_root.join(&farm.root);
+    if !farm_dir.exists() {
+        return Ok(false);
+    }
+    let root = &farm.root;
+
+    let mut entries = fs::read_dir(&farm_dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.file_name());
     let mut found_orphan = false;
 
     for entry in entries {
-        let entry = entry?;
         let name = entry.file_name();
         let name_str = name.to_string_lossy();
         let path = entry.path();
@@ -268,8 +527,13 @@ pub fn defensive_sweep(
             continue;
         }
 
-        // Skip Claude Code native files (settings*.json)
-        if name_str.starts_with("settings") && name_str.ends_with(".json") {
+        // Skip the tool's own files (settings*.json and friends)
+        if farm.is_native(&name_str) {
+            continue;
+        }
+
+        // Skip the links themselves (real dirs in copy/hardlink mode)
+        if farm.is_managed(&name_str) {
             continue;
         }
 
@@ -280,181 +544,201 @@ pub fn defensive_sweep(
 
         // Handle orphan files
         if path.is_file() {
+            let is_stub = fs::read_to_string(&path).is_ok_and(|c| c == REDIRECT_FILE_CONTENT);
+            if is_stub && mode == SweepMode::Migrate {
+                // Left behind by an earlier redirect-mode sweep; nothing to migrate.
+                continue;
+            }
             found_orphan = true;
-            if name_str.ends_with(".md") {
-                if !opts.quiet {
-                    println!("  {} orphan file: .claude/{}", "Redirecting".yellow(), name_str);
-                }
-                if !opts.dry_run {
-                    let redirect = REDIRECT_FILE_CONTENT;
-                    fs::write(&path, redirect)?;
-                }
-                report.redirected += 1;
-            } else {
+
+            if !name_str.ends_with(".md") || mode == SweepMode::Report {
                 let msg = format!(
-                    "Unexpected file in .claude/: {} (not managed by SurfContext)",
+                    "Unexpected file in {root}/: {} (not managed by SurfContext)",
                     name_str
                 );
-                if !opts.quiet {
-                    println!("  {} {}", "WARNING:".yellow(), msg);
+                report.warn(
+                    Diagnostic::new(Code::OrphanFile, Stage::Sweep, msg)
+                        .path(format!("{root}/{name_str}")),
+                );
+            } else if mode == SweepMode::Migrate {
+                let dest = format!(".context/{name_str}");
+                let outcome = migrate::migrate_file(
+                    &path,
+                    &repo_root.join(&dest),
+                    opts.dry_run,
+                    &mut report.trash,
+                )?;
+                record_migration(
+                    repo_root,
+                    Stage::Sweep,
+                    &format!("{root}/{name_str}"),
+                    &dest,
+                    &outcome,
+                    report,
+                );
+            } else {
+                report.progress(format!(
+                    "  {} orphan file: {root}/{name_str}",
+                    "Redirecting".yellow()
+                ));
+                let before = content_hash(&fs::read(&path)?);
+                if !opts.dry_run {
+                    report.trash.preserve(&path)?;
+                    atomic::write(&path, REDIRECT_FILE_CONTENT)?;
                 }
-                report.warnings.push(msg);
+                report.record(
+                    Action::new(
+                        Stage::Sweep,
+                        ActionKind::Redirect,
+                        format!("{root}/{name_str}"),
+                        "replace orphan with redirect stub",
+                    )
+                    .hashes(
+                        Some(before),
+                        Some(content_hash(REDIRECT_FILE_CONTENT.as_bytes())),
+                    ),
+                );
             }
         }
 
         // Handle orphan directories
         if path.is_dir() {
-            found_orphan = true;
             let context_counterpart = repo_root.join(".context").join(&*name_str);
-            if context_counterpart.is_dir() {
-                // Has a .context/ counterpart — convert to symlink
-                let symlink_target = std::path::Path::new("..").join(".context").join(&*name_str);
-                if !opts.quiet {
-                    println!(
-                        "  {} orphan dir: .claude/{} -> .context/{}",
-                        "Converting".yellow(),
-                        name_str,
-                        name_str
-                    );
+            // A converted orphan in copy/hardlink mode stays a real dir; skip it while current
+            if farm.method != LinkMethod::Symlink
+                && context_counterpart.is_dir()
+                && !materialize::mirror_dir(
+                    &context_counterpart,
+                    &path,
+                    farm.method,
+                    true,
+                    &mut Trash::default(),
+                )?
+                .changed()
+            {
+                continue;
+            }
+            found_orphan = true;
+            if context_counterpart.is_dir() && mode != SweepMode::Report {
+                if mode == SweepMode::Migrate {
+                    // Move everything out first so replacing the dir loses nothing
+                    for (rel, outcome) in
+                        migrate::migrate_dir(&path, &context_counterpart, opts.dry_run, &mut report.trash)?
+                    {
+                        let rel = rel.to_string_lossy();
+                        record_migration(
+                            repo_root,
+                            Stage::Sweep,
+                            &format!("{root}/{name_str}/{rel}"),
+                            &format!(".context/{name_str}/{rel}"),
+                            &outcome,
+                            report,
+                        );
+                    }
                 }
+
+                // Has a .context/ counterpart — convert to a link
+                let symlink_target =
+                    relative_link_target(&format!("{root}/{name_str}"), &format!(".context/{name_str}"));
+                report.progress(format!(
+                    "  {} orphan dir: {root}/{} -> .context/{}",
+                    "Converting".yellow(),
+                    name_str,
+                    name_str
+                ));
                 if !opts.dry_run {
+                    report.trash.preserve(&path)?;
                     fs::remove_dir_all(&path)?;
-                    create_symlink(&symlink_target, &path)?;
+                    if farm.method == LinkMethod::Symlink {
+                        create_symlink(&symlink_target, &path)?;
+                    } else {
+                        materialize::mirror_dir(
+                            &context_counterpart,
+                            &path,
+                            farm.method,
+                            false,
+                            &mut report.trash,
+                        )?;
+                    }
                 }
-                report.updated += 1;
+                report.record(Action::new(
+                    Stage::Sweep,
+                    ActionKind::Update,
+                    format!("{root}/{name_str}"),
+                    format!("convert orphan dir to link to .context/{name_str}"),
+                ));
             } else {
-                // No counterpart — add redirect README
-                let msg = format!(
-                    "Unexpected directory in .claude/: {} (no .context/ counterpart)",
-                    name_str
-                );
-                if !opts.quiet {
-                    println!("  {} {}", "WARNING:".yellow(), msg);
-                }
-                if !opts.dry_run {
-                    let readme_path = path.join("README.md");
+                let msg = if context_counterpart.is_dir() {
+                    format!("Unexpected directory in {root}/: {name_str} (belongs in .context/{name_str}/)")
+                } else {
+                    format!("Unexpected directory in {root}/: {name_str} (no .context/ counterpart)")
+                };
+                // Point readers at the fix, without clobbering a README that is already there
+                let readme_path = path.join("README.md");
+                if mode != SweepMode::Report && !opts.dry_run && !readme_path.exists() {
                     let content = format!(
-                        "<!-- DEPRECATED — this directory should not exist in .claude/ -->\n\n\
+                        "<!-- DEPRECATED — this directory should not exist in {root}/ -->\n\n\
                          # Deprecated Directory\n\n\
-                         This directory does not belong in `.claude/`. In SurfContext/ARDS v3.0, all content\n\
-                         lives in `.context/` and is symlinked or synced to `.claude/` by `surf sync`.\n\n\
+                         This directory does not belong in `{root}/`. In SurfContext/ARDS v3.0, all content\n\
+                         lives in `.context/` and is symlinked or synced to `{root}/` by `surf sync`.\n\n\
                          **To fix:** Move contents to `.context/{name_str}/` and run `surf sync`.\n"
                     );
-                    fs::write(readme_path, content)?;
+                    atomic::write(&readme_path, content)?;
                 }
-                report.warnings.push(msg);
+                report.warn(
+                    Diagnostic::new(Code::OrphanDir, Stage::Sweep, msg)
+                        .path(format!("{root}/{name_str}")),
+                );
             }
         }
     }
 
-    if !found_orphan && !opts.quiet {
-        println!("  {}", "Clean".green());
-    }
-
-    Ok(())
+    Ok(found_orphan)
 }
 
-/// Scan .context/ docs for accidental .claude/ path references.
-/// Files that reference .claude/docs/, .claude/agents/, etc. are likely mistakes —
-/// the source of truth is .context/ and all references should use .context/ paths.
-pub fn audit_path_references(
+/// Print and count one migrated file; conflicts also become warnings.
+fn record_migration(
     repo_root: &Path,
-    opts: &SyncOpts,
+    stage: Stage,
+    from: &str,
+    to: &str,
+    outcome: &Outcome,
     report: &mut SyncReport,
-) -> Result<()> {
-    let context_dir = repo_root.join(".context");
-    if !context_dir.exists() {
-        return Ok(());
+) {
+    let status = match outcome {
+        Outcome::Conflict(_) => outcome.to_string().red(),
+        _ => outcome.to_string().dimmed(),
+    };
+    report.progress(format!(
+        "  {} {from} -> {to} ({status})",
+        "Migrating".yellow()
+    ));
+    if let Outcome::Conflict(path) = outcome {
+        let kept = path.strip_prefix(repo_root).unwrap_or(path);
+        report.warn(
+            Diagnostic::new(
+                Code::MigrationConflict,
+                stage,
+                format!(
+                    "{from} conflicts with {to}; kept both, resolve {} by hand",
+                    kept.display()
+                ),
+            )
+            .path(kept.display().to_string()),
+        );
     }
-
-    let claude_path_patterns = [
-        ".claude/docs/",
-        ".claude/agents/",
-        ".claude/guides/",
-        ".claude/skills/",
-        ".claude/queue.md",
-    ];
-
-    // Directories to scan: .context/docs, .context/guides, .context/agents, .context/skills
-    let scan_dirs = ["docs", "agents", "guides", "skills"];
-    let mut violations: Vec<(String, usize, String)> = Vec::new();
-
-    for subdir in &scan_dirs {
-        let dir = context_dir.join(subdir);
-        if !dir.is_dir() {
-            continue;
-        }
-
-        for entry in walkdir::WalkDir::new(&dir)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
-            .filter(|e| {
-                e.path()
-                    .extension()
-                    .map_or(false, |ext| ext == "md" || ext == "txt")
-            })
-        {
-            let content = match fs::read_to_string(entry.path()) {
-                Ok(c) => c,
-                Err(_) => continue,
-            };
-
-            for (line_num, line) in content.lines().enumerate() {
-                for pattern in &claude_path_patterns {
-                    if line.contains(pattern) {
-                        let rel_path = entry
-                            .path()
-                            .strip_prefix(repo_root)
-                            .unwrap_or(entry.path());
-                        violations.push((
-                            rel_path.display().to_string(),
-                            line_num + 1,
-                            pattern.to_string(),
-                        ));
-                    }
-                }
-            }
-        }
-    }
-
-    // Also scan CONTEXT.md itself
-    let context_md = repo_root.join("CONTEXT.md");
-    if context_md.exists() {
-        if let Ok(content) = fs::read_to_string(&context_md) {
-            for (line_num, line) in content.lines().enumerate() {
-                for pattern in &claude_path_patterns {
-                    if line.contains(pattern) {
-                        violations.push((
-                            "CONTEXT.md".to_string(),
-                            line_num + 1,
-                            pattern.to_string(),
-                        ));
-                    }
-                }
-            }
-        }
-    }
-
-    if violations.is_empty() {
-        if !opts.quiet {
-            println!("  {}", "Clean — no .claude/ path references in .context/ files".green());
-        }
-    } else {
-        for (file, line, pattern) in &violations {
-            let msg = format!(
-                "{file}:{line} references `{pattern}` — should use .context/ path instead"
-            );
-            if !opts.quiet {
-                println!("  {} {}", "WARN:".yellow(), msg);
-            }
-            report.warnings.push(msg);
-        }
-    }
-
-    Ok(())
+    report.record(Action::new(
+        stage,
+        ActionKind::Migrate,
+        from,
+        format!("migrate to {to} ({outcome})"),
+    ));
 }
 
+/// Marker line at the top of every platform copy of `.context/queue.md`.
+pub(crate) const QUEUE_HEADER: &str =
+    "<!-- DO NOT EDIT — generated from .context/queue.md by surf sync -->";
+
 const REDIRECT_FILE_CONTENT: &str = "\
 <!-- DEPRECATED — this file has moved to .context/ -->
 <!-- This file is auto-managed by surf sync -->
@@ -0,0 +1,371 @@
+//! `.context/surf-lock.json`: every file and link `surf sync` produced.
+//!
+//! Each run records its outputs on the `SyncReport`. At the end of the run the previous
+//! lockfile is compared against them. Outputs that are no longer declared (a platform
+//! dropped from `generation`, a cross-repo file deleted at the source) are removed if they
+//! are still exactly what surf wrote. If they were changed since, they are reported instead.
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use super::cross_repo::{content_hash, file_hash};
+use super::atomic;
+use super::report::{Action, ActionKind, Code, Diagnostic, Stage};
+use super::{SyncOpts, SyncReport, is_symlink_to, relative_link_target};
+
+/// Lockfile location, relative to the repo root.
+pub const LOCK_FILE: &str = ".context/surf-lock.json";
+
+const LOCK_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OutputKind {
+    /// Platform root context file (CLAUDE.md, AGENTS.md, ...).
+    Generated,
+    /// Symlink into `.context/`.
+    Symlink,
+    /// Copy or hardlink mirror of a `.context/` dir.
+    Mirror,
+    /// Platform copy of `.context/queue.md`.
+    Queue,
+    /// File written into another repo.
+    CrossRepo,
+}
+
+/// One output, keyed in the lockfile by its path relative to the repo root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockEntry {
+    pub kind: OutputKind,
+    /// What the output was produced from, relative to the repo root.
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_hash: Option<String>,
+    /// SHA-256 of the file (or of the whole tree, for mirrors) as surf left it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_hash: Option<String>,
+    /// For mirrors: each file's hash by its path inside the mirror, as surf left it.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub files: BTreeMap<String, String>,
+}
+
+impl LockEntry {
+    pub fn new(kind: OutputKind, source: &str) -> Self {
+        Self {
+            kind,
+            source: source.to_string(),
+            source_hash: None,
+            output_hash: None,
+            files: BTreeMap::new(),
+        }
+    }
+
+    pub fn hashes(mut self, source_hash: String, output_hash: String) -> Self {
+        self.source_hash = Some(source_hash);
+        self.output_hash = Some(output_hash);
+        self
+    }
+
+    pub fn files(mut self, files: BTreeMap<String, String>) -> Self {
+        self.files = files;
+        self
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Lockfile {
+    pub version: u32,
+    pub outputs: BTreeMap<String, LockEntry>,
+}
+
+impl Lockfile {
+    /// Read the lockfile, or an empty one if the repo has none yet.
+    pub fn load(repo_root: &Path) -> Result<Self> {
+        let path = repo_root.join(LOCK_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let raw = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&raw).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Write the lockfile if its content changed; an empty one is deleted instead.
+    pub fn save(&self, repo_root: &Path) -> Result<()> {
+        let path = repo_root.join(LOCK_FILE);
+        if self.outputs.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+            return Ok(());
+        }
+        let lock = Lockfile {
+            version: LOCK_VERSION,
+            outputs: self.outputs.clone(),
+        };
+        let json = serde_json::to_string_pretty(&lock)? + "\n";
+        if fs::read_to_string(&path).ok().as_deref() != Some(json.as_str()) {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            atomic::write(&path, json)?;
+        }
+        Ok(())
+    }
+}
+
+/// Hash of a directory tree: every file's relative path and content hash, in path order.
+pub fn tree_hash(dir: &Path) -> Result<String> {
+    let listing: String = walk_hashes(dir)?
+        .into_iter()
+        .map(|(rel, hash)| format!("{hash} {rel}\n"))
+        .collect();
+    Ok(content_hash(listing.as_bytes()))
+}
+
+/// Content hash of every file under `dir`, by relative path.
+pub fn file_hashes(dir: &Path) -> Result<BTreeMap<String, String>> {
+    Ok(walk_hashes(dir)?.into_iter().collect())
+}
+
+fn walk_hashes(dir: &Path) -> Result<Vec<(String, String)>> {
+    let mut hashes = Vec::new();
+    for entry in WalkDir::new(dir)
+        .min_depth(1)
+        .follow_links(true)
+        .sort_by_file_name()
+    {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            let rel = entry.path().strip_prefix(dir)?;
+            hashes.push((rel.display().to_string(), file_hash(entry.path())?));
+        }
+    }
+    Ok(hashes)
+}
+
+/// Remove (or report) outputs from the previous run that this run no longer produced,
+/// then write the new lockfile.
+pub fn prune_and_write(repo_root: &Path, opts: &SyncOpts, report: &mut SyncReport) -> Result<()> {
+    let previous = Lockfile::load(repo_root)?;
+    let mut found_stale = false;
+
+    for (path, entry) in &previous.outputs {
+        if report.outputs.contains_key(path) {
+            continue;
+        }
+        // Cross-repo sync didn't run, so its outputs are still declared.
+        if opts.local_only && entry.kind == OutputKind::CrossRepo {
+            report.outputs.insert(path.clone(), entry.clone());
+            continue;
+        }
+        let full = repo_root.join(path);
+        if fs::symlink_metadata(&full).is_err() {
+            continue;
+        }
+
+        found_stale = true;
+        if is_pristine(repo_root, path, entry)? {
+            report.progress(format!(
+                "  {} stale output: {path} (from {})",
+                "Removing".yellow(),
+                entry.source
+            ));
+            if !opts.dry_run {
+                report.trash.preserve(&full)?;
+                if full.is_dir() && !full.is_symlink() {
+                    fs::remove_dir_all(&full)?;
+                } else {
+                    fs::remove_file(&full)?;
+                }
+            }
+            report.record(
+                Action::new(
+                    Stage::Lockfile,
+                    ActionKind::Remove,
+                    path,
+                    format!("stale output from {}", entry.source),
+                )
+                .hashes(entry.output_hash.clone(), None),
+            );
+        } else {
+            let msg = format!(
+                "Stale output {path} (from {}) was modified since surf wrote it; left in place",
+                entry.source
+            );
+            report
+                .warn(Diagnostic::new(Code::StaleOutputModified, Stage::Lockfile, msg).path(path));
+            // Keep tracking it so it is reported until someone deals with it.
+            report.outputs.insert(path.clone(), entry.clone());
+        }
+    }
+
+    if !found_stale {
+        report.progress(format!("  {}", "Clean".green()));
+    }
+
+    if previous.outputs != report.outputs {
+        let kind = if repo_root.join(LOCK_FILE).exists() {
+            ActionKind::Update
+        } else {
+            ActionKind::Create
+        };
+        report.record(Action::new(
+            Stage::Lockfile,
+            kind,
+            LOCK_FILE,
+            format!("record {} output(s)", report.outputs.len()),
+        ));
+    }
+    if opts.dry_run {
+        return Ok(());
+    }
+    Lockfile {
+        version: LOCK_VERSION,
+        outputs: report.outputs.clone(),
+    }
+    .save(repo_root)
+}
+
+/// Update the entries of the outputs this run produced and leave every other entry alone.
+///
+/// For incremental runs (`surf sync --watch`), which only see the outputs of the stages they
+/// ran; stale outputs are pruned by the next full run.
+pub fn merge_and_write(repo_root: &Path, opts: &SyncOpts, report: &SyncReport) -> Result<()> {
+    if opts.dry_run || report.outputs.is_empty() {
+        return Ok(());
+    }
+    let mut lock = Lockfile::load(repo_root)?;
+    lock.outputs
+        .extend(report.outputs.iter().map(|(path, entry)| (path.clone(), entry.clone())));
+    lock.save(repo_root)
+}
+
+/// Whether the output at `path` (relative to `repo_root`) is still exactly what surf left
+/// there. A symlink has to point where surf pointed it.
+pub fn is_pristine(repo_root: &Path, path: &str, entry: &LockEntry) -> Result<bool> {
+    let full = repo_root.join(path);
+    let meta = fs::symlink_metadata(&full)?;
+    Ok(match entry.kind {
+        OutputKind::Symlink => is_symlink_to(&full, &relative_link_target(path, &entry.source)),
+        OutputKind::Mirror => {
+            meta.is_dir() && entry.output_hash.as_deref() == Some(tree_hash(&full)?.as_str())
+        }
+        _ => meta.is_file() && entry.output_hash.as_deref() == Some(file_hash(&full)?.as_str()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestDir;
+
+    fn fixture(name: &str, lock: &Lockfile, files: &[(&str, &str)]) -> TestDir {
+        let dir = TestDir::new(name);
+        fs::create_dir_all(dir.join(".context")).unwrap();
+        for (path, content) in files {
+            fs::write(dir.join(path), content).unwrap();
+        }
+        fs::write(
+            dir.join(LOCK_FILE),
+            serde_json::to_string_pretty(lock).unwrap(),
+        )
+        .unwrap();
+        dir
+    }
+
+    fn opts() -> SyncOpts {
+        SyncOpts {
+            quiet: true,
+            ..Default::default()
+        }
+    }
+
+    fn generated(output: &str) -> LockEntry {
+        LockEntry::new(OutputKind::Generated, "CONTEXT.md")
+            .hashes(content_hash(b"src"), content_hash(output.as_bytes()))
+    }
+
+    #[test]
+    fn test_prunes_undeclared_pristine_outputs() {
+        let mut lock = Lockfile::default();
+        lock.outputs.insert("CLAUDE.md".into(), generated("claude"));
+        lock.outputs.insert("AGENTS.md".into(), generated("agents"));
+        lock.outputs.insert("GEMINI.md".into(), generated("gemini"));
+        let repo = fixture(
+            "surfcontext-test-lock-prune",
+            &lock,
+            &[
+                ("CLAUDE.md", "claude"),
+                ("AGENTS.md", "agents"),
+                ("GEMINI.md", "hand edited"),
+            ],
+        );
+
+        let mut report = SyncReport::default();
+        report
+            .outputs
+            .insert("CLAUDE.md".into(), generated("claude"));
+        prune_and_write(&repo, &opts(), &mut report).unwrap();
+
+        assert_eq!(report.summary().removed, 1);
+        assert!(!repo.join("AGENTS.md").exists());
+        assert!(repo.join("GEMINI.md").exists());
+        assert_eq!(report.warnings.len(), 1);
+
+        let written = Lockfile::load(&repo).unwrap();
+        let paths: Vec<&str> = written.outputs.keys().map(String::as_str).collect();
+        assert_eq!(paths, vec!["CLAUDE.md", "GEMINI.md"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_repointed_symlink_is_not_pristine() {
+        let mut lock = Lockfile::default();
+        let entry = LockEntry::new(OutputKind::Symlink, ".context/docs");
+        lock.outputs.insert(".codex/docs".into(), entry.clone());
+        lock.outputs.insert(".codex/guides".into(), entry);
+        let repo = fixture("surfcontext-test-lock-symlink", &lock, &[]);
+        fs::create_dir_all(repo.join(".context/docs")).unwrap();
+        fs::create_dir_all(repo.join(".codex")).unwrap();
+        std::os::unix::fs::symlink("../.context/docs", repo.join(".codex/docs")).unwrap();
+        std::os::unix::fs::symlink("/tmp", repo.join(".codex/guides")).unwrap();
+
+        let mut report = SyncReport::default();
+        prune_and_write(&repo, &opts(), &mut report).unwrap();
+
+        assert!(fs::symlink_metadata(repo.join(".codex/docs")).is_err());
+        assert!(repo.join(".codex/guides").is_symlink());
+        assert_eq!(report.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_local_only_keeps_cross_repo_outputs() {
+        let mut lock = Lockfile::default();
+        lock.outputs.insert(
+            "../other/skills/a.md".into(),
+            LockEntry::new(OutputKind::CrossRepo, ".context/skills/a.md"),
+        );
+        let repo = fixture("surfcontext-test-lock-local-only", &lock, &[]);
+
+        let mut report = SyncReport::default();
+        let opts = SyncOpts {
+            local_only: true,
+            ..opts()
+        };
+        prune_and_write(&repo, &opts, &mut report).unwrap();
+        assert!(
+            Lockfile::load(&repo)
+                .unwrap()
+                .outputs
+                .contains_key("../other/skills/a.md")
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! How a platform link is materialized: `symlink`, `copy` or `hardlink`.
+//!
+//! Copies and hardlinks are kept as incremental mirrors of the canonical directory. Each
+//! sync adds new files, refreshes changed ones (SHA-256 for `copy`, inode identity for
+//! `hardlink`) and prunes anything the source no longer has, so a mirror never goes stale.
+
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use super::atomic;
+use super::cross_repo::file_hash;
+use super::trash::Trash;
+
+/// How a link-farm entry points at its canonical directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkMethod {
+    Symlink,
+    Copy,
+    Hardlink,
+}
+
+impl LinkMethod {
+    /// Link method for a `generation.<platform>.method` value.
+    ///
+    /// Transform methods (`sed-copy`, `template-copy`) only describe the root context file,
+    /// so the links themselves stay symlinks. Windows has no unprivileged symlinks, so
+    /// there `symlink` falls back to `copy`.
+    pub fn from_config(method: &str) -> Self {
+        match method {
+            "copy" => LinkMethod::Copy,
+            "hardlink" => LinkMethod::Hardlink,
+            _ if cfg!(windows) => LinkMethod::Copy,
+            _ => LinkMethod::Symlink,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LinkMethod::Symlink => "symlink",
+            LinkMethod::Copy => "copy",
+            LinkMethod::Hardlink => "hardlink",
+        }
+    }
+}
+
+/// What a mirror pass changed, counted in files.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MirrorStats {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+impl MirrorStats {
+    pub fn changed(&self) -> bool {
+        self.added + self.updated + self.removed > 0
+    }
+}
+
+impl fmt::Display for MirrorStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "+{} ~{} -{}", self.added, self.updated, self.removed)
+    }
+}
+
+/// Make `dst` an up-to-date copy or hardlink mirror of `src`.
+///
+/// A symlink at `dst` (e.g. after switching from `symlink` to `copy`) is replaced. Anything
+/// replaced or pruned goes to `trash` first. In dry-run mode nothing is written and the
+/// stats describe what would change.
+pub fn mirror_dir(
+    src: &Path,
+    dst: &Path,
+    method: LinkMethod,
+    dry_run: bool,
+    trash: &mut Trash,
+) -> Result<MirrorStats> {
+    let mut stats = MirrorStats::default();
+    let dst_is_dir = dst.is_dir() && !dst.is_symlink();
+
+    if !dry_run {
+        if dst.is_symlink() {
+            trash.preserve(dst)?;
+            fs::remove_file(dst)?;
+        }
+        trash.before_write(dst)?;
+        fs::create_dir_all(dst)?;
+    }
+
+    let mut wanted: BTreeSet<PathBuf> = BTreeSet::new();
+    for entry in WalkDir::new(src)
+        .min_depth(1)
+        .follow_links(true)
+        .sort_by_file_name()
+    {
+        let entry = entry?;
+        let rel = entry.path().strip_prefix(src)?.to_path_buf();
+        let target = dst.join(&rel);
+        wanted.insert(rel);
+
+        if entry.file_type().is_dir() {
+            if !dry_run {
+                if target.symlink_metadata().is_ok_and(|m| !m.is_dir()) {
+                    trash.preserve(&target)?;
+                    remove_path(&target)?;
+                }
+                trash.before_write(&target)?;
+                fs::create_dir_all(&target)?;
+            }
+            continue;
+        }
+
+        if !dst_is_dir || target.symlink_metadata().is_err() {
+            stats.added += 1;
+            if !dry_run {
+                trash.before_write(&target)?;
+                place(entry.path(), &target, method)?;
+            }
+        } else if is_current(entry.path(), &target, method)? {
+            stats.unchanged += 1;
+        } else {
+            stats.updated += 1;
+            if !dry_run {
+                trash.preserve(&target)?;
+                if fs::symlink_metadata(&target)?.is_dir() {
+                    remove_path(&target)?;
+                }
+                place(entry.path(), &target, method)?;
+            }
+        }
+    }
+
+    if dst_is_dir {
+        // Children come before their parent, so stale directories are empty by the time
+        // they are removed.
+        for entry in WalkDir::new(dst).min_depth(1).contents_first(true) {
+            let entry = entry?;
+            let rel = entry.path().strip_prefix(dst)?;
+            if wanted.contains(rel) {
+                continue;
+            }
+            if !entry.file_type().is_dir() {
+                stats.removed += 1;
+            }
+            if !dry_run {
+                trash.preserve(entry.path())?;
+                remove_path(entry.path())?;
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Whether `dst` already holds `src`'s current content.
+fn is_current(src: &Path, dst: &Path, method: LinkMethod) -> Result<bool> {
+    if !fs::symlink_metadata(dst)?.is_file() {
+        return Ok(false);
+    }
+    match method {
+        LinkMethod::Hardlink => same_file(src, dst),
+        _ => Ok(file_hash(src)? == file_hash(dst)?),
+    }
+}
+
+/// Whether `a` and `b` are the same file, i.e. hardlinks to one inode.
+#[cfg(unix)]
+pub(super) fn same_file(a: &Path, b: &Path) -> Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    let (a, b) = (fs::metadata(a)?, fs::metadata(b)?);
+    Ok(a.dev() == b.dev() && a.ino() == b.ino())
+}
+
+#[cfg(not(unix))]
+pub(super) fn same_file(a: &Path, b: &Path) -> Result<bool> {
+    Ok(file_hash(a)? == file_hash(b)?)
+}
+
+/// Write `dst` in one step, replacing any file already there.
+fn place(src: &Path, dst: &Path, method: LinkMethod) -> Result<()> {
+    match method {
+        LinkMethod::Hardlink => atomic::hard_link(src, dst),
+        _ => atomic::copy(src, dst),
+    }
+}
+
+fn remove_path(path: &Path) -> Result<()> {
+    let meta = fs::symlink_metadata(path)?;
+    if meta.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestDir;
+
+    #[test]
+    fn test_method_from_config() {
+        assert_eq!(LinkMethod::from_config("copy"), LinkMethod::Copy);
+        assert_eq!(LinkMethod::from_config("hardlink"), LinkMethod::Hardlink);
+        let fallback = if cfg!(windows) {
+            LinkMethod::Copy
+        } else {
+            LinkMethod::Symlink
+        };
+        assert_eq!(LinkMethod::from_config("symlink"), fallback);
+        assert_eq!(LinkMethod::from_config("sed-copy"), fallback);
+    }
+
+    #[test]
+    fn test_copy_mirror_adds_updates_and_prunes() {
+        let dir = TestDir::new("surfcontext-test-mirror-copy").with_files(&[
+            ("src/a.md", "a"),
+            ("src/nested/b.md", "b"),
+            ("src/gone/c.md", "c"),
+        ]);
+        let (src, dst) = (dir.join("src"), dir.join("dst"));
+
+        let first = mirror_dir(&src, &dst, LinkMethod::Copy, false, &mut Trash::default()).unwrap();
+        assert_eq!(first.added, 3);
+        assert_eq!(fs::read_to_string(dst.join("nested/b.md")).unwrap(), "b");
+
+        fs::write(src.join("a.md"), "a2").unwrap();
+        fs::remove_dir_all(src.join("gone")).unwrap();
+        fs::write(dst.join("stray.md"), "x").unwrap();
+
+        let preview =
+            mirror_dir(&src, &dst, LinkMethod::Copy, true, &mut Trash::default()).unwrap();
+        assert_eq!((preview.updated, preview.removed), (1, 2));
+        assert_eq!(fs::read_to_string(dst.join("a.md")).unwrap(), "a");
+
+        let second =
+            mirror_dir(&src, &dst, LinkMethod::Copy, false, &mut Trash::default()).unwrap();
+        assert_eq!(second, preview);
+        assert_eq!(fs::read_to_string(dst.join("a.md")).unwrap(), "a2");
+        assert!(!dst.join("gone").exists());
+        assert!(!dst.join("stray.md").exists());
+
+        let third = mirror_dir(&src, &dst, LinkMethod::Copy, false, &mut Trash::default()).unwrap();
+        assert!(!third.changed());
+        assert_eq!(third.unchanged, 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlink_mirror_relinks_replaced_files() {
+        let dir = TestDir::new("surfcontext-test-mirror-hardlink").with_files(&[("src/a.md", "a")]);
+        let (src, dst) = (dir.join("src"), dir.join("dst"));
+
+        mirror_dir(
+            &src,
+            &dst,
+            LinkMethod::Hardlink,
+            false,
+            &mut Trash::default(),
+        )
+        .unwrap();
+        assert!(same_file(&src.join("a.md"), &dst.join("a.md")).unwrap());
+        assert!(
+            !mirror_dir(
+                &src,
+                &dst,
+                LinkMethod::Hardlink,
+                false,
+                &mut Trash::default()
+            )
+            .unwrap()
+            .changed()
+        );
+
+        // Editors often save by replacing the file, which breaks the hardlink.
+        fs::remove_file(src.join("a.md")).unwrap();
+        fs::write(src.join("a.md"), "a2").unwrap();
+        let stats = mirror_dir(
+            &src,
+            &dst,
+            LinkMethod::Hardlink,
+            false,
+            &mut Trash::default(),
+        )
+        .unwrap();
+        assert_eq!(stats.updated, 1);
+        assert_eq!(fs::read_to_string(dst.join("a.md")).unwrap(), "a2");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_mirror_replaces_symlink() {
+        let dir = TestDir::new("surfcontext-test-mirror-symlink").with_files(&[("src/a.md", "a")]);
+        let (src, dst) = (dir.join("src"), dir.join("dst"));
+        std::os::unix::fs::symlink(&src, &dst).unwrap();
+
+        let stats = mirror_dir(&src, &dst, LinkMethod::Copy, false, &mut Trash::default()).unwrap();
+        assert_eq!(stats.added, 1);
+        assert!(!dst.is_symlink());
+        assert_eq!(fs::read_to_string(dst.join("a.md")).unwrap(), "a");
+    }
+}
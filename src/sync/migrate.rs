@@ -0,0 +1,216 @@
+//! Moving orphaned platform-dir content back into `.context/` without losing anything.
+//!
+//! When the destination already exists, identical content is simply dropped from the
+//! platform dir. If one version is a prefix of the other (the usual "an agent appended a
+//! few lines" case) the longer one is kept, since it already contains both. This is not a
+//! merge: there is no common base to merge against, so anything else is reported as a
+//! conflict. The canonical file is left untouched and the orphan is written next to it as
+//! `<name>.conflict`.
+
+use anyhow::{Context, Result};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use super::atomic;
+use super::trash::Trash;
+
+/// What happened to one migrated file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Destination did not exist; the file was moved there.
+    Moved,
+    /// Destination already contained the orphan's content; the orphan was dropped.
+    Identical,
+    /// The orphan is the destination plus more; it replaced the destination.
+    Extended,
+    /// Contents diverged; the orphan was written to this path instead.
+    Conflict(PathBuf),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Moved => write!(f, "moved"),
+            Outcome::Identical => write!(f, "already present, dropped duplicate"),
+            Outcome::Extended => write!(f, "extends the existing copy, replaced it"),
+            Outcome::Conflict(path) => {
+                let name = path.file_name().unwrap_or(path.as_os_str());
+                write!(f, "conflict, kept as {}", name.to_string_lossy())
+            }
+        }
+    }
+}
+
+/// Move `src` to `dst`, keeping the longer of two versions where one extends the other and
+/// a `.conflict` copy otherwise, as described in the module docs.
+/// Both sides go to `trash` before they change. With `dry_run`, only works out the outcome.
+pub fn migrate_file(src: &Path, dst: &Path, dry_run: bool, trash: &mut Trash) -> Result<Outcome> {
+    if !dry_run {
+        trash.preserve(src)?;
+    }
+    if !dst.exists() {
+        if !dry_run {
+            if let Some(parent) = dst.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            trash.before_write(dst)?;
+            move_file(src, dst)?;
+        }
+        return Ok(Outcome::Moved);
+    }
+
+    let ours = fs::read(src).with_context(|| format!("Failed to read {}", src.display()))?;
+    let theirs = fs::read(dst).with_context(|| format!("Failed to read {}", dst.display()))?;
+
+    let outcome = if ours == theirs || theirs.starts_with(&ours) {
+        Outcome::Identical
+    } else if ours.starts_with(&theirs) {
+        Outcome::Extended
+    } else {
+        Outcome::Conflict(conflict_path(dst))
+    };
+
+    if !dry_run {
+        match &outcome {
+            Outcome::Identical => fs::remove_file(src)?,
+            Outcome::Extended => {
+                trash.preserve(dst)?;
+                move_file(src, dst)?;
+            }
+            Outcome::Conflict(path) => {
+                trash.before_write(path)?;
+                move_file(src, path)?;
+            }
+            Outcome::Moved => unreachable!("destination exists"),
+        }
+    }
+    Ok(outcome)
+}
+
+/// Migrate every file under `src_dir` into `dst_dir`, keeping relative paths.
+/// Returns (relative path, outcome) per file, in path order.
+pub fn migrate_dir(
+    src_dir: &Path,
+    dst_dir: &Path,
+    dry_run: bool,
+    trash: &mut Trash,
+) -> Result<Vec<(PathBuf, Outcome)>> {
+    let mut results = Vec::new();
+    for entry in WalkDir::new(src_dir).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let rel = entry.path().strip_prefix(src_dir)?.to_path_buf();
+        let outcome = migrate_file(entry.path(), &dst_dir.join(&rel), dry_run, trash)?;
+        results.push((rel, outcome));
+    }
+    Ok(results)
+}
+
+/// First free `<dst>.conflict`, `<dst>.conflict.2`, ... path.
+fn conflict_path(dst: &Path) -> PathBuf {
+    let base = dst.as_os_str().to_string_lossy();
+    let mut candidate = PathBuf::from(format!("{base}.conflict"));
+    let mut n = 2;
+    while candidate.exists() {
+        candidate = PathBuf::from(format!("{base}.conflict.{n}"));
+        n += 1;
+    }
+    candidate
+}
+
+/// Rename (replacing `dst`), falling back to copy + remove across filesystems.
+fn move_file(src: &Path, dst: &Path) -> Result<()> {
+    if fs::rename(src, dst).is_ok() {
+        return Ok(());
+    }
+    atomic::copy(src, dst)
+        .with_context(|| format!("Failed to move {} -> {}", src.display(), dst.display()))?;
+    fs::remove_file(src)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestDir;
+
+    #[test]
+    fn test_migrate_file_outcomes() {
+        let dir = TestDir::new("surfcontext-test-migrate-file").with_files(&[
+            ("orphan/new.md", "new"),
+            ("orphan/same.md", "same"),
+            ("orphan/longer.md", "base\nplus agent notes\n"),
+            ("orphan/diverged.md", "ours"),
+            ("canon/same.md", "same"),
+            ("canon/longer.md", "base\n"),
+            ("canon/diverged.md", "theirs"),
+        ]);
+        let (orphan, canon) = (dir.join("orphan"), dir.join("canon"));
+        let migrate = |name: &str| {
+            migrate_file(
+                &orphan.join(name),
+                &canon.join(name),
+                false,
+                &mut Trash::default(),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(migrate("new.md"), Outcome::Moved);
+        assert_eq!(migrate("same.md"), Outcome::Identical);
+        assert_eq!(migrate("longer.md"), Outcome::Extended);
+        assert_eq!(
+            migrate("diverged.md"),
+            Outcome::Conflict(canon.join("diverged.md.conflict"))
+        );
+
+        assert_eq!(fs::read_to_string(canon.join("new.md")).unwrap(), "new");
+        assert_eq!(
+            fs::read_to_string(canon.join("longer.md")).unwrap(),
+            "base\nplus agent notes\n"
+        );
+        assert_eq!(
+            fs::read_to_string(canon.join("diverged.md")).unwrap(),
+            "theirs"
+        );
+        assert_eq!(
+            fs::read_to_string(canon.join("diverged.md.conflict")).unwrap(),
+            "ours"
+        );
+        assert_eq!(fs::read_dir(&orphan).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_migrate_dir_dry_run_touches_nothing() {
+        let dir = TestDir::new("surfcontext-test-migrate-dir").with_files(&[
+            ("orphan/a.md", "a"),
+            ("orphan/sub/b.md", "b"),
+            ("canon/a.md", "A"),
+        ]);
+        let (orphan, canon) = (dir.join("orphan"), dir.join("canon"));
+
+        let preview = migrate_dir(&orphan, &canon, true, &mut Trash::default()).unwrap();
+        assert_eq!(
+            preview,
+            vec![
+                (
+                    PathBuf::from("a.md"),
+                    Outcome::Conflict(canon.join("a.md.conflict"))
+                ),
+                (PathBuf::from("sub/b.md"), Outcome::Moved),
+            ]
+        );
+        assert!(orphan.join("sub/b.md").exists());
+        assert!(!canon.join("sub").exists());
+
+        assert_eq!(
+            migrate_dir(&orphan, &canon, false, &mut Trash::default()).unwrap(),
+            preview
+        );
+        assert_eq!(fs::read_to_string(canon.join("sub/b.md")).unwrap(), "b");
+    }
+}
@@ -1,87 +1,56 @@
+pub mod atomic;
+pub mod audit;
+pub mod backport;
+pub mod check;
+pub mod clean;
 pub mod cross_repo;
+pub mod diff;
+pub mod directives;
 pub mod generate;
 pub mod local;
+pub mod lockfile;
+pub mod materialize;
+pub mod migrate;
+pub mod mv;
+pub mod platforms;
+pub mod queue;
+pub mod references;
+pub mod repo_lock;
+pub mod report;
+pub mod reporter;
+pub mod rewrite;
+pub mod script;
+pub mod sections;
+pub mod trash;
+pub mod watch;
 
 use anyhow::{Context, Result};
 use colored::Colorize;
 use std::path::{Path, PathBuf};
 
 use crate::config;
+use materialize::LinkMethod;
+use platforms::PlatformRegistry;
+use repo_lock::RepoLock;
+use report::{Code, Diagnostic, Stage};
+pub use report::SyncReport;
+pub use reporter::Reporter;
 
-/// Options passed from CLI to sync pipeline.
+/// Options for a sync run.
+#[derive(Default)]
 pub struct SyncOpts {
     pub dry_run: bool,
     pub verbose: bool,
     pub force: bool,
     pub local_only: bool,
+    /// Report nothing while running (the CLI's `--quiet`); see `reporter::for_opts`.
     pub quiet: bool,
-}
-
-/// Aggregate report from the sync pipeline.
-#[derive(Default)]
-pub struct SyncReport {
-    pub created: usize,
-    pub updated: usize,
-    pub unchanged: usize,
-    pub redirected: usize,
-    pub warnings: Vec<String>,
-    pub cross_repo_new: usize,
-    pub cross_repo_updated: usize,
-    pub cross_repo_unchanged: usize,
-    pub cross_repo_skipped: usize,
-}
-
-impl SyncReport {
-    pub fn print_summary(&self) {
-        println!();
-        println!("{}", "========================================".dimmed());
-        println!("{}", "Sync complete!".green().bold());
-        println!();
-
-        let mut parts = Vec::new();
-        if self.created > 0 {
-            parts.push(format!("{} created", self.created));
-        }
-        if self.updated > 0 {
-            parts.push(format!("{} updated", self.updated));
-        }
-        if self.redirected > 0 {
-            parts.push(format!("{} redirected", self.redirected));
-        }
-        if self.unchanged > 0 {
-            parts.push(format!("{} unchanged", self.unchanged));
-        }
-
-        if !parts.is_empty() {
-            println!("Local: {}", parts.join(", "));
-        }
-
-        let mut cross_parts = Vec::new();
-        if self.cross_repo_new > 0 {
-            cross_parts.push(format!("{} new", self.cross_repo_new));
-        }
-        if self.cross_repo_updated > 0 {
-            cross_parts.push(format!("{} updated", self.cross_repo_updated));
-        }
-        if self.cross_repo_unchanged > 0 {
-            cross_parts.push(format!("{} unchanged", self.cross_repo_unchanged));
-        }
-        if self.cross_repo_skipped > 0 {
-            cross_parts.push(format!("{} skipped (target newer)", self.cross_repo_skipped));
-        }
-
-        if !cross_parts.is_empty() {
-            println!("Cross-repo: {}", cross_parts.join(", "));
-        }
-
-        for w in &self.warnings {
-            println!("{} {}", "WARNING:".yellow(), w);
-        }
-    }
+    /// Print a unified diff for every file that changes, and a list of symlink retargets.
+    pub diff: bool,
 }
 
 /// Find the repo root by walking up from CWD looking for CONTEXT.md or surfcontext.json.
-fn find_repo_root() -> Result<PathBuf> {
+pub fn find_repo_root() -> Result<PathBuf> {
     let cwd = std::env::current_dir().context("Failed to get current directory")?;
     let mut dir = cwd.as_path();
 
@@ -101,76 +70,126 @@ fn find_repo_root() -> Result<PathBuf> {
     }
 }
 
-/// Run the full sync pipeline.
+/// Run the full sync pipeline from the current directory, printing to the console.
 pub fn run_sync(opts: &SyncOpts) -> Result<SyncReport> {
     let repo_root = find_repo_root()?;
     let config = config::load_config(&repo_root)?;
 
-    if !opts.quiet {
-        println!(
-            "{} {} {}",
-            "SurfContext Sync".bold(),
-            format!("v{}", config.version).dimmed(),
-            format!("— {}", repo_root.display()).dimmed()
-        );
-        println!("{}", "================================".dimmed());
-        if opts.dry_run {
-            println!("{}", "[DRY RUN] No files will be written.".yellow());
-        }
+    let mut reporter = reporter::for_opts(opts);
+    reporter.progress(&format!(
+        "{} {} {}",
+        "SurfContext Sync".bold(),
+        format!("v{}", config.version).dimmed(),
+        format!("— {}", repo_root.display()).dimmed()
+    ));
+    reporter.progress(&"================================".dimmed().to_string());
+    if opts.dry_run {
+        reporter.progress(&"[DRY RUN] No files will be written.".yellow().to_string());
     }
 
-    let mut report = SyncReport::default();
+    run_sync_at(&repo_root, &config, opts, reporter)
+}
 
-    // 1. Ensure .context/ structure
-    if !opts.quiet {
-        println!();
-        println!("{}", "[Setup] Verifying .context/ structure...".bold());
-    }
-    local::ensure_structure(&repo_root, &config, opts, &mut report)?;
+/// Every sync stage, in order, against an already located repo, reporting as `opts` says.
+///
+/// `held` is `repo_root`'s lock when the caller already has it, as `surf mv` does from
+/// planning through the sync after the move; with `None` the run takes the lock itself.
+pub fn run_stages(
+    repo_root: &Path,
+    config: &config::SurfConfig,
+    opts: &SyncOpts,
+    held: Option<RepoLock>,
+) -> Result<SyncReport> {
+    run_locked(repo_root, config, opts, reporter::for_opts(opts), held)
+}
+
+/// Run every sync stage against `repo_root`, sending progress, actions and diagnostics to
+/// `reporter` as they happen. `opts.quiet` is ignored; pass `reporter::Silent` instead.
+///
+/// Holds `.context/.surf.lock` for the whole run unless `opts.dry_run`.
+pub fn run_sync_at(
+    repo_root: &Path,
+    config: &config::SurfConfig,
+    opts: &SyncOpts,
+    reporter: Box<dyn Reporter>,
+) -> Result<SyncReport> {
+    run_locked(repo_root, config, opts, reporter, None)
+}
 
-    // 2. Setup symlinks/copies from .context/ -> .claude/
-    if !opts.quiet {
-        println!();
-        println!("{}", "[Symlinks] Setting up .claude/ -> .context/ links...".bold());
+fn run_locked(
+    repo_root: &Path,
+    config: &config::SurfConfig,
+    opts: &SyncOpts,
+    reporter: Box<dyn Reporter>,
+    held: Option<RepoLock>,
+) -> Result<SyncReport> {
+    let mut report =
+        SyncReport::with_reporter(trash::Trash::new(repo_root, opts.dry_run), reporter);
+    report.set_overrides(report::severity_overrides(&config.diagnostics)?);
+    let _lock = match held {
+        Some(lock) => Some(lock),
+        None => lock_repo(repo_root, opts, &mut report)?,
+    };
+    if check_platforms(config, &mut report) {
+        return Ok(report);
     }
-    local::setup_links(&repo_root, &config, opts, &mut report)?;
+
+    // 1. Ensure .context/ structure
+    report.stage(Stage::Setup, "[Setup] Verifying .context/ structure...");
+    local::ensure_structure(repo_root, config, opts, &mut report)?;
+
+    // 2. Setup symlinks/copies from .context/ -> each platform dir
+    report.stage(Stage::Links, "[Symlinks] Setting up platform -> .context/ links...");
+    local::setup_links(repo_root, config, opts, &mut report)?;
 
     // 3. Generate platform files (CLAUDE.md, AGENTS.md)
-    if !opts.quiet {
-        println!();
-        println!("{}", "[Generate] Building platform files...".bold());
-    }
-    generate::generate_all(&repo_root, &config, opts, &mut report)?;
+    report.stage(Stage::Generate, "[Generate] Building platform files...");
+    generate::generate_all(repo_root, config, opts, &mut report)?;
 
     // 4. Sync queue
-    local::sync_queue(&repo_root, opts, &mut report)?;
+    local::sync_queue(repo_root, config, opts, &mut report)?;
 
     // 5. Defensive sweep
-    if !opts.quiet {
-        println!();
-        println!("{}", "[Defensive] Scanning .claude/ for orphans...".bold());
-    }
-    local::defensive_sweep(&repo_root, opts, &mut report)?;
+    report.stage(Stage::Sweep, "[Defensive] Scanning platform dirs for orphans...");
+    local::defensive_sweep(repo_root, config, opts, &mut report)?;
 
-    // 6. Path reference audit — catch .claude/ references in .context/ source files
-    if !opts.quiet {
-        println!();
-        println!("{}", "[Audit] Checking .context/ files for .claude/ path references...".bold());
-    }
-    local::audit_path_references(&repo_root, opts, &mut report)?;
+    // 6. Path reference audit — catch platform-dir references in .context/ source files
+    report.stage(Stage::Audit, "[Audit] Checking .context/ files for platform path references...");
+    audit::audit_path_references(repo_root, config, &mut report)?;
+    report.stage(Stage::Audit, "[Audit] Checking that referenced paths exist...");
+    audit::audit_broken_references(repo_root, config, &mut report)?;
 
     // 7. Cross-repo sync
     if !opts.local_only {
-        if !opts.quiet {
-            println!();
-            println!("{}", "[Cross-Repo] Running cross-repo sync...".bold());
-        }
-        cross_repo::sync_repos(&repo_root, &config, opts, &mut report)?;
+        report.stage(Stage::CrossRepo, "[Cross-Repo] Running cross-repo sync...");
+        cross_repo::sync_repos(repo_root, config, opts, &mut report)?;
     }
 
+    // 8. Lockfile — prune outputs that are no longer declared, record the rest
+    report.stage(Stage::Lockfile, "[Lockfile] Pruning stale outputs...");
+    lockfile::prune_and_write(repo_root, opts, &mut report)?;
+
+    if opts.diff {
+        report.report_retargets();
+    }
     Ok(report)
 }
 
+/// `repo_lock::for_command` for a sync run, telling the run's reporter if it has to wait.
+/// Dry runs write nothing and take no lock.
+pub(crate) fn lock_repo(
+    repo_root: &Path,
+    opts: &SyncOpts,
+    report: &mut SyncReport,
+) -> Result<Option<RepoLock>> {
+    let mut silent = reporter::Silent;
+    let reporter: &mut dyn Reporter = match &mut report.reporter {
+        Some(reporter) => &mut **reporter,
+        None => &mut silent,
+    };
+    repo_lock::for_command(repo_root, opts.dry_run, reporter)
+}
+
 /// Canonicalize a directory list from config for structure setup.
 pub fn canonical_dirs(config: &config::SurfConfig) -> Vec<&str> {
     vec![
@@ -181,15 +200,147 @@ pub fn canonical_dirs(config: &config::SurfConfig) -> Vec<&str> {
     ]
 }
 
-/// Get the list of directories that should be symlinked from .claude/ -> .context/.
-pub fn link_mappings(config: &config::SurfConfig) -> Vec<(&str, &str)> {
-    // Returns (context_dir, claude_subdir_name)
-    vec![
-        (&config.canonical.agents_dir as &str, "agents"),
-        (&config.canonical.docs_dir as &str, "docs"),
-        (&config.canonical.skills_dir as &str, "skills"),
-        (&config.canonical.guides_dir as &str, "guides"),
-    ]
+/// One platform's view of `.context/`, e.g. `.claude/` with links to each canonical dir.
+pub struct LinkFarm {
+    pub platform: String,
+    /// Link dir root, relative to the repo root (e.g. `.claude`).
+    pub root: String,
+    /// (canonical dir, link path) pairs, both relative to the repo root.
+    pub links: Vec<(String, String)>,
+    /// How each link is materialized (`generation.<platform>.method`).
+    pub method: LinkMethod,
+    /// Tool-owned entries in `root` that the defensive sweep leaves alone.
+    pub native: Vec<String>,
+}
+
+impl LinkFarm {
+    /// Whether `name` (an entry directly under `root`) belongs to the tool itself.
+    pub fn is_native(&self, name: &str) -> bool {
+        (name.starts_with("settings") && name.ends_with(".json"))
+            || self.native.iter().any(|n| n == name)
+    }
+
+    /// Whether `name` (an entry directly under `root`) is, or contains, one of the links.
+    pub fn is_managed(&self, name: &str) -> bool {
+        let entry = format!("{}/{name}", self.root);
+        self.links
+            .iter()
+            .any(|(_, link)| *link == entry || link.starts_with(&format!("{entry}/")))
+    }
+}
+
+/// Work out every platform's link farm from `generation`.
+///
+/// Each entry whose platform has a link dir gets one; `agentsDir`, `docsDir`, `skillsDir`
+/// and `guidesDir` override where the individual links go, and `method` picks symlinks,
+/// copies or hardlinks. With no `generation` block,
+/// the `platforms` list (or just `claude`) is used with default settings.
+/// Platforms the registry does not know are skipped; see `unknown_platforms`.
+pub fn link_farms(config: &config::SurfConfig) -> Vec<LinkFarm> {
+    let registry = PlatformRegistry::builtin();
+    let default_gen = config::PlatformGen::default();
+
+    let mut farms = Vec::new();
+    for (platform, platform_gen) in platform_entries(config, &default_gen) {
+        let Some(generator) = registry.get(platform) else {
+            continue;
+        };
+        let Some(root) = generator.link_dir() else {
+            continue;
+        };
+
+        let link = |custom: &Option<String>, name: &str| {
+            custom
+                .as_deref()
+                .map(|d| d.trim_end_matches('/').to_string())
+                .unwrap_or_else(|| format!("{root}/{name}"))
+        };
+        let canonical = &config.canonical;
+        let links = vec![
+            (canonical.agents_dir.clone(), link(&platform_gen.agents_dir, "agents")),
+            (canonical.docs_dir.clone(), link(&platform_gen.docs_dir, "docs")),
+            (canonical.skills_dir.clone(), link(&platform_gen.skills_dir, "skills")),
+            (canonical.guides_dir.clone(), link(&platform_gen.guides_dir, "guides")),
+        ];
+
+        farms.push(LinkFarm {
+            platform: platform.to_string(),
+            root: root.to_string(),
+            links,
+            method: LinkMethod::from_config(&platform_gen.method),
+            native: generator
+                .native_entries()
+                .iter()
+                .map(|n| n.to_string())
+                .chain(platform_gen.native.iter().cloned())
+                .collect(),
+        });
+    }
+
+    farms
+}
+
+/// Report each configured platform the registry does not know (SC302). Returns whether the
+/// run has to stop: a misspelled platform would silently lose its outputs, so nothing is
+/// written unless `diagnostics` demotes the code, in which case those platforms are skipped.
+pub(crate) fn check_platforms(config: &config::SurfConfig, report: &mut SyncReport) -> bool {
+    let errors = report.errors.len();
+    let registry = PlatformRegistry::builtin();
+    for platform in unknown_platforms(config) {
+        report.error(Diagnostic::new(
+            Code::UnknownPlatform,
+            Stage::Setup,
+            format!(
+                "Unknown platform `{platform}` in surfcontext.json (known: {}); nothing was written",
+                registry.names().join(", ")
+            ),
+        ));
+    }
+    report.errors.len() > errors
+}
+
+/// Configured platform names the registry does not know, in name order.
+pub fn unknown_platforms(config: &config::SurfConfig) -> Vec<String> {
+    let registry = PlatformRegistry::builtin();
+    platform_entries(config, &config::PlatformGen::default())
+        .into_iter()
+        .filter(|(platform, _)| registry.get(platform).is_none())
+        .map(|(platform, _)| platform.to_string())
+        .collect()
+}
+
+/// The `generation` entries, or the `platforms` list with `default_gen`, in name order.
+fn platform_entries<'a>(
+    config: &'a config::SurfConfig,
+    default_gen: &'a config::PlatformGen,
+) -> Vec<(&'a str, &'a config::PlatformGen)> {
+    let mut entries: Vec<(&str, &config::PlatformGen)> = if config.generation.is_empty() {
+        let platforms = if config.platforms.is_empty() {
+            vec!["claude"]
+        } else {
+            config.platforms.iter().map(String::as_str).collect()
+        };
+        platforms.into_iter().map(|p| (p, default_gen)).collect()
+    } else {
+        config
+            .generation
+            .iter()
+            .map(|(p, g)| (p.as_str(), g))
+            .collect()
+    };
+    entries.sort_by_key(|(name, _)| *name);
+    entries
+}
+
+/// Relative symlink target that reaches `target` from the directory containing `link`.
+/// Both paths are relative to the repo root: `.claude/docs` -> `../.context/docs`.
+pub fn relative_link_target(link: &str, target: &str) -> PathBuf {
+    let depth = Path::new(link).components().count().saturating_sub(1);
+    let mut rel = PathBuf::new();
+    for _ in 0..depth {
+        rel.push("..");
+    }
+    rel.join(target)
 }
 
 /// Check if a path is a symlink pointing to the expected target.
@@ -199,3 +350,99 @@ pub fn is_symlink_to(link_path: &Path, expected: &Path) -> bool {
         Err(_) => false,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestDir;
+
+    #[test]
+    fn test_link_farms_per_platform() {
+        let config: config::SurfConfig = serde_json::from_str(
+            r#"{
+                "generation": {
+                    "claude": { "docsDir": ".claude/knowledge" },
+                    "codex": { "rootContext": "AGENTS.md", "skillsDir": ".codex/skills/", "native": ["hooks"] },
+                    "copilot": {},
+                    "windsurf": {}
+                }
+            }"#,
+        )
+        .unwrap();
+        let farms = link_farms(&config);
+        let roots: Vec<&str> = farms.iter().map(|f| f.root.as_str()).collect();
+        assert_eq!(roots, vec![".claude", ".codex"]);
+        assert!(farms[0]
+            .links
+            .contains(&(".context/docs".to_string(), ".claude/knowledge".to_string())));
+        assert!(farms[1]
+            .links
+            .contains(&(".context/skills".to_string(), ".codex/skills".to_string())));
+        assert!(farms[1].is_native("config.toml"));
+        assert!(farms[1].is_native("hooks"));
+        assert!(!farms[0].is_native("hooks"));
+        assert!(farms[0].is_native("settings.local.json"));
+        assert_eq!(unknown_platforms(&config), vec!["windsurf"]);
+    }
+
+    #[test]
+    fn test_link_farms_default_to_platforms_list() {
+        let config: config::SurfConfig = serde_json::from_str(r#"{ "platforms": ["claude"] }"#).unwrap();
+        let farms = link_farms(&config);
+        assert_eq!(farms.len(), 1);
+        assert_eq!(farms[0].links[0], (".context/agents".to_string(), ".claude/agents".to_string()));
+    }
+
+    #[test]
+    fn test_link_farm_method_and_managed_entries() {
+        let config: config::SurfConfig = serde_json::from_str(
+            r#"{ "generation": { "claude": { "method": "copy", "agentsDir": ".claude/x/agents" } } }"#,
+        )
+        .unwrap();
+        let farm = &link_farms(&config)[0];
+        assert_eq!(farm.method, LinkMethod::Copy);
+        assert!(farm.is_managed("docs"));
+        assert!(farm.is_managed("x"));
+        assert!(!farm.is_managed("notes"));
+    }
+
+    #[test]
+    fn test_relative_link_target() {
+        assert_eq!(
+            relative_link_target(".claude/docs", ".context/docs"),
+            PathBuf::from("../.context/docs")
+        );
+        assert_eq!(
+            relative_link_target(".cursor/rules/context", ".context/guides"),
+            PathBuf::from("../../.context/guides")
+        );
+    }
+
+    #[test]
+    fn test_unknown_platform_stops_the_run_unless_demoted() {
+        let repo = TestDir::new("surfcontext-test-unknown-platform");
+        std::fs::write(repo.join("CONTEXT.md"), "# Repo\n").unwrap();
+        let opts = SyncOpts {
+            local_only: true,
+            quiet: true,
+            ..Default::default()
+        };
+        let sync = |json: &str| {
+            let config: config::SurfConfig = serde_json::from_str(json).unwrap();
+            run_sync_at(&repo, &config, &opts, Box::new(reporter::Silent)).unwrap()
+        };
+
+        let report = sync(r#"{ "generation": { "claude": {}, "claud": {} } }"#);
+        assert_eq!(report.errors[0].code, Code::UnknownPlatform);
+        assert!(!repo.join("CLAUDE.md").exists());
+        assert!(!repo.join(".claude").exists());
+
+        let report = sync(
+            r#"{ "generation": { "claude": {}, "claud": {} },
+                 "diagnostics": { "unknown-platform": "warning" } }"#,
+        );
+        assert!(report.errors.is_empty());
+        assert_eq!(report.warnings[0].code, Code::UnknownPlatform);
+        assert!(repo.join("CLAUDE.md").exists());
+    }
+}
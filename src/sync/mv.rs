@@ -0,0 +1,753 @@
+//! `surf mv`: move a context document (or directory) without breaking what points at it.
+//!
+//! Every reference to the old path is rewritten in the files the audits read (CONTEXT.md
+//! and `.context/{docs,agents,guides,skills}`): backticked paths, markdown links and SurfDoc
+//! `related:` lists, in the style each was written in. Relative references inside the moved
+//! files are recomputed from their new location. In surfcontext.json, `discoveryOrder` and
+//! cross-repo `include`/`exclude` entries follow the move; the file is edited in place so its
+//! formatting survives. A local sync run afterwards regenerates the platform files; other
+//! repos see the move on the next full `surf sync`.
+//!
+//! Copy and hardlink mirrors still hold the old path. Unedited mirror copies are removed
+//! with the move, so a mirror whose lockfile entry predates per-file hashes cannot hand them
+//! back to `.context/` as new files; edited ones are left for sync to rescue.
+
+use anyhow::{Context, Result, bail};
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use super::audit;
+use super::materialize::LinkMethod;
+use super::references::{self, PathRef, RefKind};
+use super::trash::{self, Trash};
+use super::{atomic, link_farms};
+use crate::config::SurfConfig;
+
+const CONFIG_FILE: &str = "surfcontext.json";
+
+pub struct MoveOpts {
+    /// List the move and every rewrite without changing anything.
+    pub dry_run: bool,
+    pub quiet: bool,
+}
+
+/// One reference a move rewrites.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rewrite {
+    /// Repo-relative path of the file, before the move.
+    pub file: String,
+    pub line: usize,
+    pub from: String,
+    pub to: String,
+}
+
+impl std::fmt::Display for Rewrite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}  {} -> {}",
+            self.file, self.line, self.from, self.to
+        )
+    }
+}
+
+/// Everything a move changes, worked out before anything is touched.
+#[derive(Debug)]
+pub struct MovePlan {
+    pub old: PathBuf,
+    pub new: PathBuf,
+    pub rewrites: Vec<Rewrite>,
+    /// Config entries the move leaves matching nothing.
+    pub warnings: Vec<String>,
+    /// Unedited copy/hardlink mirror files of the old path, removed with the move.
+    pub copies: Vec<PathBuf>,
+    /// Where each mirror holds the old path, so the directories it empties can go too.
+    mirrors: Vec<PathBuf>,
+    /// Rewritten contents, by repo-relative path after the move.
+    contents: BTreeMap<PathBuf, String>,
+}
+
+impl MovePlan {
+    /// How many files have rewritten references.
+    pub fn files(&self) -> usize {
+        self.contents.len()
+    }
+}
+
+/// Work out the move from `old` to `new` (repo-relative) and every rewrite it needs.
+pub fn plan(repo_root: &Path, config: &SurfConfig, old: &Path, new: &Path) -> Result<MovePlan> {
+    check_move(repo_root, config, old, new)?;
+    let moved = |path: &Path| -> Option<PathBuf> {
+        let rest = path.strip_prefix(old).ok()?;
+        Some(if rest.as_os_str().is_empty() {
+            new.to_path_buf()
+        } else {
+            new.join(rest)
+        })
+    };
+
+    let mut files = audit::sources(repo_root, config);
+    let extensions = &config.audit.extensions;
+    for entry in walkdir::WalkDir::new(repo_root.join(old))
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| {
+            e.path()
+                .extension()
+                .is_some_and(|ext| extensions.iter().any(|x| ext == x.as_str()))
+        })
+    {
+        if let Ok(rel) = entry.path().strip_prefix(repo_root)
+            && !files.iter().any(|f| f == rel)
+        {
+            files.push(rel.to_path_buf());
+        }
+    }
+
+    let (copies, mirrors) = mirror_copies(repo_root, config, old)?;
+    let mut plan = MovePlan {
+        old: old.to_path_buf(),
+        new: new.to_path_buf(),
+        rewrites: Vec::new(),
+        warnings: Vec::new(),
+        copies,
+        mirrors,
+        contents: BTreeMap::new(),
+    };
+    for file in files {
+        let Ok(content) = fs::read_to_string(repo_root.join(&file)) else {
+            continue;
+        };
+        let moved_file = moved(&file);
+        let location = moved_file.clone().unwrap_or_else(|| file.clone());
+
+        let mut edits: Vec<(PathRef, String)> = Vec::new();
+        let mut refs = references::related(&file, &content);
+        refs.extend(references::extract(&file, &content));
+        for path_ref in refs {
+            let target = moved(&path_ref.target);
+            if target.is_none()
+                && (moved_file.is_none()
+                    || !from_file_dir(&path_ref)
+                    || fs::symlink_metadata(repo_root.join(&path_ref.target)).is_err())
+            {
+                continue;
+            }
+            let target = target.unwrap_or_else(|| path_ref.target.clone());
+            let to = render(&path_ref, &location, &target);
+            let seen = edits.iter().any(|(r, _)| {
+                r.line == path_ref.line && r.kind == path_ref.kind && r.raw == path_ref.raw
+            });
+            if to != path_ref.raw && !seen {
+                edits.push((path_ref, to));
+            }
+        }
+        if edits.is_empty() {
+            continue;
+        }
+
+        let mut rewritten = String::with_capacity(content.len());
+        for (index, line) in content.split_inclusive('\n').enumerate() {
+            let mut line = line.to_string();
+            for (path_ref, to) in edits.iter().filter(|(r, _)| r.line == index + 1) {
+                line = replace_reference(&line, path_ref.kind, &path_ref.raw, to);
+            }
+            rewritten.push_str(&line);
+        }
+        let file_name = file.to_string_lossy().replace('\\', "/");
+        plan.rewrites
+            .extend(edits.into_iter().map(|(path_ref, to)| Rewrite {
+                file: file_name.clone(),
+                line: path_ref.line,
+                from: path_ref.raw,
+                to,
+            }));
+        plan.contents.insert(location, rewritten);
+    }
+
+    let config_path = repo_root.join(CONFIG_FILE);
+    if config_path.is_file() {
+        let text = fs::read_to_string(&config_path)?;
+        let rewritten = rewrite_config(&text, config, &mut plan, &moved)?;
+        if rewritten != text {
+            plan.contents.insert(PathBuf::from(CONFIG_FILE), rewritten);
+        }
+    }
+    Ok(plan)
+}
+
+/// Move `plan.old` to `plan.new`, remove its mirror copies and write every rewritten file.
+/// Everything removed or edited is saved to `trash` first.
+pub fn apply(repo_root: &Path, plan: &MovePlan, trash: &mut Trash) -> Result<()> {
+    let old = repo_root.join(&plan.old);
+    let new = repo_root.join(&plan.new);
+    trash.preserve(&old)?;
+    for path in plan.contents.keys().filter(|p| !p.starts_with(&plan.new)) {
+        trash.preserve(&repo_root.join(path))?;
+    }
+    for copy in &plan.copies {
+        let copy = repo_root.join(copy);
+        trash.preserve(&copy)?;
+        fs::remove_file(&copy)?;
+    }
+    for mirror in &plan.mirrors {
+        for entry in walkdir::WalkDir::new(repo_root.join(mirror))
+            .contents_first(true)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_dir())
+        {
+            let _ = fs::remove_dir(entry.path());
+        }
+    }
+
+    if let Some(parent) = new.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    trash.before_write(&new)?;
+    fs::rename(&old, &new)
+        .with_context(|| format!("Failed to move {} -> {}", old.display(), new.display()))?;
+    for (path, content) in &plan.contents {
+        atomic::write(&repo_root.join(path), content)?;
+    }
+    Ok(())
+}
+
+/// Files under copy and hardlink mirrors that are unedited copies of `old` or its contents,
+/// and each mirror's counterpart of `old`.
+fn mirror_copies(
+    repo_root: &Path,
+    config: &SurfConfig,
+    old: &Path,
+) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut copies = Vec::new();
+    let mut mirrors = Vec::new();
+    for farm in link_farms(config) {
+        if farm.method == LinkMethod::Symlink {
+            continue;
+        }
+        for (canonical, link) in &farm.links {
+            let Ok(rest) = old.strip_prefix(canonical) else {
+                continue;
+            };
+            if repo_root.join(link).is_symlink() {
+                continue;
+            }
+            let mirror = repo_root.join(link).join(rest);
+            mirrors.push(Path::new(link).join(rest));
+            for entry in walkdir::WalkDir::new(&mirror)
+                .sort_by_file_name()
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file())
+            {
+                let rel = entry.path().strip_prefix(&mirror)?;
+                // A single file walks as itself, with an empty relative path.
+                let original = if rel.as_os_str().is_empty() {
+                    repo_root.join(old)
+                } else {
+                    repo_root.join(old).join(rel)
+                };
+                if fs::read(&original).ok() == fs::read(entry.path()).ok()
+                    && let Ok(copy) = entry.path().strip_prefix(repo_root)
+                {
+                    copies.push(copy.to_path_buf());
+                }
+            }
+        }
+    }
+    Ok((copies, mirrors))
+}
+
+/// `arg` (relative to `cwd`) as a path relative to `repo_root`.
+pub fn repo_path(repo_root: &Path, cwd: &Path, arg: &str) -> Result<PathBuf> {
+    let path = references::normalize(&cwd.join(arg));
+    match path.strip_prefix(repo_root) {
+        Ok(rel) if !rel.as_os_str().is_empty() => Ok(rel.to_path_buf()),
+        _ => bail!("{arg} is not inside the repo at {}", repo_root.display()),
+    }
+}
+
+fn check_move(repo_root: &Path, config: &SurfConfig, old: &Path, new: &Path) -> Result<()> {
+    if fs::symlink_metadata(repo_root.join(old)).is_err() {
+        bail!("{} does not exist", old.display());
+    }
+    if fs::symlink_metadata(repo_root.join(new)).is_ok() {
+        bail!("{} already exists", new.display());
+    }
+    if new.starts_with(old) {
+        bail!("Cannot move {} into itself", old.display());
+    }
+    for fixed in [&config.canonical.root_context, CONFIG_FILE] {
+        if old == Path::new(fixed) {
+            bail!(
+                "{fixed} cannot be moved with surf mv (the root context is set by canonical.rootContext)"
+            );
+        }
+    }
+    for path in [old, new] {
+        if path.starts_with(trash::TRASH_DIR) {
+            bail!("{} is inside the sync trash", path.display());
+        }
+        for farm in link_farms(config) {
+            if path.starts_with(&farm.root) {
+                bail!(
+                    "{} is inside {}, which sync generates from .context/; move .context/ paths instead",
+                    path.display(),
+                    farm.root
+                );
+            }
+        }
+    }
+    for farm in link_farms(config) {
+        if let Some((canonical, _)) = farm
+            .links
+            .iter()
+            .find(|(canonical, _)| Path::new(canonical).starts_with(old))
+        {
+            bail!(
+                "{} holds {canonical}, a canonical directory; change `canonical` in {CONFIG_FILE} instead",
+                old.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Whether `path_ref` is written relative to its file's directory.
+fn from_file_dir(path_ref: &PathRef) -> bool {
+    match path_ref.kind {
+        RefKind::Backticked => path_ref.raw.starts_with("./") || path_ref.raw.starts_with("../"),
+        RefKind::Link | RefKind::Related => !path_ref.raw.starts_with('/'),
+    }
+}
+
+/// How `path_ref` reads once it points at `target` from `file`, keeping its style: rooted
+/// (`/x`), relative to the file's directory, or relative to the repo root.
+fn render(path_ref: &PathRef, file: &Path, target: &Path) -> String {
+    let raw = &path_ref.raw;
+    let target_str = target.to_string_lossy().replace('\\', "/");
+    let mut rendered = if raw.starts_with('/') {
+        format!("/{target_str}")
+    } else if from_file_dir(path_ref) {
+        let rel = relative_path(file.parent().unwrap_or(Path::new("")), target);
+        let dotted = raw.starts_with("./") || path_ref.kind == RefKind::Backticked;
+        if dotted && !rel.starts_with("../") {
+            format!("./{rel}")
+        } else {
+            rel
+        }
+    } else {
+        target_str
+    };
+    if raw.ends_with('/') && !rendered.ends_with('/') {
+        rendered.push('/');
+    }
+    rendered
+}
+
+/// `to` relative to the directory `from_dir`; both repo-relative.
+fn relative_path(from_dir: &Path, to: &Path) -> String {
+    let from: Vec<_> = from_dir.components().collect();
+    let to: Vec<_> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut parts = vec!["..".to_string(); from.len() - common];
+    parts.extend(
+        to[common..]
+            .iter()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned()),
+    );
+    parts.join("/")
+}
+
+/// Replace the reference `from` in `line` with `to`, only where it appears as that kind of
+/// reference: a link target, a whole code span (or one with a `:line` suffix), or a list item.
+fn replace_reference(line: &str, kind: RefKind, from: &str, to: &str) -> String {
+    let bounded = |before: &str, after: &str| {
+        let next = after.chars().next();
+        match kind {
+            RefKind::Link => {
+                (before.ends_with("](") || before.ends_with("](<"))
+                    && next.is_none_or(|c| c.is_whitespace() || matches!(c, ')' | '#' | '?' | '>'))
+            }
+            RefKind::Backticked => before.ends_with('`') && matches!(next, Some('`' | ':')),
+            RefKind::Related => {
+                before
+                    .chars()
+                    .next_back()
+                    .is_none_or(|c| c.is_whitespace() || matches!(c, '[' | ',' | '"' | '\''))
+                    && next.is_none_or(|c| c.is_whitespace() || matches!(c, ']' | ',' | '"' | '\''))
+            }
+        }
+    };
+
+    let mut out = String::with_capacity(line.len());
+    let mut copied = 0;
+    for (start, _) in line.match_indices(from) {
+        let end = start + from.len();
+        if bounded(&line[..start], &line[end..]) {
+            out.push_str(&line[copied..start]);
+            out.push_str(to);
+            copied = end;
+        }
+    }
+    out.push_str(&line[copied..]);
+    out
+}
+
+/// Rewrite the `discoveryOrder` and cross-repo `include`/`exclude` entries in `text` (the
+/// raw surfcontext.json) that name a moved path, recording each in `plan`.
+fn rewrite_config(
+    text: &str,
+    config: &SurfConfig,
+    plan: &mut MovePlan,
+    moved: &dyn Fn(&Path) -> Option<PathBuf>,
+) -> Result<String> {
+    let old_name = plan
+        .old
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned());
+    let new_name = plan
+        .new
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned());
+
+    let mut replacements: Vec<(Range<usize>, String)> = Vec::new();
+    for (keys, span) in json_strings(text)? {
+        let value: String = serde_json::from_str(&text[span.clone()])?;
+        let line = text[..span.start].matches('\n').count() + 1;
+        let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+        let replacement = match keys.as_slice() {
+            ["discoveryOrder", _] => {
+                moved(Path::new(&value)).map(|p| p.to_string_lossy().replace('\\', "/"))
+            }
+            ["sync", section, "targets", _, "include" | "exclude", _] => {
+                let Some(source) = config.sync.get(*section).map(|s| Path::new(&s.source)) else {
+                    continue;
+                };
+                let left_source = format!(
+                    "{CONFIG_FILE}:{line} sync.{section} filter `{value}` will match nothing: {} is outside {}",
+                    plan.new.display(),
+                    source.display()
+                );
+                if let Some(target) = moved(&source.join(&value)) {
+                    match target.strip_prefix(source) {
+                        Ok(rel) => Some(rel.to_string_lossy().replace('\\', "/")),
+                        Err(_) => {
+                            plan.warnings.push(left_source);
+                            None
+                        }
+                    }
+                } else if plan.old.starts_with(source)
+                    && old_name.as_deref() == Some(value.as_str())
+                    && old_name != new_name
+                {
+                    if !plan.new.starts_with(source) {
+                        plan.warnings.push(left_source);
+                        None
+                    } else {
+                        new_name.clone()
+                    }
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        if let Some(to) = replacement.filter(|to| *to != value) {
+            replacements.push((span, serde_json::to_string(&to)?));
+            plan.rewrites.push(Rewrite {
+                file: CONFIG_FILE.to_string(),
+                line,
+                from: value,
+                to,
+            });
+        }
+    }
+
+    let mut rewritten = text.to_string();
+    for (span, literal) in replacements.into_iter().rev() {
+        rewritten.replace_range(span, &literal);
+    }
+    Ok(rewritten)
+}
+
+/// Every string value in the JSON `text`: the keys and indices leading to it, and the byte
+/// range of its literal (quotes included), so a value can be replaced without reformatting.
+fn json_strings(text: &str) -> Result<Vec<(Vec<String>, Range<usize>)>> {
+    struct Walker<'a> {
+        text: &'a str,
+        pos: usize,
+        keys: Vec<String>,
+        found: Vec<(Vec<String>, Range<usize>)>,
+    }
+
+    impl Walker<'_> {
+        fn peek(&mut self) -> Result<u8> {
+            while let Some(b) = self.text.as_bytes().get(self.pos) {
+                if !b.is_ascii_whitespace() {
+                    return Ok(*b);
+                }
+                self.pos += 1;
+            }
+            bail!("{CONFIG_FILE} ends unexpectedly")
+        }
+
+        fn expect(&mut self, byte: u8) -> Result<()> {
+            if self.peek()? != byte {
+                bail!(
+                    "{CONFIG_FILE}: expected `{}` at byte {}",
+                    byte as char,
+                    self.pos
+                );
+            }
+            self.pos += 1;
+            Ok(())
+        }
+
+        fn string(&mut self) -> Result<Range<usize>> {
+            let start = self.pos;
+            self.expect(b'"')?;
+            let bytes = self.text.as_bytes();
+            while let Some(b) = bytes.get(self.pos) {
+                self.pos += if *b == b'\\' { 2 } else { 1 };
+                if *b == b'"' {
+                    return Ok(start..self.pos);
+                }
+            }
+            bail!("{CONFIG_FILE}: unterminated string at byte {start}")
+        }
+
+        fn value(&mut self) -> Result<()> {
+            match self.peek()? {
+                b'{' => {
+                    self.pos += 1;
+                    if self.peek()? == b'}' {
+                        self.pos += 1;
+                        return Ok(());
+                    }
+                    loop {
+                        let key = self.string()?;
+                        self.keys.push(serde_json::from_str(&self.text[key])?);
+                        self.expect(b':')?;
+                        self.value()?;
+                        self.keys.pop();
+                        if self.peek()? == b'}' {
+                            self.pos += 1;
+                            return Ok(());
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                b'[' => {
+                    self.pos += 1;
+                    if self.peek()? == b']' {
+                        self.pos += 1;
+                        return Ok(());
+                    }
+                    for index in 0.. {
+                        self.keys.push(index.to_string());
+                        self.value()?;
+                        self.keys.pop();
+                        if self.peek()? == b']' {
+                            self.pos += 1;
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                    Ok(())
+                }
+                b'"' => {
+                    let span = self.string()?;
+                    self.found.push((self.keys.clone(), span));
+                    Ok(())
+                }
+                _ => {
+                    let bytes = self.text.as_bytes();
+                    while bytes
+                        .get(self.pos)
+                        .is_some_and(|b| !b.is_ascii_whitespace() && !b",]}".contains(b))
+                    {
+                        self.pos += 1;
+                    }
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    let mut walker = Walker {
+        text,
+        pos: 0,
+        keys: Vec::new(),
+        found: Vec::new(),
+    };
+    walker.value()?;
+    Ok(walker.found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+    use crate::test_util::TestDir;
+
+    #[test]
+    fn test_move_rewrites_documents_and_config() {
+        let config_text = r#"{
+  "discoveryOrder": ["CONTEXT.md", ".context/docs/api.md"],
+  "sync": {
+    "docs": {
+      "source": ".context/docs",
+      "targets": [{ "repo": "../other", "dest": "docs", "include": ["api.md", "auth.md"] }]
+    }
+  }
+}
+"#;
+        let repo = TestDir::new("surfcontext-test-mv").with_files(&[
+            (CONFIG_FILE, config_text),
+            (
+                "CONTEXT.md",
+                "| `.context/docs/api.md` | API |\n```\n`.context/docs/api.md`\n```\n",
+            ),
+            (
+                ".context/docs/api.md",
+                "---\nrelated: [auth.md]\n---\nSee [setup](../guides/setup.md) and `./missing.md`.\n",
+            ),
+            (
+                ".context/docs/auth.md",
+                "[api](api.md#errors) and [self](auth.md)\n",
+            ),
+            (
+                ".context/guides/setup.md",
+                "---\nrelated:\n  - ../docs/api.md\n---\n",
+            ),
+        ]);
+        let config = config::load_config(&repo).unwrap();
+        let plan = plan(
+            &repo,
+            &config,
+            Path::new(".context/docs/api.md"),
+            Path::new(".context/guides/api/reference.md"),
+        )
+        .unwrap();
+
+        let rewrites: Vec<String> = plan.rewrites.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            rewrites,
+            vec![
+                ".context/docs/api.md:2  auth.md -> ../../docs/auth.md",
+                ".context/docs/api.md:4  ../guides/setup.md -> ../setup.md",
+                ".context/docs/auth.md:1  api.md -> ../guides/api/reference.md",
+                ".context/guides/setup.md:3  ../docs/api.md -> api/reference.md",
+                "CONTEXT.md:1  .context/docs/api.md -> .context/guides/api/reference.md",
+                "surfcontext.json:2  .context/docs/api.md -> .context/guides/api/reference.md",
+            ]
+        );
+        assert_eq!(plan.warnings.len(), 1, "{:?}", plan.warnings);
+        assert!(plan.warnings[0].contains("sync.docs filter `api.md` will match nothing"));
+
+        let mut trash = Trash::new(&repo, false);
+        apply(&repo, &plan, &mut trash).unwrap();
+        assert!(!repo.join(".context/docs/api.md").exists());
+        assert_eq!(
+            fs::read_to_string(repo.join(".context/guides/api/reference.md")).unwrap(),
+            "---\nrelated: [../../docs/auth.md]\n---\nSee [setup](../setup.md) and `./missing.md`.\n"
+        );
+        assert_eq!(
+            fs::read_to_string(repo.join(".context/docs/auth.md")).unwrap(),
+            "[api](../guides/api/reference.md#errors) and [self](auth.md)\n"
+        );
+        assert_eq!(
+            fs::read_to_string(repo.join("CONTEXT.md")).unwrap(),
+            "| `.context/guides/api/reference.md` | API |\n```\n`.context/docs/api.md`\n```\n"
+        );
+        assert_eq!(
+            fs::read_to_string(repo.join(CONFIG_FILE)).unwrap(),
+            config_text.replace(
+                r#""CONTEXT.md", ".context/docs/api.md""#,
+                r#""CONTEXT.md", ".context/guides/api/reference.md""#
+            )
+        );
+    }
+
+    #[test]
+    fn test_move_removes_unedited_mirror_copies() {
+        let repo = TestDir::new("surfcontext-test-mv-mirror").with_files(&[
+            (
+                CONFIG_FILE,
+                r#"{ "generation": { "claude": { "method": "copy" } } }"#,
+            ),
+            (".context/docs/api/a.md", "a\n"),
+            (".context/docs/api/b.md", "b\n"),
+            (".claude/docs/api/a.md", "a\n"),
+            (".claude/docs/api/b.md", "edited\n"),
+            (".claude/docs/other.md", "other\n"),
+        ]);
+        let config = config::load_config(&repo).unwrap();
+        let plan = plan(
+            &repo,
+            &config,
+            Path::new(".context/docs/api"),
+            Path::new(".context/api"),
+        )
+        .unwrap();
+        assert_eq!(plan.copies, vec![PathBuf::from(".claude/docs/api/a.md")]);
+
+        apply(&repo, &plan, &mut Trash::new(&repo, false)).unwrap();
+        assert!(repo.join(".context/api/b.md").exists());
+        assert!(!repo.join(".claude/docs/api/a.md").exists());
+        assert!(repo.join(".claude/docs/api/b.md").exists());
+        assert!(repo.join(".claude/docs/other.md").exists());
+    }
+
+    #[test]
+    fn test_move_is_refused_for_generated_and_existing_paths() {
+        let repo = TestDir::new("surfcontext-test-mv-refused").with_files(&[
+            ("CONTEXT.md", "x\n"),
+            (".context/docs/a.md", "a\n"),
+            (".context/docs/b.md", "b\n"),
+        ]);
+        let config = config::load_config(&repo).unwrap();
+        let refused = |old: &str, new: &str| {
+            plan(&repo, &config, Path::new(old), Path::new(new))
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(refused(".context/docs/a.md", ".context/docs/b.md").contains("already exists"));
+        assert!(refused(".context/docs/a.md", ".claude/docs/c.md").contains("sync generates"));
+        assert!(refused(".context/docs", ".context/docs/x").contains("into itself"));
+        assert!(refused(".context/docs", ".context/knowledge").contains("canonical directory"));
+        assert!(refused("CONTEXT.md", "README.md").contains("canonical.rootContext"));
+    }
+
+    #[test]
+    fn test_json_strings_and_relative_paths() {
+        let text = r#"{ "a": [1, "x\"y", { "b": "z" }], "c": true }"#;
+        let found: Vec<(String, &str)> = json_strings(text)
+            .unwrap()
+            .into_iter()
+            .map(|(keys, span)| (keys.join("."), &text[span]))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("a.1".to_string(), r#""x\"y""#),
+                ("a.2.b".to_string(), r#""z""#)
+            ]
+        );
+
+        assert_eq!(
+            relative_path(Path::new(".context/docs"), Path::new(".context/docs/a.md")),
+            "a.md"
+        );
+        assert_eq!(
+            relative_path(Path::new(".context/docs/x"), Path::new("CONTEXT.md")),
+            "../../../CONTEXT.md"
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! Platform generator registry.
+//!
+//! Each tool that consumes SurfContext (Claude, Codex, Cursor, ...) is described by a
+//! `PlatformGenerator`: where its root context file lives, how CONTEXT.md is transformed
+//! into it, and which directory holds its view of `.context/`. `generate_all` looks
+//! platforms up here instead of matching on names, so adding a tool means registering
+//! one more generator.
+
+use anyhow::{Result, bail};
+
+use super::generate::{generate_agents_md, generate_claude_md};
+use super::rewrite::PathRewriter;
+use crate::config::PlatformGen;
+
+/// Root-context transforms understood by the built-in generators.
+pub const TRANSFORM_METHODS: &[&str] = &["sed-copy", "template-copy"];
+
+/// Link materialization methods accepted in `generation.<platform>.method`.
+pub const LINK_METHODS: &[&str] = &["symlink", "copy", "hardlink"];
+
+/// A platform that SurfContext can generate files for.
+pub trait PlatformGenerator {
+    /// Key used under `generation` in surfcontext.json (e.g. `"claude"`).
+    fn name(&self) -> &str;
+
+    /// Root context output path, relative to the repo root, when `rootContext` is unset.
+    fn default_output(&self) -> &str;
+
+    /// Transform used when neither `rootContextMethod` nor a transform `method` is set.
+    fn default_method(&self) -> &str;
+
+    /// Directory holding this platform's view of `.context/` (e.g. `.claude`), if any.
+    fn link_dir(&self) -> Option<&str>;
+
+    /// Entries in the link dir that belong to the tool itself and are never swept.
+    /// `settings*.json` is always treated as native.
+    fn native_entries(&self) -> &[&str] {
+        &[]
+    }
+
+    /// Transforms this platform accepts.
+    fn methods(&self) -> &[&str] {
+        TRANSFORM_METHODS
+    }
+
+    /// Turn CONTEXT.md content into the platform's root context file.
+    /// `rewriter` maps canonical paths into this platform's link farm.
+    fn transform(
+        &self,
+        method: &str,
+        context_content: &str,
+        rewriter: &PathRewriter,
+    ) -> Result<String> {
+        match method {
+            "sed-copy" => Ok(generate_claude_md(
+                context_content,
+                rewriter,
+                self.link_dir(),
+            )),
+            "template-copy" => Ok(generate_agents_md(context_content, self.link_dir())),
+            other => bail!(
+                "Platform `{}` does not support method `{other}`",
+                self.name()
+            ),
+        }
+    }
+}
+
+/// Table-driven generator used for all built-in platforms.
+struct BuiltinPlatform {
+    name: &'static str,
+    output: &'static str,
+    method: &'static str,
+    link_dir: Option<&'static str>,
+    native: &'static [&'static str],
+}
+
+impl PlatformGenerator for BuiltinPlatform {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn default_output(&self) -> &str {
+        self.output
+    }
+
+    fn default_method(&self) -> &str {
+        self.method
+    }
+
+    fn link_dir(&self) -> Option<&str> {
+        self.link_dir
+    }
+
+    fn native_entries(&self) -> &[&str] {
+        self.native
+    }
+}
+
+/// Set of known platform generators, keyed by name.
+pub struct PlatformRegistry {
+    generators: Vec<Box<dyn PlatformGenerator>>,
+}
+
+/// A `generation` entry resolved against the registry.
+pub struct ResolvedPlatform<'a> {
+    pub generator: &'a dyn PlatformGenerator,
+    pub output: String,
+    pub method: String,
+}
+
+impl PlatformRegistry {
+    /// Registry with every platform SurfContext ships support for.
+    pub fn builtin() -> Self {
+        let mut registry = Self {
+            generators: Vec::new(),
+        };
+        let builtins: [(_, _, _, _, &'static [&'static str]); 5] = [
+            ("claude", "CLAUDE.md", "sed-copy", Some(".claude"), &[]),
+            (
+                "codex",
+                "AGENTS.md",
+                "template-copy",
+                Some(".codex"),
+                &["config.toml"],
+            ),
+            (
+                "cursor",
+                ".cursorrules",
+                "template-copy",
+                Some(".cursor"),
+                &["rules", "mcp.json"],
+            ),
+            (
+                "gemini",
+                "GEMINI.md",
+                "template-copy",
+                Some(".gemini"),
+                &["commands", "extensions"],
+            ),
+            (
+                "copilot",
+                ".github/copilot-instructions.md",
+                "template-copy",
+                None,
+                &[],
+            ),
+        ];
+        for (name, output, method, link_dir, native) in builtins {
+            registry.register(Box::new(BuiltinPlatform {
+                name,
+                output,
+                method,
+                link_dir,
+                native,
+            }));
+        }
+        registry
+    }
+
+    /// Add a generator, replacing any existing one with the same name.
+    pub fn register(&mut self, generator: Box<dyn PlatformGenerator>) {
+        self.generators.retain(|g| g.name() != generator.name());
+        self.generators.push(generator);
+    }
+
+    /// Look up a generator by platform name.
+    pub fn get(&self, name: &str) -> Option<&dyn PlatformGenerator> {
+        self.generators
+            .iter()
+            .find(|g| g.name() == name)
+            .map(|g| g.as_ref())
+    }
+
+    /// Names of all registered platforms, in registration order.
+    pub fn names(&self) -> Vec<&str> {
+        self.generators.iter().map(|g| g.name()).collect()
+    }
+
+    /// Validate a `generation.<platform>` entry and work out its output path and transform.
+    ///
+    /// `rootContextMethod` wins; otherwise `method` is used when it names a transform
+    /// (older configs put `"template-copy"` there), and the platform default applies last.
+    pub fn resolve<'a>(
+        &'a self,
+        platform: &str,
+        platform_gen: &PlatformGen,
+    ) -> Result<ResolvedPlatform<'a>> {
+        let Some(generator) = self.get(platform) else {
+            bail!(
+                "Unknown platform `{platform}` in surfcontext.json generation (known: {})",
+                self.names().join(", ")
+            );
+        };
+
+        let link_method_ok = LINK_METHODS.contains(&platform_gen.method.as_str());
+        let transform_method_ok = generator.methods().contains(&platform_gen.method.as_str());
+        if !link_method_ok && !transform_method_ok {
+            bail!(
+                "Unknown method `{}` for platform `{platform}` (expected one of: {})",
+                platform_gen.method,
+                LINK_METHODS
+                    .iter()
+                    .chain(generator.methods())
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        let method = match platform_gen.root_context_method.as_deref() {
+            Some(m) if generator.methods().contains(&m) => m,
+            Some(m) => bail!(
+                "Unknown rootContextMethod `{m}` for platform `{platform}` (expected one of: {})",
+                generator.methods().join(", ")
+            ),
+            None if transform_method_ok => platform_gen.method.as_str(),
+            None => generator.default_method(),
+        };
+
+        let output = platform_gen
+            .root_context
+            .as_deref()
+            .unwrap_or(generator.default_output());
+
+        Ok(ResolvedPlatform {
+            generator,
+            output: output.to_string(),
+            method: method.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn platform_gen(json: &str) -> PlatformGen {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn resolve_err(registry: &PlatformRegistry, platform: &str, json: &str) -> String {
+        match registry.resolve(platform, &platform_gen(json)) {
+            Ok(_) => panic!("expected `{platform}` with {json} to be rejected"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_defaults() {
+        let registry = PlatformRegistry::builtin();
+        let resolved = registry.resolve("codex", &platform_gen("{}")).unwrap();
+        assert_eq!(resolved.output, "AGENTS.md");
+        assert_eq!(resolved.method, "template-copy");
+
+        let resolved = registry.resolve("claude", &platform_gen("{}")).unwrap();
+        assert_eq!(resolved.output, "CLAUDE.md");
+        assert_eq!(resolved.method, "sed-copy");
+        assert_eq!(resolved.generator.link_dir(), Some(".claude"));
+    }
+
+    #[test]
+    fn test_resolve_legacy_transform_in_method() {
+        let registry = PlatformRegistry::builtin();
+        let entry = platform_gen(r#"{ "rootContext": "AGENTS.md", "method": "template-copy" }"#);
+        let resolved = registry.resolve("codex", &entry).unwrap();
+        assert_eq!(resolved.method, "template-copy");
+    }
+
+    #[test]
+    fn test_resolve_root_context_method_wins() {
+        let registry = PlatformRegistry::builtin();
+        let entry = platform_gen(r#"{ "method": "symlink", "rootContextMethod": "sed-copy" }"#);
+        let resolved = registry.resolve("gemini", &entry).unwrap();
+        assert_eq!(resolved.output, "GEMINI.md");
+        assert_eq!(resolved.method, "sed-copy");
+    }
+
+    #[test]
+    fn test_resolve_unknown_platform_is_error() {
+        let registry = PlatformRegistry::builtin();
+        let err = resolve_err(&registry, "windsurf", "{}");
+        assert!(err.contains("Unknown platform `windsurf`"));
+    }
+
+    #[test]
+    fn test_resolve_unknown_methods_are_errors() {
+        let registry = PlatformRegistry::builtin();
+        let err = resolve_err(&registry, "claude", r#"{ "method": "rsync" }"#);
+        assert!(err.contains("Unknown method `rsync`"));
+
+        let err = resolve_err(&registry, "claude", r#"{ "rootContextMethod": "magic" }"#);
+        assert!(err.contains("Unknown rootContextMethod `magic`"));
+    }
+
+    #[test]
+    fn test_register_replaces_existing() {
+        let mut registry = PlatformRegistry::builtin();
+        registry.register(Box::new(BuiltinPlatform {
+            name: "codex",
+            output: "CODEX.md",
+            method: "sed-copy",
+            link_dir: Some(".codex"),
+            native: &[],
+        }));
+        assert_eq!(
+            registry.names().iter().filter(|n| **n == "codex").count(),
+            1
+        );
+        assert_eq!(registry.get("codex").unwrap().default_output(), "CODEX.md");
+    }
+}
@@ -0,0 +1,708 @@
+//! `surf queue`: structured edits to the shared task queue, `.context/queue.md`.
+//!
+//! Tasks are markdown task items, with optional tokens anywhere in the text:
+//!
+//! ```text
+//! - [ ] Q-4 Fix the login redirect @alice !high #auth
+//! - [~] Q-3 Write the migration guide @bob
+//! - [x] Q-1 Set up CI #infra
+//! ```
+//!
+//! `[ ]` is to do, `[~]` in progress and `[x]` done; `Q-<n>` is the task ID, `@owner`,
+//! `!priority` and `#tag` are what `list` filters on. Everything else in the file (headings,
+//! prose, plain bullets, indented notes under a task) is left as written, and edits touch
+//! only the task line they are about. Hand-written tasks without an ID get the next free
+//! one on the first edit. IDs are never reused: a `<!-- surf-queue next-id: N -->` line under
+//! the title remembers the next one after tasks are removed, and a file with the
+//! same ID twice is refused until it is fixed by hand. Edits hold the repo lock, so agents
+//! sharing the queue never lose each other's changes, and the platform copies are refreshed
+//! afterwards.
+
+use anyhow::{Result, anyhow, bail};
+use colored::Colorize;
+use regex::Regex;
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use super::directives::is_fence;
+use super::repo_lock;
+use super::reporter::Reporter;
+use super::watch::{self, Affected};
+use super::{SyncOpts, atomic};
+use crate::config;
+
+/// Queue location, relative to the repo root.
+pub const QUEUE_FILE: &str = ".context/queue.md";
+
+const ID_PREFIX: &str = "Q-";
+
+/// The placeholder `surf init` writes into an empty queue; the first `add` replaces it.
+const EMPTY_QUEUE: &str = "No tasks.";
+
+static TASK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\s*)([-*+]) \[([ xX~])\] (.*)$").unwrap());
+static ID: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^Q-(\d+)\b\s*").unwrap());
+static NEXT_ID: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^<!-- surf-queue next-id: (\d+) -->$").unwrap());
+static TOKEN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(^|\s)(@[\w.-]+|![\w-]+|#[A-Za-z][\w/-]*)").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Status {
+    Todo,
+    InProgress,
+    Done,
+}
+
+impl Status {
+    fn mark(self) -> char {
+        match self {
+            Status::Todo => ' ',
+            Status::InProgress => '~',
+            Status::Done => 'x',
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Todo => "todo",
+            Status::InProgress => "in-progress",
+            Status::Done => "done",
+        }
+    }
+}
+
+/// One task item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Task {
+    pub id: Option<u32>,
+    pub status: Status,
+    /// The text without its ID, owner, priority and tags.
+    pub title: String,
+    pub owner: Option<String>,
+    pub priority: Option<String>,
+    pub tags: Vec<String>,
+    /// 0-based line index in the file.
+    line: usize,
+}
+
+impl Task {
+    pub fn label(&self) -> String {
+        match self.id {
+            Some(id) => format!("{ID_PREFIX}{id}"),
+            None => "-".to_string(),
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "id": self.id.map(|_| self.label()),
+            "status": self.status.as_str(),
+            "title": self.title,
+            "owner": self.owner,
+            "priority": self.priority,
+            "tags": self.tags,
+            "line": self.line + 1,
+        })
+    }
+}
+
+/// What `list` shows; every set field has to match.
+#[derive(Debug, Default)]
+pub struct Filter {
+    pub status: Option<Status>,
+    pub owner: Option<String>,
+    pub priority: Option<String>,
+    pub tag: Option<String>,
+}
+
+impl Filter {
+    fn matches(&self, task: &Task) -> bool {
+        self.status.is_none_or(|s| s == task.status)
+            && self
+                .owner
+                .as_deref()
+                .is_none_or(|o| task.owner.as_deref() == Some(strip_sigil(o, '@')))
+            && self
+                .priority
+                .as_deref()
+                .is_none_or(|p| task.priority.as_deref() == Some(strip_sigil(p, '!')))
+            && self
+                .tag
+                .as_deref()
+                .is_none_or(|t| task.tags.iter().any(|tag| tag == strip_sigil(t, '#')))
+    }
+}
+
+/// A task for `add`.
+#[derive(Debug, Default)]
+pub struct NewTask {
+    pub title: String,
+    pub owner: Option<String>,
+    pub priority: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// queue.md, line by line, with the tasks found in it.
+#[derive(Debug)]
+pub struct Queue {
+    lines: Vec<String>,
+    tasks: Vec<Task>,
+}
+
+impl Queue {
+    pub fn parse(content: &str) -> Self {
+        let lines: Vec<String> = content.lines().map(str::to_string).collect();
+        let mut queue = Self {
+            lines,
+            tasks: Vec::new(),
+        };
+        queue.reparse();
+        queue
+    }
+
+    pub fn tasks(&self) -> &[Task] {
+        &self.tasks
+    }
+
+    fn reparse(&mut self) {
+        let mut in_fence = false;
+        self.tasks = Vec::new();
+        for (index, line) in self.lines.iter().enumerate() {
+            if is_fence(line) {
+                in_fence = !in_fence;
+                continue;
+            }
+            if !in_fence && let Some(task) = parse_task(index, line) {
+                self.tasks.push(task);
+            }
+        }
+    }
+
+    /// Give every task without an ID the next free one.
+    pub fn number(&mut self) {
+        while let Some(line) = self.tasks.iter().find(|t| t.id.is_none()).map(|t| t.line) {
+            let id = self.next_id();
+            self.edit_text(line, |text| format!("{ID_PREFIX}{id} {text}"));
+            self.set_next_id(id + 1);
+        }
+    }
+
+    /// An error naming the first ID that two tasks share.
+    pub fn check_ids(&self) -> Result<()> {
+        let mut seen = std::collections::BTreeMap::new();
+        for task in &self.tasks {
+            let Some(id) = task.id else {
+                continue;
+            };
+            if let Some(first) = seen.insert(id, task.line) {
+                bail!(
+                    "{QUEUE_FILE} has {ID_PREFIX}{id} on lines {} and {}; give one of them a new ID",
+                    first + 1,
+                    task.line + 1
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// One past the highest ID ever given out: the recorded next ID, or one past the
+    /// highest in the file if that is more.
+    fn next_id(&self) -> u32 {
+        let highest = self.tasks.iter().filter_map(|t| t.id).max().unwrap_or(0) + 1;
+        let recorded = self
+            .lines
+            .iter()
+            .find_map(|l| NEXT_ID.captures(l.trim()).and_then(|c| c[1].parse().ok()))
+            .unwrap_or(0);
+        highest.max(recorded)
+    }
+
+    /// Record `next` as the next ID, in the marker line or a new one under the title.
+    fn set_next_id(&mut self, next: u32) {
+        let marker = format!("<!-- surf-queue next-id: {next} -->");
+        match self.lines.iter().position(|l| NEXT_ID.is_match(l.trim())) {
+            Some(index) => self.lines[index] = marker,
+            None => {
+                let under_title = self.lines.first().is_some_and(|l| l.starts_with("# "));
+                self.lines.insert(usize::from(under_title), marker);
+                self.reparse();
+            }
+        }
+    }
+
+    /// Add `task` after the last task (or in place of the empty-queue placeholder), and
+    /// return its label.
+    pub fn add(&mut self, task: &NewTask) -> Result<String> {
+        let title = task.title.split_whitespace().collect::<Vec<_>>().join(" ");
+        if title.is_empty() {
+            bail!("A task needs a title");
+        }
+        let id = self.next_id();
+        let mut line = format!("- [ ] {ID_PREFIX}{id} {title}");
+        if let Some(owner) = &task.owner {
+            line.push_str(&format!(" @{}", token(owner, '@')?));
+        }
+        if let Some(priority) = &task.priority {
+            line.push_str(&format!(" !{}", token(priority, '!')?));
+        }
+        for tag in &task.tags {
+            line.push_str(&format!(" #{}", token(tag, '#')?));
+        }
+
+        if let Some(last) = self.tasks.last().map(|t| t.line) {
+            let at = self.block_end(last);
+            self.lines.insert(at, line);
+        } else if let Some(placeholder) = self.lines.iter().position(|l| l.trim() == EMPTY_QUEUE) {
+            self.lines[placeholder] = line;
+        } else {
+            if self.lines.is_empty() {
+                self.lines.push("# Task Queue".to_string());
+            }
+            if self.lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                self.lines.push(String::new());
+            }
+            self.lines.push(line);
+        }
+        self.set_next_id(id + 1);
+        self.reparse();
+        Ok(format!("{ID_PREFIX}{id}"))
+    }
+
+    pub fn find(&self, id: &str) -> Result<&Task> {
+        let number = parse_id(id)?;
+        self.tasks
+            .iter()
+            .find(|t| t.id == Some(number))
+            .ok_or_else(|| anyhow!("No task {ID_PREFIX}{number} in {QUEUE_FILE}"))
+    }
+
+    pub fn set_status(&mut self, id: &str, status: Status) -> Result<()> {
+        let line = self.find(id)?.line;
+        let caps = TASK.captures(&self.lines[line]).expect("task lines match");
+        self.lines[line] = format!("{}{} [{}] {}", &caps[1], &caps[2], status.mark(), &caps[4]);
+        self.reparse();
+        Ok(())
+    }
+
+    /// Set the task's `@owner`, replacing the one it has.
+    pub fn assign(&mut self, id: &str, owner: &str) -> Result<()> {
+        let owner = format!("@{}", token(owner, '@')?);
+        let line = self.find(id)?.line;
+        self.edit_text(line, |text| {
+            let existing = TOKEN
+                .captures_iter(text)
+                .map(|c| c.get(2).unwrap())
+                .find(|m| m.as_str().starts_with('@'));
+            match existing {
+                Some(m) => format!("{}{owner}{}", &text[..m.start()], &text[m.end()..]),
+                None => format!("{} {owner}", text.trim_end()),
+            }
+        });
+        Ok(())
+    }
+
+    /// Remove the task along with the indented lines under it. Its ID stays taken.
+    pub fn remove(&mut self, id: &str) -> Result<Task> {
+        let task = self.find(id)?.clone();
+        let next = self.next_id();
+        let end = self.block_end(task.line);
+        self.lines.drain(task.line..end);
+        self.reparse();
+        self.set_next_id(next);
+        Ok(task)
+    }
+
+    /// The line after the task at `line` and the lines indented under it.
+    fn block_end(&self, line: usize) -> usize {
+        let indent = indent_of(&self.lines[line]);
+        let mut end = line + 1;
+        while end < self.lines.len()
+            && !self.lines[end].trim().is_empty()
+            && indent_of(&self.lines[end]) > indent
+        {
+            end += 1;
+        }
+        end
+    }
+
+    /// Replace the text (after the checkbox) of the task line at `line`.
+    fn edit_text(&mut self, line: usize, edit: impl FnOnce(&str) -> String) {
+        let caps = TASK.captures(&self.lines[line]).expect("task lines match");
+        let text = edit(&caps[4]);
+        self.lines[line] = format!("{}{} [{}] {text}", &caps[1], &caps[2], &caps[3]);
+        self.reparse();
+    }
+}
+
+impl std::fmt::Display for Queue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_task(index: usize, line: &str) -> Option<Task> {
+    let caps = TASK.captures(line)?;
+    let status = match &caps[3] {
+        " " => Status::Todo,
+        "~" => Status::InProgress,
+        _ => Status::Done,
+    };
+    let mut text = &caps[4];
+    let id = ID.captures(text).and_then(|c| {
+        let id = c[1].parse().ok();
+        text = &text[c[0].len()..];
+        id
+    });
+
+    let mut task = Task {
+        id,
+        status,
+        title: String::new(),
+        owner: None,
+        priority: None,
+        tags: Vec::new(),
+        line: index,
+    };
+    for caps in TOKEN.captures_iter(text) {
+        let token = &caps[2];
+        let value = token[1..].to_string();
+        match token.as_bytes()[0] {
+            b'@' => task.owner = Some(value),
+            b'!' => task.priority = Some(value),
+            _ => task.tags.push(value),
+        }
+    }
+    task.title = TOKEN
+        .replace_all(text, "$1")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some(task)
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+fn strip_sigil(value: &str, sigil: char) -> &str {
+    value.strip_prefix(sigil).unwrap_or(value)
+}
+
+/// `value` as an `@owner`, `!priority` or `#tag` token body, without the sigil.
+fn token(value: &str, sigil: char) -> Result<String> {
+    let value = strip_sigil(value.trim(), sigil);
+    let parsed = TOKEN
+        .captures(&format!("{sigil}{value}"))
+        .map(|c| c[2][1..].to_string());
+    match parsed {
+        Some(parsed) if parsed == value => Ok(parsed),
+        _ => bail!("`{sigil}{value}` is not a valid {sigil} token (letters, digits, `_`, `-`)"),
+    }
+}
+
+fn parse_id(id: &str) -> Result<u32> {
+    let digits = id.strip_prefix(ID_PREFIX).unwrap_or(id);
+    digits
+        .parse()
+        .map_err(|_| anyhow!("`{id}` is not a task ID (e.g. {ID_PREFIX}3)"))
+}
+
+/// Read the queue of `repo_root`, or an empty one. A queue with duplicate IDs is an error.
+pub fn load(repo_root: &Path) -> Result<Queue> {
+    let queue = match fs::read_to_string(repo_root.join(QUEUE_FILE)) {
+        Ok(content) => Queue::parse(&content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Queue::parse(""),
+        Err(e) => return Err(e.into()),
+    };
+    queue.check_ids()?;
+    Ok(queue)
+}
+
+/// Apply `change` to the queue under the repo lock, write it back, and refresh the platform
+/// copies. Returns what `change` returned, a line describing the edit; `reporter` hears
+/// about waiting for the lock.
+pub fn edit(
+    repo_root: &Path,
+    reporter: &mut dyn Reporter,
+    change: impl FnOnce(&mut Queue) -> Result<String>,
+) -> Result<String> {
+    let message = {
+        let _lock = repo_lock::for_command(repo_root, false, reporter)?;
+        let mut queue = load(repo_root)?;
+        queue.number();
+        let message = change(&mut queue)?;
+        let path = repo_root.join(QUEUE_FILE);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        atomic::write(&path, queue.to_string())?;
+        message
+    };
+
+    let config = config::load_config(repo_root)?;
+    let opts = SyncOpts {
+        local_only: true,
+        quiet: true,
+        ..Default::default()
+    };
+    let affected = Affected {
+        queue: true,
+        ..Default::default()
+    };
+    watch::run_affected(repo_root, &config, &opts, affected)?;
+    Ok(message)
+}
+
+/// `surf queue add`. Returns the line describing the edit, as do the other task edits.
+pub fn add_task(repo_root: &Path, task: &NewTask, reporter: &mut dyn Reporter) -> Result<String> {
+    edit(repo_root, reporter, |queue| {
+        let id = queue.add(task)?;
+        Ok(format!(
+            "{} {id} {}",
+            "Added".green(),
+            queue.find(&id)?.title
+        ))
+    })
+}
+
+/// `surf queue start`: mark a task in progress, taking it for `owner` if given. A task
+/// someone else is already working on is refused.
+pub fn start_task(
+    repo_root: &Path,
+    id: &str,
+    owner: Option<&str>,
+    reporter: &mut dyn Reporter,
+) -> Result<String> {
+    edit(repo_root, reporter, |queue| {
+        let task = queue.find(id)?;
+        let label = task.label();
+        match task.status {
+            Status::Done => bail!("{label} is already done"),
+            Status::InProgress
+                if owner.is_none_or(|o| task.owner.as_deref() != Some(strip_sigil(o, '@'))) =>
+            {
+                bail!(
+                    "{label} is already in progress{}",
+                    task.owner
+                        .as_ref()
+                        .map(|o| format!(" (@{o})"))
+                        .unwrap_or_default()
+                )
+            }
+            _ => {}
+        }
+        queue.set_status(id, Status::InProgress)?;
+        if let Some(owner) = owner {
+            queue.assign(id, owner)?;
+        }
+        Ok(format!(
+            "{} {label} {}",
+            "Started".yellow(),
+            queue.find(id)?.title
+        ))
+    })
+}
+
+/// `surf queue done`
+pub fn finish_task(repo_root: &Path, id: &str, reporter: &mut dyn Reporter) -> Result<String> {
+    edit(repo_root, reporter, |queue| {
+        queue.set_status(id, Status::Done)?;
+        let task = queue.find(id)?;
+        Ok(format!(
+            "{} {} {}",
+            "Done".green(),
+            task.label(),
+            task.title
+        ))
+    })
+}
+
+/// `surf queue assign`
+pub fn assign_task(
+    repo_root: &Path,
+    id: &str,
+    owner: &str,
+    reporter: &mut dyn Reporter,
+) -> Result<String> {
+    edit(repo_root, reporter, |queue| {
+        queue.assign(id, owner)?;
+        let task = queue.find(id)?;
+        Ok(format!(
+            "{} {} to @{}",
+            "Assigned".green(),
+            task.label(),
+            task.owner.as_deref().unwrap_or_default()
+        ))
+    })
+}
+
+/// `surf queue rm`
+pub fn remove_task(repo_root: &Path, id: &str, reporter: &mut dyn Reporter) -> Result<String> {
+    edit(repo_root, reporter, |queue| {
+        let task = queue.remove(id)?;
+        Ok(format!(
+            "{} {} {}",
+            "Removed".yellow(),
+            task.label(),
+            task.title
+        ))
+    })
+}
+
+/// `surf queue list`: the tasks `filter` matches, in file order.
+pub fn list_tasks(repo_root: &Path, filter: &Filter) -> Result<Vec<Task>> {
+    let queue = load(repo_root)?;
+    Ok(queue
+        .tasks()
+        .iter()
+        .filter(|t| filter.matches(t))
+        .cloned()
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUEUE: &str = "\
+# Task Queue
+
+Agents: take a task with `surf queue start`.
+
+- [ ] Q-2 Fix the login redirect @alice !high #auth #web
+  Repro: log out, then open /settings.
+- [~] Write the migration guide @bob
+- [x] Q-1 Set up CI #infra
+- not a task
+
+```
+- [ ] Q-9 example in a code block
+```
+";
+
+    #[test]
+    fn test_parse_tasks_and_tokens() {
+        let queue = Queue::parse(QUEUE);
+        let tasks = queue.tasks();
+        assert_eq!(tasks.len(), 3);
+        assert_eq!(
+            tasks[0],
+            Task {
+                id: Some(2),
+                status: Status::Todo,
+                title: "Fix the login redirect".to_string(),
+                owner: Some("alice".to_string()),
+                priority: Some("high".to_string()),
+                tags: vec!["auth".to_string(), "web".to_string()],
+                line: 4,
+            }
+        );
+        assert_eq!((tasks[1].id, tasks[1].status), (None, Status::InProgress));
+        assert_eq!(tasks[2].status, Status::Done);
+
+        let filter = Filter {
+            tag: Some("#infra".to_string()),
+            ..Default::default()
+        };
+        let found: Vec<Option<u32>> = tasks
+            .iter()
+            .filter(|t| filter.matches(t))
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(found, vec![Some(1)]);
+    }
+
+    #[test]
+    fn test_edits_keep_prose_and_touch_only_their_task() {
+        let mut queue = Queue::parse(QUEUE);
+        queue.number();
+        let id = queue
+            .add(&NewTask {
+                title: "Document  queue".to_string(),
+                owner: Some("@carol".to_string()),
+                tags: vec!["docs".to_string()],
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(id, "Q-4");
+        queue.set_status("Q-2", Status::InProgress).unwrap();
+        queue.assign("2", "dave").unwrap();
+        queue.set_status("Q-3", Status::Done).unwrap();
+        let removed = queue.remove("Q-1").unwrap();
+        assert_eq!(removed.title, "Set up CI");
+
+        assert_eq!(
+            queue.to_string(),
+            "\
+# Task Queue
+<!-- surf-queue next-id: 5 -->
+
+Agents: take a task with `surf queue start`.
+
+- [~] Q-2 Fix the login redirect @dave !high #auth #web
+  Repro: log out, then open /settings.
+- [x] Q-3 Write the migration guide @bob
+- [ ] Q-4 Document queue @carol #docs
+- not a task
+
+```
+- [ ] Q-9 example in a code block
+```
+"
+        );
+        assert!(queue.find("Q-1").is_err());
+        assert!(queue.assign("Q-2", "not valid").is_err());
+    }
+
+    #[test]
+    fn test_add_replaces_the_empty_placeholder() {
+        let mut queue = Queue::parse("# Task Queue\n\nNo tasks.\n");
+        queue
+            .add(&NewTask {
+                title: "First".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            queue.to_string(),
+            "# Task Queue\n<!-- surf-queue next-id: 2 -->\n\n- [ ] Q-1 First\n"
+        );
+
+        let mut empty = Queue::parse("");
+        empty
+            .add(&NewTask {
+                title: "First".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            empty.to_string(),
+            "# Task Queue\n<!-- surf-queue next-id: 2 -->\n\n- [ ] Q-1 First\n"
+        );
+    }
+
+    #[test]
+    fn test_ids_are_never_reused_and_duplicates_are_refused() {
+        let mut queue = Queue::parse("# Task Queue\n\n- [ ] Q-1 One\n- [ ] Q-2 Two\n");
+        queue.remove("Q-2").unwrap();
+        let new = NewTask {
+            title: "Three".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(queue.add(&new).unwrap(), "Q-3");
+        queue.remove("Q-3").unwrap();
+
+        let mut reloaded = Queue::parse(&queue.to_string());
+        assert_eq!(reloaded.add(&new).unwrap(), "Q-4");
+
+        let duplicated = Queue::parse("- [ ] Q-1 One\n- [x] Q-1 Again\n");
+        let err = duplicated.check_ids().unwrap_err().to_string();
+        assert!(err.contains("Q-1 on lines 1 and 2"), "{err}");
+    }
+}
@@ -0,0 +1,416 @@
+//! Paths that context documents point at.
+//!
+//! Two kinds count: backticked paths (`` `src/main.rs` ``, as in a Key Files table), read
+//! from the repo root, and relative markdown links (`[setup](../guides/setup.md)`), read
+//! from the linking file's directory. Fenced code blocks are skipped; their paths are
+//! examples more often than pointers. SurfDoc front matter `related:` lists are read
+//! separately, by `related`. Not every backticked `a/b` is a path (`owner/repo`,
+//! `application/json`); `is_checkable` decides which missing ones are worth reporting.
+
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::LazyLock;
+
+use super::directives::is_fence;
+use super::link_farms;
+use super::lockfile::LOCK_FILE;
+use super::repo_lock::REPO_LOCK;
+use super::trash::TRASH_DIR;
+use crate::config::SurfConfig;
+
+static BACKTICKED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"`([^`\s]+)`").unwrap());
+static MARKDOWN_LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\[[^\]]*\]\(<?([^)\s>]+)>?(?:\s+"[^"]*")?\)"#).unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefKind {
+    Backticked,
+    Link,
+    /// An entry in the front matter `related:` list.
+    Related,
+}
+
+/// One path a document points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathRef {
+    pub kind: RefKind,
+    pub line: usize,
+    /// The path as written, without `#anchor` or `:line` suffixes.
+    pub raw: String,
+    /// Repo-relative, with `.` and `..` resolved. Starts with `..` if it leaves the repo.
+    pub target: PathBuf,
+}
+
+/// Every path `content` (the file at repo-relative `file`) points at, in line order.
+pub fn extract(file: &Path, content: &str) -> Vec<PathRef> {
+    let dir = file.parent().unwrap_or(Path::new(""));
+    let mut refs = Vec::new();
+    let mut in_fence = false;
+    for (line_num, line) in content.lines().enumerate() {
+        if is_fence(line) {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        for caps in MARKDOWN_LINK.captures_iter(line) {
+            if let Some(raw) = link_path(&caps[1]) {
+                let base = if raw.starts_with('/') {
+                    Path::new("")
+                } else {
+                    dir
+                };
+                refs.push(PathRef {
+                    kind: RefKind::Link,
+                    line: line_num + 1,
+                    target: normalize(&base.join(raw.trim_start_matches('/'))),
+                    raw,
+                });
+            }
+        }
+        // Link targets in backticks are links, not code spans.
+        let without_links = MARKDOWN_LINK.replace_all(line, "");
+        for caps in BACKTICKED.captures_iter(&without_links) {
+            if let Some(raw) = backticked_path(&caps[1]) {
+                let base = if raw.starts_with("./") || raw.starts_with("../") {
+                    dir
+                } else {
+                    Path::new("")
+                };
+                refs.push(PathRef {
+                    kind: RefKind::Backticked,
+                    line: line_num + 1,
+                    target: normalize(&base.join(&raw)),
+                    raw,
+                });
+            }
+        }
+    }
+    refs
+}
+
+/// The entries of the front matter `related:` list in `content`, inline
+/// (`related: [a.md, ../guides/b.md]`) or one `- item` per line. They resolve like links:
+/// from the file's directory, or from the root with a leading `/`.
+pub fn related(file: &Path, content: &str) -> Vec<PathRef> {
+    let dir = file.parent().unwrap_or(Path::new(""));
+    let mut lines = content.lines().enumerate();
+    if lines.next().map(|(_, line)| line.trim_end()) != Some("---") {
+        return Vec::new();
+    }
+
+    let mut refs = Vec::new();
+    let mut in_list = false;
+    for (line_num, line) in lines {
+        let trimmed = line.trim();
+        if trimmed == "---" || trimmed == "..." {
+            break;
+        }
+        let items: Vec<&str> = if let Some(value) = trimmed.strip_prefix("related:") {
+            let value = value.trim();
+            in_list = value.is_empty();
+            value
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .split(',')
+                .collect()
+        } else if in_list && let Some(item) = trimmed.strip_prefix("- ") {
+            vec![item]
+        } else {
+            in_list = false;
+            continue;
+        };
+        for item in items {
+            if let Some(raw) = link_path(item.trim().trim_matches(['"', '\''])) {
+                let base = if raw.starts_with('/') {
+                    Path::new("")
+                } else {
+                    dir
+                };
+                refs.push(PathRef {
+                    kind: RefKind::Related,
+                    line: line_num + 1,
+                    target: normalize(&base.join(raw.trim_start_matches('/'))),
+                    raw,
+                });
+            }
+        }
+    }
+    refs
+}
+
+/// The local path a link target names, or `None` for URLs, anchors and mail links.
+fn link_path(target: &str) -> Option<String> {
+    if target.contains("://") || target.starts_with('#') || target.starts_with("mailto:") {
+        return None;
+    }
+    let path = target.split(['#', '?']).next().unwrap_or_default();
+    let path = path.replace("%20", " ");
+    (!path.is_empty()).then_some(path)
+}
+
+/// The path a code span holds, if it looks like one: a relative path with a `/`, made of
+/// path characters only (no globs, placeholders, `::` or calls), minus a `:line` suffix.
+fn backticked_path(span: &str) -> Option<String> {
+    let path = match span.split_once(':') {
+        Some((path, rest))
+            if rest
+                .split(':')
+                .all(|n| n.chars().all(|c| c.is_ascii_digit())) =>
+        {
+            path
+        }
+        Some(_) => return None,
+        None => span,
+    };
+    let is_path_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '/');
+    let looks_like_path = path.contains('/')
+        && !path.starts_with('/')
+        && path.chars().any(char::is_alphanumeric)
+        && path.chars().all(is_path_char);
+    looks_like_path.then(|| path.to_string())
+}
+
+/// Whether a missing `path_ref` should be reported. A backticked path read from the root
+/// only counts when its first segment exists in the repo, which rules out `owner/repo`,
+/// `application/json` and `feature/x`. Surf's own bookkeeping files come and go and never
+/// count.
+pub fn is_checkable(repo_root: &Path, path_ref: &PathRef) -> bool {
+    let target = &path_ref.target;
+    if target == Path::new(REPO_LOCK)
+        || target == Path::new(LOCK_FILE)
+        || target.starts_with(TRASH_DIR)
+    {
+        return false;
+    }
+    let from_root = !path_ref.raw.starts_with("./") && !path_ref.raw.starts_with("../");
+    if path_ref.kind == RefKind::Backticked && from_root {
+        return target
+            .components()
+            .next()
+            .is_some_and(|first| repo_root.join(first).exists());
+    }
+    true
+}
+
+/// Resolve `.` and `..` without touching the filesystem.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut out: Vec<Component> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(out.last(), Some(Component::Normal(_))) => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out.iter().collect()
+}
+
+/// Every file and directory in the repo by name, for suggesting where a missing path went.
+/// Skips `.git`, build output, the trash and platform link farms (they mirror `.context/`).
+pub struct PathIndex {
+    by_name: BTreeMap<String, Vec<String>>,
+}
+
+impl PathIndex {
+    pub fn build(repo_root: &Path, config: &SurfConfig) -> Self {
+        let mut skip: Vec<PathBuf> = [".git", "target", "node_modules", TRASH_DIR]
+            .iter()
+            .map(|p| repo_root.join(p))
+            .collect();
+        skip.extend(
+            link_farms(config)
+                .into_iter()
+                .map(|farm| repo_root.join(farm.root)),
+        );
+
+        let mut by_name: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for entry in walkdir::WalkDir::new(repo_root)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| !skip.iter().any(|s| e.path() == s))
+            .filter_map(|e| e.ok())
+            .filter(|e| !e.path_is_symlink())
+        {
+            if let Ok(rel) = entry.path().strip_prefix(repo_root) {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                by_name
+                    .entry(name)
+                    .or_default()
+                    .push(rel.to_string_lossy().replace('\\', "/"));
+            }
+        }
+        Self { by_name }
+    }
+
+    /// The existing path most likely meant by the missing `target`: one with the same name
+    /// (sharing the most trailing directories with `target`), else the closest name by edit
+    /// distance.
+    pub fn suggest(&self, target: &Path) -> Option<String> {
+        let name = target.file_name()?.to_string_lossy();
+        let wanted: Vec<String> = target
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let shared_suffix = |path: &str| {
+            path.rsplit('/')
+                .zip(wanted.iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count()
+        };
+
+        if let Some(paths) = self.by_name.get(name.as_ref()) {
+            return paths
+                .iter()
+                .max_by_key(|p| (shared_suffix(p), std::cmp::Reverse(p.len())))
+                .cloned();
+        }
+
+        let limit = (name.chars().count() / 4).max(1);
+        self.by_name
+            .iter()
+            .map(|(candidate, paths)| {
+                (
+                    edit_distance(&name.to_lowercase(), &candidate.to_lowercase()),
+                    paths,
+                )
+            })
+            .filter(|(distance, _)| *distance <= limit)
+            .min_by_key(|(distance, _)| *distance)
+            .and_then(|(_, paths)| paths.first().cloned())
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let next = (diagonal + usize::from(ca != *cb))
+                .min(row[j] + 1)
+                .min(row[j + 1] + 1);
+            diagonal = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestDir;
+    use std::fs;
+
+    #[test]
+    fn test_extract_backticked_paths_and_links() {
+        let content = "\
+| `src/main.rs` | entry |
+| `src/sync/mod.rs:42` | `cargo build` `serde_json::from_str` `*.md` `<path>/x` |
+See [setup](../guides/setup.md#install), [site](https://example.com) and [top](#top).
+Root link [ctx](/CONTEXT.md) and `./sibling.md`.
+```
+`src/in_code.rs` [x](in_code.md)
+```
+";
+        let refs = extract(Path::new(".context/docs/a.md"), content);
+        let got: Vec<(usize, &str, PathBuf)> = refs
+            .iter()
+            .map(|r| (r.line, r.raw.as_str(), r.target.clone()))
+            .collect();
+        assert_eq!(
+            got,
+            vec![
+                (1, "src/main.rs", PathBuf::from("src/main.rs")),
+                (2, "src/sync/mod.rs", PathBuf::from("src/sync/mod.rs")),
+                (
+                    3,
+                    "../guides/setup.md",
+                    PathBuf::from(".context/guides/setup.md")
+                ),
+                (4, "/CONTEXT.md", PathBuf::from("CONTEXT.md")),
+                (4, "./sibling.md", PathBuf::from(".context/docs/sibling.md")),
+            ]
+        );
+        assert_eq!(refs[2].kind, RefKind::Link);
+    }
+
+    #[test]
+    fn test_related_front_matter_inline_and_block() {
+        let file = Path::new(".context/docs/api.md");
+        let inline = "---\ntitle: API\nrelated: [auth.md, \"../guides/setup.md\"]\n---\n";
+        let targets: Vec<PathBuf> = related(file, inline)
+            .into_iter()
+            .map(|r| r.target)
+            .collect();
+        assert_eq!(
+            targets,
+            vec![
+                PathBuf::from(".context/docs/auth.md"),
+                PathBuf::from(".context/guides/setup.md")
+            ]
+        );
+
+        let block = "---\nrelated:\n  - /CONTEXT.md\ntags: [x.md]\n---\nrelated: [body.md]\n";
+        let refs = related(file, block);
+        assert_eq!(refs.len(), 1);
+        assert_eq!(
+            (refs[0].line, refs[0].target.as_path()),
+            (3, Path::new("CONTEXT.md"))
+        );
+        assert!(related(file, "related: [a.md]\n").is_empty());
+    }
+
+    #[test]
+    fn test_non_paths_and_bookkeeping_are_not_checked() {
+        let repo = TestDir::new("surfcontext-test-references-checkable");
+        fs::create_dir_all(repo.join("src")).unwrap();
+        let content = "\
+Clone `owner/repo`, send `application/json`, branch `feature/x`.
+Locked by `.context/.surf.lock`, saved to `.context/.surf-trash/` and `.context/surf-lock.json`.
+Missing: `src/gone.rs`, `./gone.md`, [gone](gone.md).
+";
+        let checked: Vec<String> = extract(Path::new(".context/docs/a.md"), content)
+            .into_iter()
+            .filter(|r| is_checkable(&repo, r))
+            .map(|r| r.raw)
+            .collect();
+        assert_eq!(checked, vec!["gone.md", "src/gone.rs", "./gone.md"]);
+    }
+
+    #[test]
+    fn test_suggest_same_name_then_closest_name() {
+        let repo = TestDir::new("surfcontext-test-references-index");
+        for path in [
+            "src/report.rs",
+            "src/sync/report.rs",
+            ".context/guides/set-up.md",
+        ] {
+            fs::create_dir_all(repo.join(path).parent().unwrap()).unwrap();
+            fs::write(repo.join(path), "").unwrap();
+        }
+        let config: SurfConfig = serde_json::from_str("{}").unwrap();
+        let index = PathIndex::build(&repo, &config);
+
+        assert_eq!(
+            index
+                .suggest(Path::new("src/old/sync/report.rs"))
+                .as_deref(),
+            Some("src/sync/report.rs")
+        );
+        assert_eq!(
+            index
+                .suggest(Path::new(".context/guides/setup.md"))
+                .as_deref(),
+            Some(".context/guides/set-up.md")
+        );
+        assert_eq!(index.suggest(Path::new("docs/unrelated.md")), None);
+    }
+}
@@ -0,0 +1,242 @@
+//! `.context/.surf.lock`: at most one writing sync per repo at a time.
+//!
+//! Several agents in one repo can start `surf sync` together. The lock is an OS advisory
+//! lock (`File::try_lock`) on a file that is never deleted, so there is no window in which
+//! two runs can each believe they removed or created it. The OS drops the lock when its
+//! holder exits, crash or `kill -9` included, so one bad run never wedges the repo. The
+//! file records who holds it, for the waiting message, and is empty while the lock is free.
+//! It is listed in `.git/info/exclude`, so it stays out of git without touching the repo's
+//! own files, which matters for cross-repo targets surf does not otherwise manage.
+
+use anyhow::{Result, bail};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, TryLockError};
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::reporter::Reporter;
+
+/// Lock location, relative to the repo root.
+pub const REPO_LOCK: &str = ".context/.surf.lock";
+
+/// How long a run waits for another one to finish.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+const POLL: Duration = Duration::from_millis(100);
+
+/// Who holds a lock, as written into the lock file.
+#[derive(Debug, Serialize, Deserialize)]
+struct Holder {
+    pid: u32,
+    host: String,
+    /// Unix seconds.
+    since: u64,
+}
+
+/// A held repo lock, released on drop.
+#[derive(Debug)]
+pub struct RepoLock {
+    file: File,
+}
+
+impl RepoLock {
+    /// Take `repo_root`'s lock, waiting up to `timeout` for the current holder to finish.
+    /// `waiting` is called once, with a description of the holder, if the lock is busy.
+    pub fn acquire(repo_root: &Path, timeout: Duration, mut waiting: impl FnMut(&str)) -> Result<Self> {
+        let path = repo_root.join(REPO_LOCK);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Best effort: a repo we cannot exclude it in can still be synced.
+        let _ = exclude_from_git(repo_root);
+        let deadline = Instant::now() + timeout;
+        let mut announced = false;
+        loop {
+            let mut file = fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)?;
+            match file.try_lock() {
+                Ok(()) => {
+                    let holder = Holder {
+                        pid: std::process::id(),
+                        host: hostname(),
+                        since: now(),
+                    };
+                    file.set_len(0)?;
+                    file.write_all(serde_json::to_string(&holder)?.as_bytes())?;
+                    return Ok(Self { file });
+                }
+                Err(TryLockError::WouldBlock) => {}
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            }
+
+            let current = fs::read_to_string(&path).ok().and_then(|s| serde_json::from_str(&s).ok());
+            let who = describe(current.as_ref());
+            if Instant::now() >= deadline {
+                bail!(
+                    "{} is held by {who}; gave up after {}s",
+                    path.display(),
+                    timeout.as_secs()
+                );
+            }
+            if !announced {
+                waiting(&who);
+                announced = true;
+            }
+            std::thread::sleep(POLL);
+        }
+    }
+}
+
+/// Take `repo_root`'s lock for a command that writes, telling `reporter` if it has to wait.
+/// Dry runs write nothing and take no lock.
+pub fn for_command(
+    repo_root: &Path,
+    dry_run: bool,
+    reporter: &mut dyn Reporter,
+) -> Result<Option<RepoLock>> {
+    if dry_run {
+        return Ok(None);
+    }
+    let lock = RepoLock::acquire(repo_root, DEFAULT_TIMEOUT, |who| {
+        reporter.progress(&format!(
+            "{} {who} is syncing this repo, waiting...",
+            "[Lock]".dimmed()
+        ));
+    })?;
+    Ok(Some(lock))
+}
+
+impl Drop for RepoLock {
+    fn drop(&mut self) {
+        // Forget the holder; closing the file releases the OS lock. The file itself stays:
+        // deleting it would let a waiter lock the old file while a newcomer locks a new one.
+        let _ = self.file.set_len(0);
+    }
+}
+
+/// Add the lock file to `.git/info/exclude` unless it is already there. Repos that are not
+/// git checkouts, or whose `.git` is a worktree file, are left alone.
+fn exclude_from_git(repo_root: &Path) -> Result<()> {
+    let git_dir = repo_root.join(".git");
+    if !git_dir.is_dir() {
+        return Ok(());
+    }
+    let exclude = git_dir.join("info/exclude");
+    let pattern = format!("/{REPO_LOCK}");
+    let current = fs::read_to_string(&exclude).unwrap_or_default();
+    if current.lines().any(|line| line.trim() == pattern) {
+        return Ok(());
+    }
+    fs::create_dir_all(git_dir.join("info"))?;
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&exclude)?;
+    if !current.is_empty() && !current.ends_with('\n') {
+        writeln!(file)?;
+    }
+    writeln!(file, "{pattern}")?;
+    Ok(())
+}
+
+fn describe(holder: Option<&Holder>) -> String {
+    match holder {
+        Some(h) => format!(
+            "pid {} on {} (for {}s)",
+            h.pid,
+            if h.host.is_empty() { "unknown host" } else { &h.host },
+            now().saturating_sub(h.since)
+        ),
+        None => "another sync".to_string(),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .or_else(|_| fs::read_to_string("/etc/hostname"))
+        .map(|h| h.trim().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestDir;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_lock_is_exclusive_and_released_on_drop() {
+        let repo = TestDir::new("surfcontext-test-repo-lock");
+        let lock = RepoLock::acquire(&repo, Duration::ZERO, |_| {}).unwrap();
+        assert!(repo.join(REPO_LOCK).exists());
+
+        let mut waited = Vec::new();
+        let err = RepoLock::acquire(&repo, Duration::ZERO, |who| waited.push(who.to_string()))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains(&format!("pid {}", std::process::id())), "{err}");
+        assert!(waited.is_empty());
+
+        drop(lock);
+        RepoLock::acquire(&repo, Duration::ZERO, |_| {}).unwrap();
+    }
+
+    #[test]
+    fn test_lock_file_is_excluded_from_git_once() {
+        let repo = TestDir::new("surfcontext-test-repo-lock-exclude");
+        fs::create_dir_all(repo.join(".git/info")).unwrap();
+        fs::write(repo.join(".git/info/exclude"), "# local excludes").unwrap();
+
+        drop(RepoLock::acquire(&repo, Duration::ZERO, |_| {}).unwrap());
+        drop(RepoLock::acquire(&repo, Duration::ZERO, |_| {}).unwrap());
+        assert_eq!(
+            fs::read_to_string(repo.join(".git/info/exclude")).unwrap(),
+            "# local excludes\n/.context/.surf.lock\n"
+        );
+
+        let plain = TestDir::new("surfcontext-test-repo-lock-no-git");
+        RepoLock::acquire(&plain, Duration::ZERO, |_| {}).unwrap();
+        assert!(!plain.join(".git").exists());
+    }
+
+    #[test]
+    fn test_leftover_lock_file_does_not_block() {
+        let repo = TestDir::new("surfcontext-test-repo-lock-leftover");
+        fs::create_dir_all(repo.join(".context")).unwrap();
+        fs::write(repo.join(REPO_LOCK), r#"{"pid":1,"host":"gone","since":0}"#).unwrap();
+        RepoLock::acquire(&repo, Duration::ZERO, |_| {}).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_acquires_never_overlap() {
+        let repo = TestDir::new("surfcontext-test-repo-lock-concurrent");
+        let inside = AtomicUsize::new(0);
+        let entered = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    let _lock = RepoLock::acquire(&repo, Duration::from_secs(30), |_| {}).unwrap();
+                    assert_eq!(inside.fetch_add(1, Ordering::SeqCst), 0);
+                    entered.fetch_add(1, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(20));
+                    inside.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        assert_eq!(entered.load(Ordering::SeqCst), 8);
+    }
+}
@@ -0,0 +1,705 @@
+//! What a sync run did, as data.
+//!
+//! Every stage records one `Action` per file it creates, updates or removes, and one coded
+//! `Diagnostic` per warning or error. The colored terminal summary, `--format json` and
+//! `--format ndjson` are all renderings of the same `SyncReport`, so bots and dashboards
+//! never have to scrape the progress output.
+
+use anyhow::{Result, bail};
+use colored::Colorize;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use super::lockfile::LockEntry;
+use super::reporter::Reporter;
+use super::trash::{self, Trash};
+
+/// How a sync report is printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Progress output and a colored summary.
+    Text,
+    /// One JSON document once the run is over.
+    Json,
+    /// One JSON object per line: each action, each diagnostic, then the summary.
+    Ndjson,
+}
+
+/// Pipeline stage an action or diagnostic came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Stage {
+    Setup,
+    Links,
+    Generate,
+    Queue,
+    Sweep,
+    Audit,
+    CrossRepo,
+    Lockfile,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ActionKind {
+    Create,
+    Update,
+    Remove,
+    /// Orphaned content moved into `.context/`.
+    Migrate,
+    /// Orphaned file replaced with a pointer to `.context/`.
+    Redirect,
+}
+
+/// One file or link a run changed (or, in dry-run mode, would change).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Action {
+    pub kind: ActionKind,
+    pub stage: Stage,
+    /// Path relative to the repo root.
+    pub path: String,
+    /// SHA-256 of the file before the action, when it was a file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    /// SHA-256 of the file after the action, when it is a file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+    pub reason: String,
+}
+
+impl Action {
+    pub fn new(
+        stage: Stage,
+        kind: ActionKind,
+        path: impl Into<String>,
+        reason: impl Into<String>,
+    ) -> Self {
+        Self {
+            kind,
+            stage,
+            path: path.into(),
+            before: None,
+            after: None,
+            reason: reason.into(),
+        }
+    }
+
+    pub fn hashes(mut self, before: Option<String>, after: Option<String>) -> Self {
+        self.before = before;
+        self.after = after;
+        self
+    }
+
+    /// Rewriting the lockfile is bookkeeping, not an output.
+    pub fn is_bookkeeping(&self) -> bool {
+        self.stage == Stage::Lockfile
+            && matches!(self.kind, ActionKind::Create | ActionKind::Update)
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.reason)
+    }
+}
+
+/// Stable identifier for each kind of warning or error sync reports. The hundreds digit is
+/// the area: 1 sources, 2 platform dirs, 3 links and platforms, 4 outputs, 5 cross-repo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Code {
+    /// A `.context/` file or CONTEXT.md points at a platform-dir path.
+    PlatformPathReference,
+    /// A `.context/` file or CONTEXT.md points at a path that does not exist.
+    BrokenReference,
+    /// Malformed or unknown `surf:` directive.
+    Directive,
+    /// Unmanaged file in a platform dir.
+    OrphanFile,
+    /// Unmanaged directory in a platform dir.
+    OrphanDir,
+    /// Migrated content diverged from `.context/`; both were kept.
+    MigrationConflict,
+    /// A file sits where a platform link should go.
+    LinkBlocked,
+    /// A configured platform is not one surf knows. An error by default, which stops the run
+    /// before anything is written; demoted, the platform is skipped.
+    UnknownPlatform,
+    /// A generated file was edited by hand and left alone.
+    HandEdited,
+    /// A stale output was modified since surf wrote it and left alone.
+    StaleOutputModified,
+    /// A cross-repo target repo does not exist.
+    CrossRepoMissing,
+    /// A cross-repo target file is newer than its source and was skipped.
+    CrossRepoTargetNewer,
+    /// A cross-repo target repo stayed locked by another sync and was skipped.
+    CrossRepoLocked,
+}
+
+impl Code {
+    pub const ALL: [Code; 13] = [
+        Code::PlatformPathReference,
+        Code::BrokenReference,
+        Code::Directive,
+        Code::OrphanFile,
+        Code::OrphanDir,
+        Code::MigrationConflict,
+        Code::LinkBlocked,
+        Code::UnknownPlatform,
+        Code::HandEdited,
+        Code::StaleOutputModified,
+        Code::CrossRepoMissing,
+        Code::CrossRepoTargetNewer,
+        Code::CrossRepoLocked,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Code::PlatformPathReference => "SC101",
+            Code::BrokenReference => "SC102",
+            Code::Directive => "SC103",
+            Code::OrphanFile => "SC201",
+            Code::OrphanDir => "SC202",
+            Code::MigrationConflict => "SC203",
+            Code::LinkBlocked => "SC301",
+            Code::UnknownPlatform => "SC302",
+            Code::HandEdited => "SC401",
+            Code::StaleOutputModified => "SC402",
+            Code::CrossRepoMissing => "SC501",
+            Code::CrossRepoTargetNewer => "SC502",
+            Code::CrossRepoLocked => "SC503",
+        }
+    }
+
+    /// Readable alias for the code, accepted wherever the code is.
+    pub fn name(self) -> &'static str {
+        match self {
+            Code::PlatformPathReference => "path-reference",
+            Code::BrokenReference => "broken-reference",
+            Code::Directive => "directive",
+            Code::OrphanFile => "orphan-file",
+            Code::OrphanDir => "orphan-dir",
+            Code::MigrationConflict => "migration-conflict",
+            Code::LinkBlocked => "link-blocked",
+            Code::UnknownPlatform => "unknown-platform",
+            Code::HandEdited => "hand-edited",
+            Code::StaleOutputModified => "stale-output-modified",
+            Code::CrossRepoMissing => "cross-repo-missing",
+            Code::CrossRepoTargetNewer => "cross-repo-target-newer",
+            Code::CrossRepoLocked => "cross-repo-locked",
+        }
+    }
+
+    /// Look a code up by `SC...` code or by name.
+    pub fn parse(s: &str) -> Option<Code> {
+        Code::ALL
+            .into_iter()
+            .find(|code| code.as_str().eq_ignore_ascii_case(s) || code.name() == s)
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Code {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// How much a diagnostic matters. Each call site picks a default, and surfcontext.json's
+/// `diagnostics` map can promote, demote or switch off any code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// Per-code overrides from surfcontext.json; `None` suppresses the code.
+pub type SeverityOverrides = BTreeMap<Code, Option<Severity>>;
+
+/// Parse surfcontext.json's `diagnostics` map, rejecting unknown codes and levels.
+pub fn severity_overrides(config: &HashMap<String, String>) -> Result<SeverityOverrides> {
+    let mut overrides = SeverityOverrides::new();
+    for (key, level) in config {
+        let Some(code) = Code::parse(key) else {
+            bail!("Unknown diagnostic code `{key}` in surfcontext.json `diagnostics`");
+        };
+        let severity = match level.as_str() {
+            "error" => Some(Severity::Error),
+            "warning" => Some(Severity::Warning),
+            "off" => None,
+            _ => bail!(
+                "Invalid level `{level}` for `{key}` in surfcontext.json `diagnostics` \
+                 (expected \"error\", \"warning\" or \"off\")"
+            ),
+        };
+        overrides.insert(code, severity);
+    }
+    Ok(overrides)
+}
+
+/// A coded warning or error.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub code: Code,
+    /// Set when the diagnostic is recorded, from the call site's default and the overrides.
+    pub severity: Severity,
+    pub stage: Stage,
+    /// File the diagnostic is about, relative to the repo root.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(code: Code, stage: Stage, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            severity: Severity::Warning,
+            stage,
+            path: None,
+            message: message.into(),
+        }
+    }
+
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.code, self.message)
+    }
+}
+
+/// A platform link that now resolves somewhere else.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Retarget {
+    pub link: String,
+    /// What was there before: the old link target, or a description such as `directory`.
+    /// `None` when nothing was.
+    pub from: Option<String>,
+    pub to: String,
+}
+
+/// Aggregate report from the sync pipeline.
+#[derive(Default)]
+pub struct SyncReport {
+    /// Everything this run changed, or in dry-run mode would change.
+    pub actions: Vec<Action>,
+    pub warnings: Vec<Diagnostic>,
+    /// Problems sync cannot fix on its own (e.g. a file where a link should be).
+    pub errors: Vec<Diagnostic>,
+    pub unchanged: usize,
+    pub cross_repo_unchanged: usize,
+    pub cross_repo_skipped: usize,
+    /// Snapshots of everything this run removed or overwrote.
+    pub trash: Trash,
+    /// Everything this run produced, keyed by path relative to the repo root.
+    pub outputs: BTreeMap<String, LockEntry>,
+    /// Links this run created or repointed, for `surf sync --diff`.
+    pub retargets: Vec<Retarget>,
+    /// Diagnostics dropped because their code is `"off"` in surfcontext.json.
+    pub suppressed: usize,
+    pub(crate) overrides: SeverityOverrides,
+    /// Where progress goes while the run is going; `None` is silent.
+    pub(crate) reporter: Option<Box<dyn Reporter>>,
+}
+
+/// Per-kind action counts, as shown in the summary.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Summary {
+    pub created: usize,
+    pub updated: usize,
+    pub redirected: usize,
+    pub migrated: usize,
+    /// Stale outputs from the previous run that were deleted.
+    pub removed: usize,
+    pub unchanged: usize,
+    pub cross_repo_new: usize,
+    pub cross_repo_updated: usize,
+    pub cross_repo_unchanged: usize,
+    pub cross_repo_skipped: usize,
+    pub warnings: usize,
+    pub errors: usize,
+    pub suppressed: usize,
+}
+
+impl SyncReport {
+    /// A report for a run that sends its progress to `reporter`.
+    pub fn with_reporter(trash: Trash, reporter: Box<dyn Reporter>) -> Self {
+        Self {
+            trash,
+            reporter: Some(reporter),
+            ..Default::default()
+        }
+    }
+
+    pub fn record(&mut self, action: Action) {
+        if let Some(reporter) = &mut self.reporter {
+            reporter.action(&action);
+        }
+        self.actions.push(action);
+    }
+
+    /// Apply surfcontext.json's `diagnostics` overrides to everything recorded from now on.
+    pub fn set_overrides(&mut self, overrides: SeverityOverrides) {
+        self.overrides = overrides;
+    }
+
+    /// Record a warning, or whatever the overrides make of its code.
+    pub fn warn(&mut self, diagnostic: Diagnostic) {
+        self.diagnose(diagnostic, Severity::Warning);
+    }
+
+    /// Record an error, or whatever the overrides make of its code.
+    pub fn error(&mut self, diagnostic: Diagnostic) {
+        self.diagnose(diagnostic, Severity::Error);
+    }
+
+    fn diagnose(&mut self, mut diagnostic: Diagnostic, default: Severity) {
+        diagnostic.severity = match self.overrides.get(&diagnostic.code) {
+            Some(Some(severity)) => *severity,
+            Some(None) => {
+                self.suppressed += 1;
+                return;
+            }
+            None => default,
+        };
+        match diagnostic.severity {
+            Severity::Warning => {
+                if let Some(reporter) = &mut self.reporter {
+                    reporter.warning(&diagnostic);
+                }
+                self.warnings.push(diagnostic);
+            }
+            Severity::Error => {
+                if let Some(reporter) = &mut self.reporter {
+                    reporter.error(&diagnostic);
+                }
+                self.errors.push(diagnostic);
+            }
+        }
+    }
+
+    /// Whether the run should fail: any error, or with `strict` any warning too.
+    pub fn failed(&self, strict: bool) -> bool {
+        !self.errors.is_empty() || (strict && !self.warnings.is_empty())
+    }
+
+    pub(crate) fn stage(&mut self, stage: Stage, title: &str) {
+        if let Some(reporter) = &mut self.reporter {
+            reporter.stage(stage, title);
+        }
+    }
+
+    pub(crate) fn progress(&mut self, line: impl AsRef<str>) {
+        if let Some(reporter) = &mut self.reporter {
+            reporter.progress(line.as_ref());
+        }
+    }
+
+    pub fn summary(&self) -> Summary {
+        let mut summary = Summary {
+            unchanged: self.unchanged,
+            cross_repo_unchanged: self.cross_repo_unchanged,
+            cross_repo_skipped: self.cross_repo_skipped,
+            warnings: self.warnings.len(),
+            errors: self.errors.len(),
+            suppressed: self.suppressed,
+            ..Default::default()
+        };
+        for action in self.actions.iter().filter(|a| !a.is_bookkeeping()) {
+            let counter = match (action.stage, action.kind) {
+                (Stage::CrossRepo, ActionKind::Create) => &mut summary.cross_repo_new,
+                (Stage::CrossRepo, ActionKind::Update) => &mut summary.cross_repo_updated,
+                (_, ActionKind::Create) => &mut summary.created,
+                (_, ActionKind::Update) => &mut summary.updated,
+                (_, ActionKind::Redirect) => &mut summary.redirected,
+                (_, ActionKind::Migrate) => &mut summary.migrated,
+                (_, ActionKind::Remove) => &mut summary.removed,
+            };
+            *counter += 1;
+        }
+        summary
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "summary": self.summary(),
+            "actions": self.actions,
+            "warnings": self.warnings,
+            "errors": self.errors,
+            "trash": self.trash_json(),
+        })
+    }
+
+    /// One object per action and diagnostic, tagged with `type`, then the summary.
+    pub fn to_ndjson(&self) -> Vec<String> {
+        let tagged = |kind: &str, value: serde_json::Value| {
+            let mut value = value;
+            value["type"] = json!(kind);
+            value.to_string()
+        };
+        let mut lines = Vec::new();
+        for action in &self.actions {
+            lines.push(tagged("action", json!(action)));
+        }
+        for warning in &self.warnings {
+            lines.push(tagged("warning", json!(warning)));
+        }
+        for error in &self.errors {
+            lines.push(tagged("error", json!(error)));
+        }
+        let mut summary = json!(self.summary());
+        summary["trash"] = self.trash_json();
+        lines.push(tagged("summary", summary));
+        lines
+    }
+
+    fn trash_json(&self) -> serde_json::Value {
+        match self.trash.saved() {
+            Some((id, count)) => json!({ "run": id, "items": count }),
+            None => serde_json::Value::Null,
+        }
+    }
+
+    /// Send the `--diff` list of links whose target changed to the reporter.
+    pub(crate) fn report_retargets(&mut self) {
+        if self.retargets.is_empty() {
+            return;
+        }
+        self.stage(Stage::Links, "[Symlinks] Retargets:");
+        for r in self.retargets.clone() {
+            self.progress(format!(
+                "  {} {} {} {}",
+                r.link,
+                r.from.as_deref().unwrap_or("(none)").red(),
+                "->".dimmed(),
+                r.to.green()
+            ));
+        }
+    }
+
+    /// The colored text summary `surf sync` prints after its progress output, one line each.
+    pub fn summary_lines(&self) -> Vec<String> {
+        let summary = self.summary();
+        let mut lines = vec![
+            String::new(),
+            "========================================"
+                .dimmed()
+                .to_string(),
+            "Sync complete!".green().bold().to_string(),
+            String::new(),
+        ];
+
+        let mut parts = Vec::new();
+        if summary.created > 0 {
+            parts.push(format!("{} created", summary.created));
+        }
+        if summary.updated > 0 {
+            parts.push(format!("{} updated", summary.updated));
+        }
+        if summary.redirected > 0 {
+            parts.push(format!("{} redirected", summary.redirected));
+        }
+        if summary.migrated > 0 {
+            parts.push(format!("{} migrated", summary.migrated));
+        }
+        if summary.removed > 0 {
+            parts.push(format!("{} removed", summary.removed));
+        }
+        if summary.unchanged > 0 {
+            parts.push(format!("{} unchanged", summary.unchanged));
+        }
+
+        if !parts.is_empty() {
+            lines.push(format!("Local: {}", parts.join(", ")));
+        }
+
+        let mut cross_parts = Vec::new();
+        if summary.cross_repo_new > 0 {
+            cross_parts.push(format!("{} new", summary.cross_repo_new));
+        }
+        if summary.cross_repo_updated > 0 {
+            cross_parts.push(format!("{} updated", summary.cross_repo_updated));
+        }
+        if summary.cross_repo_unchanged > 0 {
+            cross_parts.push(format!("{} unchanged", summary.cross_repo_unchanged));
+        }
+        if summary.cross_repo_skipped > 0 {
+            cross_parts.push(format!(
+                "{} skipped (target newer)",
+                summary.cross_repo_skipped
+            ));
+        }
+
+        if !cross_parts.is_empty() {
+            lines.push(format!("Cross-repo: {}", cross_parts.join(", ")));
+        }
+
+        if let Some((id, count)) = self.trash.saved() {
+            lines.push(format!(
+                "Trash: {count} item(s) saved to {}/{id}/ {}",
+                trash::TRASH_DIR,
+                "(surf undo to restore)".dimmed()
+            ));
+        }
+
+        for w in &self.warnings {
+            lines.push(format!("{} {}", "WARNING:".yellow(), w));
+        }
+        for e in &self.errors {
+            lines.push(format!("{} {}", "ERROR:".red(), e));
+        }
+        if self.suppressed > 0 {
+            lines.push(
+                format!(
+                    "{} finding(s) suppressed by surfcontext.json",
+                    self.suppressed
+                )
+                .dimmed()
+                .to_string(),
+            );
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> SyncReport {
+        let mut report = SyncReport {
+            unchanged: 2,
+            ..Default::default()
+        };
+        report.record(
+            Action::new(
+                Stage::Generate,
+                ActionKind::Update,
+                "CLAUDE.md",
+                "regenerate from CONTEXT.md",
+            )
+            .hashes(Some("aa".into()), Some("bb".into())),
+        );
+        report.record(Action::new(
+            Stage::CrossRepo,
+            ActionKind::Create,
+            "../b/x.md",
+            "copy",
+        ));
+        report.record(Action::new(
+            Stage::Lockfile,
+            ActionKind::Update,
+            ".context/surf-lock.json",
+            "record",
+        ));
+        report.warnings.push(
+            Diagnostic::new(Code::OrphanFile, Stage::Sweep, "Unexpected file")
+                .path(".claude/x.txt"),
+        );
+        report
+    }
+
+    #[test]
+    fn test_summary_counts_actions() {
+        let summary = report().summary();
+        assert_eq!(
+            (
+                summary.updated,
+                summary.cross_repo_new,
+                summary.unchanged,
+                summary.warnings
+            ),
+            (1, 1, 2, 1)
+        );
+        assert_eq!(summary.created, 0);
+    }
+
+    #[test]
+    fn test_json_and_ndjson_shapes() {
+        let report = report();
+        let json = report.to_json();
+        assert_eq!(json["actions"][0]["kind"], "update");
+        assert_eq!(json["actions"][0]["stage"], "generate");
+        assert_eq!(json["actions"][0]["before"], "aa");
+        assert_eq!(json["actions"][1]["stage"], "crossRepo");
+        assert!(json["actions"][1].get("before").is_none());
+        assert_eq!(json["warnings"][0]["code"], "SC201");
+        assert_eq!(json["summary"]["crossRepoNew"], 1);
+
+        let lines = report.to_ndjson();
+        assert_eq!(lines.len(), 5);
+        let last: serde_json::Value = serde_json::from_str(lines.last().unwrap()).unwrap();
+        assert_eq!(last["type"], "summary");
+        assert_eq!(last["updated"], 1);
+        let warning: serde_json::Value = serde_json::from_str(&lines[3]).unwrap();
+        assert_eq!(
+            (warning["type"].as_str(), warning["path"].as_str()),
+            (Some("warning"), Some(".claude/x.txt"))
+        );
+        assert_eq!(warning["severity"], "warning");
+    }
+
+    #[test]
+    fn test_overrides_promote_and_suppress() {
+        let config: HashMap<String, String> = [
+            ("path-reference", "error"),
+            ("SC201", "off"),
+            ("sc401", "warning"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let mut report = SyncReport::default();
+        report.set_overrides(severity_overrides(&config).unwrap());
+
+        report.warn(Diagnostic::new(Code::PlatformPathReference, Stage::Audit, "ref"));
+        report.warn(Diagnostic::new(Code::OrphanFile, Stage::Sweep, "stray"));
+        report.warn(Diagnostic::new(Code::HandEdited, Stage::Generate, "edited"));
+        report.error(Diagnostic::new(Code::LinkBlocked, Stage::Links, "blocked"));
+
+        let codes = |d: &[Diagnostic]| d.iter().map(|d| d.code).collect::<Vec<_>>();
+        assert_eq!(codes(&report.errors), vec![Code::PlatformPathReference, Code::LinkBlocked]);
+        assert_eq!(report.errors[0].severity, Severity::Error);
+        assert_eq!(codes(&report.warnings), vec![Code::HandEdited]);
+        assert_eq!(report.suppressed, 1);
+
+        report.errors.clear();
+        assert!(!report.failed(false));
+        assert!(report.failed(true));
+    }
+
+    #[test]
+    fn test_codes_are_unique_and_parse_back() {
+        let mut seen = std::collections::HashSet::new();
+        for code in Code::ALL {
+            assert!(seen.insert(code.as_str()), "{code} used twice");
+            assert_eq!(Code::parse(code.as_str()), Some(code));
+            assert_eq!(Code::parse(code.name()), Some(code));
+        }
+        assert_eq!(Code::parse("SC101"), Some(Code::PlatformPathReference));
+        assert_eq!(Code::parse("SC201"), Some(Code::OrphanFile));
+    }
+
+    #[test]
+    fn test_overrides_reject_unknown_codes_and_levels() {
+        let one = |k: &str, v: &str| HashMap::from([(k.to_string(), v.to_string())]);
+        assert!(severity_overrides(&one("SC999", "off")).is_err());
+        assert!(severity_overrides(&one("SC101", "fatal")).is_err());
+    }
+}
@@ -0,0 +1,67 @@
+//! Where a sync run's progress goes.
+//!
+//! Stages never print. They hand progress lines, actions and diagnostics to the run's
+//! `Reporter`: the CLI uses `Console`, `--quiet` uses `Silent`, and embedders (editor
+//! extensions, CI bots, the playground) implement the trait to collect events their own way.
+
+use colored::Colorize;
+
+use super::SyncOpts;
+use super::report::{Action, Diagnostic, Stage};
+
+/// Receives events from a sync run as they happen. Every method defaults to doing nothing.
+pub trait Reporter {
+    /// A stage is starting. `title` is its header, e.g. `[Generate] Building platform files...`.
+    fn stage(&mut self, _stage: Stage, _title: &str) {}
+
+    /// One line of human-readable progress, possibly styled with ANSI colors.
+    fn progress(&mut self, _line: &str) {}
+
+    /// A file or link was (or, in dry-run mode, would be) changed.
+    fn action(&mut self, _action: &Action) {}
+
+    fn warning(&mut self, _diagnostic: &Diagnostic) {}
+
+    fn error(&mut self, _diagnostic: &Diagnostic) {}
+}
+
+/// Prints to stdout, as `surf sync` always has.
+pub struct Console;
+
+impl Reporter for Console {
+    fn stage(&mut self, _stage: Stage, title: &str) {
+        println!();
+        println!("{}", title.bold());
+    }
+
+    fn progress(&mut self, line: &str) {
+        println!("{line}");
+    }
+
+    fn warning(&mut self, diagnostic: &Diagnostic) {
+        println!("  {} {diagnostic}", "WARNING:".yellow());
+    }
+
+    fn error(&mut self, diagnostic: &Diagnostic) {
+        println!("  {} {diagnostic}", "ERROR:".red());
+    }
+}
+
+/// Drops everything; the report still records actions and diagnostics.
+pub struct Silent;
+
+impl Reporter for Silent {}
+
+/// The reporter the CLI uses for `opts`: the console, or nothing with `--quiet`.
+pub fn for_opts(opts: &SyncOpts) -> Box<dyn Reporter> {
+    console(opts.quiet)
+}
+
+/// The console, or nothing if `quiet`; for commands other than `surf sync`.
+pub fn console(quiet: bool) -> Box<dyn Reporter> {
+    if quiet {
+        Box::new(Silent)
+    } else {
+        Box::new(Console)
+    }
+}
@@ -0,0 +1,312 @@
+//! Path rewriting from canonical `.context/` locations to a platform's link farm.
+//!
+//! The table is derived from `canonical.*Dir` and `generation.<platform>.*Dir`, so a repo
+//! that keeps its docs in `knowledge/` gets `knowledge/...` rewritten, not `.context/docs/...`.
+//! Only whole path tokens are rewritten: the match must start at a word boundary, and
+//! fenced code blocks are left alone.
+
+use super::directives::is_fence;
+use crate::config::{PlatformGen, SurfConfig};
+
+/// One rewrite: `from` -> `to`, matched as a path token.
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    from: String,
+    to: String,
+    /// Whether `from` may match on its own; otherwise it must be followed by `/`. Only
+    /// dotted or nested paths may: a bare `knowledge` is more likely a word than a path.
+    bare: bool,
+}
+
+/// Ordered rewrite table for one platform.
+#[derive(Debug, Clone, Default)]
+pub struct PathRewriter {
+    rules: Vec<Rule>,
+    /// Rewrite inside fenced code blocks too.
+    code_blocks: bool,
+}
+
+impl PathRewriter {
+    /// Build the table for a platform whose link farm lives at `link_dir` (e.g. `.claude`).
+    pub fn for_platform(
+        config: &SurfConfig,
+        platform_gen: Option<&PlatformGen>,
+        link_dir: &str,
+    ) -> Self {
+        let link_dir = link_dir.trim_end_matches('/');
+        let override_dir = |pick: fn(&PlatformGen) -> Option<&String>, name: &str| {
+            platform_gen
+                .and_then(pick)
+                .map(|d| d.trim_end_matches('/').to_string())
+                .unwrap_or_else(|| format!("{link_dir}/{name}"))
+        };
+
+        let canonical = &config.canonical;
+        let mut rules = vec![
+            Rule {
+                from: canonical.agents_dir.clone(),
+                to: override_dir(|g| g.agents_dir.as_ref(), "agents"),
+                bare: true,
+            },
+            Rule {
+                from: canonical.docs_dir.clone(),
+                to: override_dir(|g| g.docs_dir.as_ref(), "docs"),
+                bare: true,
+            },
+            Rule {
+                from: canonical.skills_dir.clone(),
+                to: override_dir(|g| g.skills_dir.as_ref(), "skills"),
+                bare: true,
+            },
+            Rule {
+                from: canonical.guides_dir.clone(),
+                to: override_dir(|g| g.guides_dir.as_ref(), "guides"),
+                bare: true,
+            },
+            Rule {
+                from: ".context/queue.md".to_string(),
+                to: format!("{link_dir}/queue.md"),
+                bare: true,
+            },
+            Rule {
+                from: ".context".to_string(),
+                to: link_dir.to_string(),
+                bare: false,
+            },
+        ];
+
+        for rule in &mut rules {
+            rule.from = rule.from.trim_end_matches('/').to_string();
+            rule.bare &= is_path_like(&rule.from);
+        }
+        rules.retain(|r| !r.from.is_empty() && r.from != r.to);
+        // Longest source first so `.context/docs` wins over `.context`.
+        rules.sort_by_key(|r| std::cmp::Reverse(r.from.len()));
+        Self {
+            rules,
+            code_blocks: false,
+        }
+    }
+
+    /// A table of explicit `(from, to)` paths, each matched as a whole path token.
+    pub fn from_pairs(pairs: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut rules: Vec<Rule> = pairs
+            .into_iter()
+            .map(|(from, to)| Rule {
+                from: from.trim_end_matches('/').to_string(),
+                to: to.trim_end_matches('/').to_string(),
+                bare: true,
+            })
+            .filter(|r| !r.from.is_empty() && r.from != r.to)
+            .collect();
+        rules.sort_by_key(|r| std::cmp::Reverse(r.from.len()));
+        Self {
+            rules,
+            code_blocks: false,
+        }
+    }
+
+    /// Also rewrite inside fenced code blocks.
+    pub fn in_code_blocks(mut self) -> Self {
+        self.code_blocks = true;
+        self
+    }
+
+    /// The table mapping link-farm paths back to their canonical locations. The `.context`
+    /// catch-all is not inverted: the link dir also holds the platform's own files, such as
+    /// `.claude/settings.json`, which have no canonical counterpart.
+    pub fn inverse(&self) -> Self {
+        let mut rules: Vec<Rule> = self
+            .rules
+            .iter()
+            .filter(|r| r.from != ".context")
+            .map(|r| Rule {
+                from: r.to.clone(),
+                to: r.from.clone(),
+                bare: r.bare && is_path_like(&r.to),
+            })
+            .collect();
+        rules.sort_by_key(|r| std::cmp::Reverse(r.from.len()));
+        Self {
+            rules,
+            code_blocks: self.code_blocks,
+        }
+    }
+
+    /// Rewrite path tokens in `content`, skipping fenced code blocks unless
+    /// `in_code_blocks` was set.
+    pub fn apply(&self, content: &str) -> String {
+        if self.rules.is_empty() {
+            return content.to_string();
+        }
+
+        let mut output = String::with_capacity(content.len());
+        let mut in_fence = false;
+        for line in content.split_inclusive('\n') {
+            if is_fence(line) {
+                in_fence = !in_fence;
+                output.push_str(line);
+            } else if in_fence && !self.code_blocks {
+                output.push_str(line);
+            } else {
+                self.apply_line(line, &mut output);
+            }
+        }
+        output
+    }
+
+    fn apply_line(&self, line: &str, output: &mut String) {
+        let mut i = 0;
+        while i < line.len() {
+            let at_boundary = line[..i].chars().next_back().is_none_or(is_token_boundary);
+            if at_boundary && let Some(rule) = self.rules.iter().find(|r| r.matches_at(line, i)) {
+                output.push_str(&rule.to);
+                i += rule.from.len();
+                continue;
+            }
+            let ch = line[i..]
+                .chars()
+                .next()
+                .expect("index is on a char boundary");
+            output.push(ch);
+            i += ch.len_utf8();
+        }
+    }
+}
+
+impl Rule {
+    fn matches_at(&self, line: &str, i: usize) -> bool {
+        if !line[i..].starts_with(&self.from) {
+            return false;
+        }
+        match line[i + self.from.len()..].chars().next() {
+            Some('/') => true,
+            Some(c) => self.bare && !is_path_char(c),
+            None => self.bare,
+        }
+    }
+}
+
+/// Whether `path` cannot be mistaken for a plain word.
+fn is_path_like(path: &str) -> bool {
+    path.starts_with('.') || path.contains('/')
+}
+
+/// Characters that can continue a path token.
+fn is_path_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '/')
+}
+
+/// Characters that may precede the start of a path token.
+fn is_token_boundary(c: char) -> bool {
+    !is_path_char(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(json: &str) -> SurfConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_default_claude_table() {
+        let config = config("{}");
+        let rw = PathRewriter::for_platform(&config, None, ".claude");
+        let out = rw.apply(
+            "See `.context/docs/foo.md`, .context/agents/bar.md, (.context/queue.md) and .context/plans/x.\n",
+        );
+        assert_eq!(
+            out,
+            "See `.claude/docs/foo.md`, .claude/agents/bar.md, (.claude/queue.md) and .claude/plans/x.\n"
+        );
+    }
+
+    #[test]
+    fn test_rewrites_follow_config_dirs() {
+        let config = config(
+            r#"{
+                "canonical": { "docsDir": "knowledge/" },
+                "generation": { "claude": { "agentsDir": ".claude/personas" } }
+            }"#,
+        );
+        let rw = PathRewriter::for_platform(&config, config.generation.get("claude"), ".claude");
+        let out =
+            rw.apply("`knowledge/api.md` and `.context/agents/a.md` and `.context/docs/old.md`\n");
+        assert_eq!(
+            out,
+            "`.claude/docs/api.md` and `.claude/personas/a.md` and `.claude/docs/old.md`\n"
+        );
+    }
+
+    #[test]
+    fn test_undotted_dir_matches_only_as_a_path() {
+        let knowledge = config(r#"{ "canonical": { "docsDir": "knowledge/" } }"#);
+        let rw = PathRewriter::for_platform(&knowledge, None, ".claude");
+        assert_eq!(
+            rw.apply("Shared knowledge lives in `knowledge/api.md`, not `knowledge`.\n"),
+            "Shared knowledge lives in `.claude/docs/api.md`, not `knowledge`.\n"
+        );
+
+        let docs = config(r#"{ "generation": { "claude": { "docsDir": "docs" } } }"#);
+        let inverse =
+            PathRewriter::for_platform(&docs, docs.generation.get("claude"), ".claude").inverse();
+        assert_eq!(
+            inverse.apply("The docs are in docs/a.md and `docs`.\n"),
+            "The docs are in .context/docs/a.md and `docs`.\n"
+        );
+    }
+
+    #[test]
+    fn test_only_whole_tokens_are_rewritten() {
+        let config = config("{}");
+        let rw = PathRewriter::for_platform(&config, None, ".claude");
+        let input =
+            "foo.context/docs/x my.context/ ../other/.context/docs/y .context-old/ .contexts/\n";
+        assert_eq!(rw.apply(input), input);
+        assert_eq!(
+            rw.apply("the `.context/docs` dir\n"),
+            "the `.claude/docs` dir\n"
+        );
+        assert_eq!(rw.apply("the `.context` dir\n"), "the `.context` dir\n");
+    }
+
+    #[test]
+    fn test_fenced_code_is_untouched() {
+        let config = config("{}");
+        let rw = PathRewriter::for_platform(&config, None, ".claude");
+        let input =
+            "Use .context/docs/a.md\n```\ncat .context/docs/a.md\n```\nthen .context/docs/b.md\n";
+        assert_eq!(
+            rw.apply(input),
+            "Use .claude/docs/a.md\n```\ncat .context/docs/a.md\n```\nthen .claude/docs/b.md\n"
+        );
+    }
+
+    #[test]
+    fn test_inverse_restores_canonical_paths() {
+        let config = config(r#"{ "canonical": { "docsDir": "knowledge/" } }"#);
+        let rw = PathRewriter::for_platform(&config, None, ".claude");
+        let input = "`knowledge/api.md`, .context/agents/a.md, .context/queue.md\n";
+        assert_eq!(rw.inverse().apply(&rw.apply(input)), input);
+    }
+
+    #[test]
+    fn test_inverse_leaves_platform_files_alone() {
+        let config = config("{}");
+        let inverse = PathRewriter::for_platform(&config, None, ".claude").inverse();
+        assert_eq!(
+            inverse.apply("See .claude/settings.json and .claude/docs/a.md\n"),
+            "See .claude/settings.json and .context/docs/a.md\n"
+        );
+    }
+
+    #[test]
+    fn test_identity_when_link_dir_is_context() {
+        let config = config("{}");
+        let rw = PathRewriter::for_platform(&config, None, ".context");
+        let input = "`.context/docs/a.md` and .context/queue.md\n";
+        assert_eq!(rw.apply(input), input);
+    }
+}
@@ -0,0 +1,173 @@
+//! Custom root-context transforms via `generation.<platform>.rootContextScript`.
+//!
+//! The script is a local executable that receives CONTEXT.md on stdin and prints the
+//! platform file on stdout. It runs from the repo root with `SURF_PLATFORM` and
+//! `SURF_REPO_ROOT` set, and is killed if it outlives its timeout. The timeout also bounds
+//! the wait for its output, which a background process it started may hold open.
+
+use anyhow::{Context, Result, anyhow, bail};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// Timeout applied when `rootContextScriptTimeout` is not set.
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// Run a transform script and return its stdout.
+pub fn run_context_script(
+    repo_root: &Path,
+    script: &str,
+    platform: &str,
+    input: &str,
+    timeout: Duration,
+) -> Result<String> {
+    let script_path = repo_root.join(script);
+    if !script_path.is_file() {
+        bail!(
+            "rootContextScript `{script}` for platform `{platform}` not found at {}",
+            script_path.display()
+        );
+    }
+
+    let mut child = Command::new(&script_path)
+        .current_dir(repo_root)
+        .env("SURF_PLATFORM", platform)
+        .env("SURF_REPO_ROOT", repo_root)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run rootContextScript `{script}`"))?;
+
+    // Feed stdin and drain stdout/stderr on threads so a chatty script can't deadlock us. The
+    // readers report over channels: a background process the script left behind can hold the
+    // pipes open after the script exits, and the timeout has to cover that wait too.
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = input.to_string();
+    std::thread::spawn(move || {
+        // A script that ignores stdin may close it early; that is not our error.
+        let _ = stdin.write_all(input.as_bytes());
+    });
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let (out_tx, out_rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = out_tx.send(stdout.read_to_end(&mut buf).map(|_| buf));
+    });
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let (err_tx, err_rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut buf = String::new();
+        let _ = stderr.read_to_string(&mut buf);
+        let _ = err_tx.send(buf);
+    });
+
+    let deadline = Instant::now() + timeout;
+    let timed_out = || {
+        anyhow!(
+            "rootContextScript `{script}` timed out after {:.1}s",
+            timeout.as_secs_f64()
+        )
+    };
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() > deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(timed_out());
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    };
+    let remaining = || deadline.saturating_duration_since(Instant::now());
+
+    if !status.success() {
+        let code = status
+            .code()
+            .map_or_else(|| "a signal".to_string(), |c| format!("status {c}"));
+        let stderr = err_rx.recv_timeout(remaining()).unwrap_or_default();
+        let stderr = stderr.trim();
+        if stderr.is_empty() {
+            bail!("rootContextScript `{script}` exited with {code}");
+        }
+        bail!("rootContextScript `{script}` exited with {code}: {stderr}");
+    }
+
+    let stdout = out_rx
+        .recv_timeout(remaining())
+        .map_err(|_| anyhow!("{}: a process it started kept its output open", timed_out()))?
+        .with_context(|| format!("Failed to read output of rootContextScript `{script}`"))?;
+    String::from_utf8(stdout)
+        .with_context(|| format!("rootContextScript `{script}` produced non-UTF-8 output"))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_util::TestDir;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn write_script(dir: &Path, name: &str, body: &str) {
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_script_transforms_stdin() {
+        let dir = TestDir::new("surfcontext-test-script-ok");
+        write_script(&dir, "upper.sh", "echo \"# $SURF_PLATFORM\"; tr a-z A-Z");
+
+        let out = run_context_script(&dir, "upper.sh", "codex", "hello\n", Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(out, "# codex\nHELLO\n");
+    }
+
+    #[test]
+    fn test_script_nonzero_exit_is_error() {
+        let dir = TestDir::new("surfcontext-test-script-fail");
+        write_script(&dir, "fail.sh", "echo 'bad heading' >&2; exit 3");
+
+        let err = run_context_script(&dir, "fail.sh", "codex", "", Duration::from_secs(5))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("exited with status 3"));
+        assert!(err.contains("bad heading"));
+    }
+
+    #[test]
+    fn test_script_timeout() {
+        let dir = TestDir::new("surfcontext-test-script-slow");
+        write_script(&dir, "slow.sh", "sleep 5");
+
+        let err = run_context_script(&dir, "slow.sh", "codex", "", Duration::from_millis(200))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("timed out"));
+    }
+
+    #[test]
+    fn test_timeout_covers_output_held_open_by_a_background_process() {
+        let dir = TestDir::new("surfcontext-test-script-background");
+        write_script(&dir, "bg.sh", "sleep 5 & echo done");
+
+        let started = Instant::now();
+        let err =
+            run_context_script(&dir, "bg.sh", "codex", "", Duration::from_millis(300)).unwrap_err();
+        assert!(err.to_string().contains("kept its output open"));
+        assert!(started.elapsed() < Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_missing_script() {
+        let dir = TestDir::new("surfcontext-test-script-missing");
+        let err = run_context_script(&dir, "nope.sh", "codex", "", Duration::from_secs(1))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("not found"));
+    }
+}
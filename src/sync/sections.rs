@@ -0,0 +1,261 @@
+//! Declarative section filtering for generated platform files.
+//!
+//! Configured per platform under `generation.<platform>.sections`:
+//!
+//! ```json
+//! "sections": {
+//!   "exclude": ["Your Expertise", "/^Commands?$/"],
+//!   "title": [{ "find": "Advisor Agent", "replace": "Repository" }],
+//!   "replace": [{ "find": "You are the strategic advisor for", "replace": "This repo holds ..." }]
+//! }
+//! ```
+//!
+//! Heading patterns match the heading text exactly, or as a regex when wrapped in `/.../`.
+//! An excluded section runs until the next heading of the same or higher level. When
+//! `include` is non-empty, only matching sections (plus the preamble and `#` title) are kept.
+//! `title` rewrites apply to the first `#` heading, every match in turn, so a rule meant for
+//! one title should only match that title (`/Strategic (.*Advisor Agent)/`, not
+//! `Strategic `); `replace` swaps out any paragraph whose first line starts with (or
+//! matches) `find`.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use super::directives::is_fence;
+use crate::config::SectionRules;
+
+/// A heading or text pattern: literal, or regex when written as `/.../`.
+enum Pattern {
+    Literal(String),
+    Regex(Regex),
+}
+
+impl Pattern {
+    fn parse(raw: &str) -> Result<Self> {
+        if raw.len() > 1 && raw.starts_with('/') && raw.ends_with('/') {
+            let re = Regex::new(&raw[1..raw.len() - 1])
+                .with_context(|| format!("Invalid section pattern `{raw}`"))?;
+            Ok(Pattern::Regex(re))
+        } else {
+            Ok(Pattern::Literal(raw.to_string()))
+        }
+    }
+
+    fn matches_heading(&self, heading: &str) -> bool {
+        match self {
+            Pattern::Literal(s) => heading == s,
+            Pattern::Regex(re) => re.is_match(heading),
+        }
+    }
+
+    fn starts_paragraph(&self, line: &str) -> bool {
+        match self {
+            Pattern::Literal(s) => line.starts_with(s.as_str()),
+            Pattern::Regex(re) => re.is_match(line),
+        }
+    }
+
+    fn replace_all(&self, text: &str, with: &str) -> String {
+        match self {
+            Pattern::Literal(s) => text.replace(s.as_str(), with),
+            Pattern::Regex(re) => re.replace_all(text, with).into_owned(),
+        }
+    }
+}
+
+/// Compiled form of `SectionRules`.
+struct CompiledRules<'a> {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    title: Vec<(Pattern, &'a str)>,
+    replace: Vec<(Pattern, &'a str)>,
+}
+
+impl<'a> CompiledRules<'a> {
+    fn compile(rules: &'a SectionRules) -> Result<Self> {
+        let patterns = |raw: &[String]| {
+            raw.iter()
+                .map(|p| Pattern::parse(p))
+                .collect::<Result<Vec<_>>>()
+        };
+        let rewrites = |raw: &'a [crate::config::TextRewrite]| {
+            raw.iter()
+                .map(|r| Ok((Pattern::parse(&r.find)?, r.replace.as_str())))
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            include: patterns(&rules.include)?,
+            exclude: patterns(&rules.exclude)?,
+            title: rewrites(&rules.title)?,
+            replace: rewrites(&rules.replace)?,
+        })
+    }
+}
+
+/// Parse a markdown ATX heading into (level, text).
+fn parse_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &line[level..];
+    if rest.is_empty() {
+        return Some((level, ""));
+    }
+    rest.strip_prefix(' ').map(|text| (level, text.trim()))
+}
+
+/// Apply section rules to CONTEXT.md content.
+pub fn apply(content: &str, rules: &SectionRules) -> Result<String> {
+    let rules = CompiledRules::compile(rules)?;
+    let mut output = String::with_capacity(content.len());
+
+    let lines: Vec<&str> = content.lines().collect();
+    let mut in_fence = false;
+    let mut title_done = false;
+    // Level of the excluded section being skipped.
+    let mut skip_level: Option<usize> = None;
+    // Level of the included section being kept (only used when `include` is set).
+    let mut keep_level: Option<usize> = None;
+    // Body of a heading that `include` did not select.
+    let mut dropping = false;
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+
+        if is_fence(line) {
+            in_fence = !in_fence;
+        } else if !in_fence && let Some((level, text)) = parse_heading(line) {
+            if skip_level.is_some_and(|l| level <= l) {
+                skip_level = None;
+            }
+            if keep_level.is_some_and(|l| level <= l) {
+                keep_level = None;
+            }
+            dropping = false;
+
+            if skip_level.is_none() {
+                if rules.exclude.iter().any(|p| p.matches_heading(text)) {
+                    skip_level = Some(level);
+                } else if level == 1 && !title_done {
+                    title_done = true;
+                    let title = rules
+                        .title
+                        .iter()
+                        .fold(text.to_string(), |t, (p, with)| p.replace_all(&t, with));
+                    output.push_str(&format!("# {title}\n"));
+                    i += 1;
+                    continue;
+                } else if !rules.include.is_empty() && keep_level.is_none() && level > 1 {
+                    if rules.include.iter().any(|p| p.matches_heading(text)) {
+                        keep_level = Some(level);
+                    } else {
+                        dropping = true;
+                    }
+                }
+            }
+        }
+
+        if skip_level.is_some() || dropping {
+            i += 1;
+            continue;
+        }
+
+        // Paragraph replacement: swap the whole block for the configured text.
+        let paragraph_start = !in_fence && (i == 0 || lines[i - 1].trim().is_empty());
+        if paragraph_start
+            && let Some((_, with)) = rules.replace.iter().find(|(p, _)| p.starts_paragraph(line))
+        {
+            output.push_str(with.trim_end());
+            output.push('\n');
+            while i < lines.len() && !lines[i].trim().is_empty() {
+                i += 1;
+            }
+            continue;
+        }
+
+        output.push_str(line);
+        output.push('\n');
+        i += 1;
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(json: &str) -> SectionRules {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_no_rules_is_identity() {
+        let input = "# Title\n\nBody.\n\n## Section\n\nMore.\n";
+        assert_eq!(apply(input, &SectionRules::default()).unwrap(), input);
+    }
+
+    #[test]
+    fn test_exclude_runs_to_next_peer_heading() {
+        let input = "## Keep\n\na\n\n## Drop\n\nb\n\n### Nested\n\nc\n\n## After\n\nd\n";
+        let out = apply(input, &rules(r#"{ "exclude": ["Drop"] }"#)).unwrap();
+        assert!(out.contains("## Keep"));
+        assert!(!out.contains("## Drop"));
+        assert!(!out.contains("### Nested"));
+        assert!(!out.contains("c\n"));
+        assert!(out.contains("## After\n\nd\n"));
+    }
+
+    #[test]
+    fn test_exclude_regex() {
+        let input = "## Commands\n\nx\n\n## Command Reference\n\ny\n\n## Other\n\nz\n";
+        let out = apply(input, &rules(r#"{ "exclude": ["/^Command/"] }"#)).unwrap();
+        assert!(!out.contains("x\n"));
+        assert!(!out.contains("y\n"));
+        assert!(out.contains("## Other"));
+    }
+
+    #[test]
+    fn test_include_keeps_only_selected_sections() {
+        let input = "# Repo\n\nIntro.\n\n## Stack\n\nRust.\n\n### Tooling\n\nCargo.\n\n\
+                     ## Persona\n\nYou are...\n\n## Principles\n\nBe kind.\n";
+        let out = apply(input, &rules(r#"{ "include": ["Stack", "Principles"] }"#)).unwrap();
+        assert!(out.contains("# Repo\n\nIntro."));
+        assert!(out.contains("### Tooling\n\nCargo."));
+        assert!(!out.contains("Persona"));
+        assert!(!out.contains("You are"));
+        assert!(out.contains("## Principles\n\nBe kind."));
+    }
+
+    #[test]
+    fn test_headings_in_code_fences_are_ignored() {
+        let input = "## Keep\n\n```\n## Drop\n```\n\n## Drop\n\ngone\n";
+        let out = apply(input, &rules(r#"{ "exclude": ["Drop"] }"#)).unwrap();
+        assert!(out.contains("```\n## Drop\n```"));
+        assert!(!out.contains("gone"));
+    }
+
+    #[test]
+    fn test_title_and_paragraph_rewrites() {
+        let input = "# Acme Helper Agent\n\nYou are the helper for Acme.\nBe nice.\n\nRest.\n";
+        let out = apply(
+            input,
+            &rules(
+                r#"{
+                    "title": [{ "find": "/Helper Agent$/", "replace": "Repository" }],
+                    "replace": [{ "find": "You are the helper", "replace": "Acme's main repo." }]
+                }"#,
+            ),
+        )
+        .unwrap();
+        assert_eq!(out, "# Acme Repository\n\nAcme's main repo.\n\nRest.\n");
+    }
+
+    #[test]
+    fn test_invalid_regex_is_error() {
+        let err = apply("x\n", &rules(r#"{ "exclude": ["/(/"] }"#)).unwrap_err();
+        assert!(err.to_string().contains("Invalid section pattern"));
+    }
+}
@@ -0,0 +1,496 @@
+//! Trash journal for destructive sync operations.
+//!
+//! Before sync removes or overwrites anything it snapshots the original into
+//! `.context/.surf-trash/<run-id>/`, next to a `journal.json` that records where each
+//! snapshot came from. Files, links and mirrors the run creates are journaled too, without a
+//! snapshot. `surf undo` puts a run back, removing what it created, after saving what is on
+//! disk now as a new run so the undo can itself be undone; `surf trash prune` drops old runs.
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::atomic;
+
+/// Trash location, relative to the repo root.
+pub const TRASH_DIR: &str = ".context/.surf-trash";
+
+const JOURNAL_FILE: &str = "journal.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    /// Created by the run; undo removes it.
+    New,
+}
+
+/// One thing a sync run created, removed or overwrote.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    /// Original location, relative to the repo root (`../other-repo/...` for cross-repo targets).
+    pub path: String,
+    pub kind: EntryKind,
+    /// Snapshot name inside the run dir (files and dirs).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
+    /// Link target (symlinks).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+/// `journal.json` for one sync run.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Journal {
+    pub id: String,
+    /// Unix timestamp (seconds).
+    pub created_at: u64,
+    pub entries: Vec<Entry>,
+}
+
+/// Snapshots for the current sync run. The default value is disabled and records nothing,
+/// which is also what dry runs get.
+#[derive(Default)]
+pub struct Trash {
+    repo_root: Option<PathBuf>,
+    journal: Option<Journal>,
+    seen: HashSet<PathBuf>,
+    /// Paths recorded as new; nothing inside them needs recording.
+    new: Vec<PathBuf>,
+}
+
+impl Trash {
+    pub fn new(repo_root: &Path, dry_run: bool) -> Self {
+        Self {
+            repo_root: (!dry_run).then(|| repo_root.to_path_buf()),
+            ..Default::default()
+        }
+    }
+
+    /// Snapshot `path` before it is removed or overwritten. Missing paths are ignored, and
+    /// each path is saved once per run, so undo returns to the state before the run.
+    pub fn preserve(&mut self, path: &Path) -> Result<()> {
+        let Some(repo_root) = &self.repo_root else {
+            return Ok(());
+        };
+        let Ok(meta) = fs::symlink_metadata(path) else {
+            return Ok(());
+        };
+        if !self.seen.insert(path.to_path_buf()) {
+            return Ok(());
+        }
+
+        let journal = match &mut self.journal {
+            Some(journal) => journal,
+            None => self.journal.insert(start_run(repo_root)?),
+        };
+        let dir = run_dir(repo_root, &journal.id);
+        let display = display_path(repo_root, path);
+
+        let entry = if meta.is_symlink() {
+            Entry {
+                path: display,
+                kind: EntryKind::Symlink,
+                snapshot: None,
+                target: Some(fs::read_link(path)?.to_string_lossy().into_owned()),
+            }
+        } else {
+            let name = format!("{:04}", journal.entries.len() + 1);
+            copy_tree(path, &dir.join(&name))
+                .with_context(|| format!("Failed to save {} to the trash", path.display()))?;
+            Entry {
+                path: display,
+                kind: if meta.is_dir() {
+                    EntryKind::Dir
+                } else {
+                    EntryKind::File
+                },
+                snapshot: Some(name),
+                target: None,
+            }
+        };
+        journal.entries.push(entry);
+        write_journal(&dir, journal)
+    }
+
+    /// Call before writing `path`: an existing path is preserved, a missing one is recorded
+    /// as new so undo removes it.
+    pub fn before_write(&mut self, path: &Path) -> Result<()> {
+        if fs::symlink_metadata(path).is_ok() {
+            return self.preserve(path);
+        }
+        let Some(repo_root) = &self.repo_root else {
+            return Ok(());
+        };
+        if self.new.iter().any(|new| path.starts_with(new)) || !self.seen.insert(path.to_path_buf())
+        {
+            return Ok(());
+        }
+        self.new.push(path.to_path_buf());
+
+        let journal = match &mut self.journal {
+            Some(journal) => journal,
+            None => self.journal.insert(start_run(repo_root)?),
+        };
+        journal.entries.push(Entry {
+            path: display_path(repo_root, path),
+            kind: EntryKind::New,
+            snapshot: None,
+            target: None,
+        });
+        write_journal(&run_dir(repo_root, &journal.id), journal)
+    }
+
+    /// Run id and entry count, if anything was saved.
+    pub fn saved(&self) -> Option<(&str, usize)> {
+        self.journal
+            .as_ref()
+            .map(|j| (j.id.as_str(), j.entries.len()))
+    }
+}
+
+fn run_dir(repo_root: &Path, id: &str) -> PathBuf {
+    repo_root.join(TRASH_DIR).join(id)
+}
+
+fn display_path(repo_root: &Path, path: &Path) -> String {
+    path.strip_prefix(repo_root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Written after every entry so an aborted run can still be undone.
+fn write_journal(dir: &Path, journal: &Journal) -> Result<()> {
+    atomic::write(
+        &dir.join(JOURNAL_FILE),
+        serde_json::to_string_pretty(journal)? + "\n",
+    )
+}
+
+fn start_run(repo_root: &Path) -> Result<Journal> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let trash = repo_root.join(TRASH_DIR);
+    fs::create_dir_all(&trash)?;
+    let gitignore = trash.join(".gitignore");
+    if !gitignore.exists() {
+        fs::write(gitignore, "*\n")?;
+    }
+
+    let base = run_id(now);
+    let mut id = base.clone();
+    let mut n = 2;
+    while trash.join(&id).exists() {
+        id = format!("{base}-{n}");
+        n += 1;
+    }
+    fs::create_dir_all(trash.join(&id))?;
+
+    Ok(Journal {
+        id,
+        created_at: now,
+        entries: Vec::new(),
+    })
+}
+
+/// Sortable UTC run id, e.g. `20261016-201500`.
+fn run_id(unix_secs: u64) -> String {
+    let days = (unix_secs / 86_400) as i64;
+    let secs = unix_secs % 86_400;
+
+    // Civil-from-days (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+        secs / 3_600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Copy a file, dir or symlink, keeping symlinks as links.
+fn copy_tree(src: &Path, dst: &Path) -> Result<()> {
+    let meta = fs::symlink_metadata(src)?;
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if meta.is_symlink() {
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(fs::read_link(src)?, dst)?;
+        #[cfg(not(unix))]
+        fs::copy(src, dst)?;
+    } else if meta.is_dir() {
+        fs::create_dir_all(dst)?;
+        for entry in fs::read_dir(src)? {
+            let entry = entry?;
+            copy_tree(&entry.path(), &dst.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(src, dst)?;
+    }
+    Ok(())
+}
+
+fn remove_path(path: &Path) -> Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+fn read_journal(dir: &Path) -> Result<Journal> {
+    let raw = fs::read_to_string(dir.join(JOURNAL_FILE))?;
+    Ok(serde_json::from_str(&raw)?)
+}
+
+/// All runs in the trash, oldest first.
+pub fn list_runs(repo_root: &Path) -> Result<Vec<Journal>> {
+    let trash = repo_root.join(TRASH_DIR);
+    if !trash.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut runs = Vec::new();
+    for entry in fs::read_dir(&trash)? {
+        let path = entry?.path();
+        if path.is_dir()
+            && let Ok(journal) = read_journal(&path)
+        {
+            runs.push(journal);
+        }
+    }
+    runs.sort_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+    Ok(runs)
+}
+
+/// Restore a run (the latest when `id` is `None`) and remove it from the trash. Whatever is
+/// on disk now is preserved into `trash` before it is removed or overwritten, so edits made
+/// after the run are never lost.
+pub fn undo(repo_root: &Path, id: Option<&str>, trash: &mut Trash) -> Result<Journal> {
+    let journal = match id {
+        Some(id) => read_journal(&run_dir(repo_root, id))
+            .with_context(|| format!("No trash run `{id}` in {TRASH_DIR}/"))?,
+        None => match list_runs(repo_root)?.pop() {
+            Some(journal) => journal,
+            None => bail!("Nothing to undo: {TRASH_DIR}/ is empty"),
+        },
+    };
+    let dir = run_dir(repo_root, &journal.id);
+
+    // Latest first, so an earlier snapshot of an enclosing dir has the final say. Files and
+    // links are renamed over whatever is there, so an agent never reads a half-restored file.
+    for entry in journal.entries.iter().rev() {
+        let target = repo_root.join(&entry.path);
+        let existing = fs::symlink_metadata(&target).ok();
+        trash.preserve(&target)?;
+        if entry.kind == EntryKind::New {
+            if existing.is_some() {
+                remove_path(&target)?;
+            }
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if entry.kind != EntryKind::Dir && existing.is_some_and(|meta| meta.is_dir()) {
+            fs::remove_dir_all(&target)?;
+        }
+        match (entry.kind, &entry.snapshot, &entry.target) {
+            (EntryKind::Symlink, _, Some(link)) => {
+                #[cfg(unix)]
+                atomic::symlink(Path::new(link), &target)?;
+                #[cfg(not(unix))]
+                bail!(
+                    "Cannot restore symlink {} -> {link} on this platform",
+                    entry.path
+                );
+            }
+            (EntryKind::Dir, Some(snapshot), _) => {
+                atomic::replace_dir(&target, |tmp| copy_tree(&dir.join(snapshot), tmp))
+                    .with_context(|| format!("Failed to restore {}", entry.path))?
+            }
+            (_, Some(snapshot), _) => atomic::copy(&dir.join(snapshot), &target)
+                .with_context(|| format!("Failed to restore {}", entry.path))?,
+            _ => bail!("Corrupt journal entry for {}", entry.path),
+        }
+    }
+
+    fs::remove_dir_all(&dir)?;
+    Ok(journal)
+}
+
+/// Delete all but the `keep` most recent runs. Returns the removed run ids.
+pub fn prune(repo_root: &Path, keep: usize) -> Result<Vec<String>> {
+    let runs = list_runs(repo_root)?;
+    let excess = runs.len().saturating_sub(keep);
+    let mut removed = Vec::new();
+    for journal in runs.into_iter().take(excess) {
+        fs::remove_dir_all(run_dir(repo_root, &journal.id))?;
+        removed.push(journal.id);
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestDir;
+
+    fn fixture(name: &str) -> TestDir {
+        let dir = TestDir::new(name);
+        fs::create_dir_all(dir.join(".claude/plans")).unwrap();
+        fs::write(dir.join("CLAUDE.md"), "hand edited\n").unwrap();
+        fs::write(dir.join(".claude/plans/a.md"), "plan\n").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_run_id() {
+        assert_eq!(run_id(0), "19700101-000000");
+        assert_eq!(run_id(1_792_181_723), "20261016-201523");
+        assert_eq!(run_id(951_782_400), "20000229-000000");
+    }
+
+    #[test]
+    fn test_preserve_and_undo() {
+        let repo = fixture("surfcontext-test-trash-undo");
+        let mut trash = Trash::new(&repo, false);
+
+        trash.preserve(&repo.join("CLAUDE.md")).unwrap();
+        fs::write(repo.join("CLAUDE.md"), "generated\n").unwrap();
+        trash.preserve(&repo.join("CLAUDE.md")).unwrap();
+        trash.preserve(&repo.join(".claude/plans")).unwrap();
+        fs::remove_dir_all(repo.join(".claude/plans")).unwrap();
+        trash.preserve(&repo.join("missing.md")).unwrap();
+
+        let (id, count) = trash.saved().unwrap();
+        assert_eq!(count, 2);
+        let id = id.to_string();
+
+        let journal = undo(&repo, None, &mut Trash::default()).unwrap();
+        assert_eq!(journal.id, id);
+        assert_eq!(
+            fs::read_to_string(repo.join("CLAUDE.md")).unwrap(),
+            "hand edited\n"
+        );
+        assert_eq!(
+            fs::read_to_string(repo.join(".claude/plans/a.md")).unwrap(),
+            "plan\n"
+        );
+        assert!(list_runs(&repo).unwrap().is_empty());
+        assert!(undo(&repo, None, &mut Trash::default()).is_err());
+    }
+
+    #[test]
+    fn test_undo_removes_what_the_run_created() {
+        let repo = fixture("surfcontext-test-trash-new");
+        let mut trash = Trash::new(&repo, false);
+
+        trash.before_write(&repo.join("AGENTS.md")).unwrap();
+        fs::write(repo.join("AGENTS.md"), "generated\n").unwrap();
+        trash.before_write(&repo.join(".codex/docs")).unwrap();
+        fs::create_dir_all(repo.join(".codex/docs")).unwrap();
+        trash.before_write(&repo.join(".codex/docs/a.md")).unwrap();
+        fs::write(repo.join(".codex/docs/a.md"), "a\n").unwrap();
+        trash.before_write(&repo.join("CLAUDE.md")).unwrap();
+        fs::write(repo.join("CLAUDE.md"), "generated\n").unwrap();
+        assert_eq!(trash.saved().unwrap().1, 3);
+
+        let journal = undo(&repo, None, &mut Trash::default()).unwrap();
+        let kinds: Vec<EntryKind> = journal.entries.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, vec![EntryKind::New, EntryKind::New, EntryKind::File]);
+        assert!(!repo.join("AGENTS.md").exists());
+        assert!(!repo.join(".codex/docs").exists());
+        assert_eq!(
+            fs::read_to_string(repo.join("CLAUDE.md")).unwrap(),
+            "hand edited\n"
+        );
+    }
+
+    #[test]
+    fn test_undo_saves_edits_made_after_the_run() {
+        let repo = fixture("surfcontext-test-trash-later-edits");
+        let mut run = Trash::new(&repo, false);
+        run.preserve(&repo.join("CLAUDE.md")).unwrap();
+        fs::write(repo.join("CLAUDE.md"), "generated\n").unwrap();
+        run.before_write(&repo.join("AGENTS.md")).unwrap();
+        fs::write(repo.join("AGENTS.md"), "generated\n").unwrap();
+        let run_id = run.saved().unwrap().0.to_string();
+
+        fs::write(repo.join("CLAUDE.md"), "edited after the run\n").unwrap();
+        fs::write(repo.join("AGENTS.md"), "also edited\n").unwrap();
+
+        let mut undo_trash = Trash::new(&repo, false);
+        undo(&repo, Some(&run_id), &mut undo_trash).unwrap();
+        assert_eq!(
+            fs::read_to_string(repo.join("CLAUDE.md")).unwrap(),
+            "hand edited\n"
+        );
+        assert!(!repo.join("AGENTS.md").exists());
+        assert_eq!(undo_trash.saved().unwrap().1, 2);
+
+        // Undoing the undo brings the later edits back.
+        undo(&repo, None, &mut Trash::default()).unwrap();
+        assert_eq!(
+            fs::read_to_string(repo.join("CLAUDE.md")).unwrap(),
+            "edited after the run\n"
+        );
+        assert_eq!(
+            fs::read_to_string(repo.join("AGENTS.md")).unwrap(),
+            "also edited\n"
+        );
+    }
+
+    #[test]
+    fn test_dry_run_and_default_record_nothing() {
+        let repo = fixture("surfcontext-test-trash-dry");
+        let mut trash = Trash::new(&repo, true);
+        trash.preserve(&repo.join("CLAUDE.md")).unwrap();
+        Trash::default().preserve(&repo.join("CLAUDE.md")).unwrap();
+        assert!(trash.saved().is_none());
+        assert!(!repo.join(TRASH_DIR).exists());
+    }
+
+    #[test]
+    fn test_prune_keeps_latest() {
+        let repo = fixture("surfcontext-test-trash-prune");
+        for _ in 0..3 {
+            let mut trash = Trash::new(&repo, false);
+            trash.preserve(&repo.join("CLAUDE.md")).unwrap();
+        }
+        let ids: Vec<String> = list_runs(&repo)
+            .unwrap()
+            .into_iter()
+            .map(|j| j.id)
+            .collect();
+        assert_eq!(ids.len(), 3);
+
+        assert_eq!(prune(&repo, 1).unwrap(), ids[..2].to_vec());
+        let left: Vec<String> = list_runs(&repo)
+            .unwrap()
+            .into_iter()
+            .map(|j| j.id)
+            .collect();
+        assert_eq!(left, ids[2..].to_vec());
+    }
+}
@@ -0,0 +1,563 @@
+//! `surf sync --watch`: keep platform files in sync while agents and people work.
+//!
+//! One full sync runs first. After that, every change to CONTEXT.md (or a file it
+//! includes), surfcontext.json, `.context/`, a canonical dir or cross-repo source outside
+//! it, or a platform dir re-runs only the stages it affects: regeneration for CONTEXT.md and its includes, the queue copy for `queue.md`, the
+//! defensive sweep for anything new in `.claude/` (or another platform dir). A config change
+//! reloads surfcontext.json and runs everything. Events are debounced like `surf build
+//! --watch`.
+//! Events that arrive during a run are kept for the next batch, except those for the paths
+//! the run wrote itself.
+
+use anyhow::Result;
+use colored::Colorize;
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use super::lockfile::{self, LOCK_FILE};
+use super::repo_lock::REPO_LOCK;
+use super::trash::{self, Trash};
+use super::{Reporter, SyncOpts, SyncReport, canonical_dirs};
+use super::{atomic, audit, cross_repo, directives, generate, link_farms, local, report, reporter};
+use crate::config::{self, SurfConfig};
+
+/// Quiet period that ends a burst of events (editors write in stages).
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// The stages a batch of changes calls for.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Affected {
+    /// surfcontext.json changed: reload it and run every stage.
+    pub config: bool,
+    pub links: bool,
+    pub generate: bool,
+    pub queue: bool,
+    pub sweep: bool,
+    pub audit: bool,
+    pub cross_repo: bool,
+}
+
+impl Affected {
+    pub fn any(&self) -> bool {
+        *self != Self::default()
+    }
+
+    fn merge(&mut self, other: Affected) {
+        self.config |= other.config;
+        self.links |= other.links;
+        self.generate |= other.generate;
+        self.queue |= other.queue;
+        self.sweep |= other.sweep;
+        self.audit |= other.audit;
+        self.cross_repo |= other.cross_repo;
+    }
+
+    /// Stage names, for the progress line.
+    fn names(&self) -> Vec<&'static str> {
+        [
+            (self.links, "links"),
+            (self.generate, "generate"),
+            (self.queue, "queue"),
+            (self.sweep, "sweep"),
+            (self.audit, "audit"),
+            (self.cross_repo, "cross-repo"),
+        ]
+        .into_iter()
+        .filter(|(on, _)| *on)
+        .map(|(_, name)| name)
+        .collect()
+    }
+}
+
+/// Which stages a change to `rel` (relative to the repo root) affects. `includes` are the
+/// files CONTEXT.md pulls in, which may live outside `.context/`.
+pub fn classify(
+    rel: &Path,
+    config: &SurfConfig,
+    farm_roots: &[String],
+    includes: &[PathBuf],
+) -> Affected {
+    let path = rel.to_string_lossy().replace('\\', "/");
+    let mut affected = Affected::default();
+    let inside = |dir: &str| path == dir || path.starts_with(&format!("{dir}/"));
+
+    if path == LOCK_FILE
+        || path == REPO_LOCK
+        || inside(trash::TRASH_DIR)
+        || rel
+            .file_name()
+            .is_some_and(|name| atomic::is_temp(&name.to_string_lossy()))
+    {
+        // Sync's own bookkeeping.
+        return affected;
+    }
+
+    if path == "surfcontext.json" {
+        affected.config = true;
+    } else if path == config.canonical.root_context {
+        affected.generate = true;
+    } else if path == ".context/queue.md" {
+        affected.queue = true;
+    } else if source_dirs(config).iter().any(|dir| inside(dir)) {
+        // Included by CONTEXT.md, mirrored into copy farms, or scanned by the audit.
+        affected.generate = true;
+        affected.links = true;
+        affected.audit = true;
+    } else if includes.iter().any(|include| include == rel) {
+        affected.generate = true;
+    } else if let Some(root) = farm_roots
+        .iter()
+        .find(|root| path.starts_with(&format!("{root}/")))
+    {
+        if path == format!("{root}/queue.md") {
+            affected.queue = true;
+        } else {
+            affected.sweep = true;
+            affected.links = true;
+        }
+    }
+
+    affected.cross_repo = config
+        .sync
+        .values()
+        .any(|section| inside(&normalize_dir(&section.source)));
+    affected
+}
+
+/// `.context/`, every canonical dir and every cross-repo source, relative to the repo root.
+/// Canonical dirs and sources may live outside `.context/` (`canonical.docsDir:
+/// "knowledge/"`).
+fn source_dirs(config: &SurfConfig) -> Vec<String> {
+    let mut dirs = vec![".context".to_string()];
+    let sources = config.sync.values().map(|section| section.source.as_str());
+    for dir in canonical_dirs(config).into_iter().chain(sources) {
+        let dir = normalize_dir(dir);
+        if !dir.is_empty() && !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+fn normalize_dir(dir: &str) -> String {
+    dir.trim_start_matches("./")
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// `surf sync --watch`: sync once, then re-sync on every change until Ctrl+C.
+pub fn run_watch(opts: &SyncOpts) -> Result<()> {
+    let report = super::run_sync(opts)?;
+    let mut reporter = reporter::for_opts(opts);
+    for line in report.summary_lines() {
+        reporter.progress(&line);
+    }
+
+    let repo_root = super::find_repo_root()?;
+    let repo_root = repo_root.canonicalize().unwrap_or(repo_root);
+    let mut config = config::load_config(&repo_root)?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            let _ = tx.send(event);
+        }
+    })?;
+    let mut includes = root_includes(&repo_root, &config).unwrap_or_default();
+    let mut watched = BTreeSet::new();
+    watch_paths(&mut watcher, &repo_root, &config, &includes, &mut watched)?;
+
+    reporter.progress("");
+    reporter.progress(&format!(
+        "{} {} for changes (Ctrl+C to stop)",
+        "Watching".cyan().bold(),
+        repo_root.display()
+    ));
+
+    let mut pending: Vec<notify::Event> = Vec::new();
+    loop {
+        let mut events = std::mem::take(&mut pending);
+        if events.is_empty() {
+            let Ok(first) = rx.recv() else {
+                break;
+            };
+            events.push(first);
+        }
+        while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+            events.push(event);
+        }
+
+        let farm_roots = farm_roots(&config);
+        let mut affected = Affected::default();
+        let mut changed = BTreeSet::new();
+        for event in &events {
+            if !matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            ) {
+                continue;
+            }
+            for path in &event.paths {
+                let Ok(rel) = path.strip_prefix(&repo_root) else {
+                    continue;
+                };
+                let this = classify(rel, &config, &farm_roots, &includes);
+                if this.any() {
+                    changed.insert(rel.display().to_string());
+                    affected.merge(this);
+                }
+            }
+        }
+        if !affected.any() {
+            continue;
+        }
+
+        let stages = if affected.config {
+            "reload config, full sync".to_string()
+        } else {
+            affected.names().join(", ")
+        };
+        reporter.progress("");
+        reporter.progress(&format!(
+            "{} {} changed {}",
+            "[Watch]".cyan().bold(),
+            changed.into_iter().collect::<Vec<_>>().join(", "),
+            format!("({stages})").dimmed()
+        ));
+
+        let result = if affected.config {
+            reload(&repo_root, &mut config).and_then(|()| {
+                reporter.progress(&format!("  {}", "Reloaded surfcontext.json".green()));
+                watch_paths(&mut watcher, &repo_root, &config, &includes, &mut watched)?;
+                super::run_stages(&repo_root, &config, opts, None)
+            })
+        } else {
+            run_affected(&repo_root, &config, opts, affected)
+        };
+        let written = match result {
+            Ok(report) => {
+                report_result(&report, reporter.as_mut());
+                written_paths(&report)
+            }
+            Err(e) => {
+                reporter.progress(&format!("{} {e:#}", "Sync error:".red().bold()));
+                Vec::new()
+            }
+        };
+
+        // CONTEXT.md or an included file may have changed what it includes. While an
+        // include is broken, keep watching the last set that expanded.
+        if (affected.config || affected.generate)
+            && let Some(found) = root_includes(&repo_root, &config)
+        {
+            includes = found;
+            if let Err(e) = watch_paths(&mut watcher, &repo_root, &config, &includes, &mut watched)
+            {
+                reporter.progress(&format!("{} {e:#}", "Watch error:".red().bold()));
+            }
+        }
+
+        // Keep what changed during the run for the next batch, minus this run's own writes.
+        // Bookkeeping (lockfile, repo lock, trash, temp files) is already ignored by classify.
+        std::thread::sleep(Duration::from_millis(50));
+        pending = rx
+            .try_iter()
+            .filter_map(|mut event| {
+                event.paths.retain(|path| {
+                    path.strip_prefix(&repo_root)
+                        .is_ok_and(|rel| !is_written(rel, &written))
+                });
+                (!event.paths.is_empty()).then_some(event)
+            })
+            .collect();
+    }
+
+    Ok(())
+}
+
+/// Re-run only the `affected` stages, in pipeline order.
+pub fn run_affected(
+    repo_root: &Path,
+    config: &SurfConfig,
+    opts: &SyncOpts,
+    affected: Affected,
+) -> Result<SyncReport> {
+    let mut report = SyncReport::with_reporter(
+        Trash::new(repo_root, opts.dry_run),
+        reporter::for_opts(opts),
+    );
+    report.set_overrides(report::severity_overrides(&config.diagnostics)?);
+    let _lock = super::lock_repo(repo_root, opts, &mut report)?;
+    if super::check_platforms(config, &mut report) {
+        return Ok(report);
+    }
+    if affected.links {
+        local::setup_links(repo_root, config, opts, &mut report)?;
+    }
+    if affected.generate {
+        generate::generate_all(repo_root, config, opts, &mut report)?;
+    }
+    if affected.queue {
+        local::sync_queue(repo_root, config, opts, &mut report)?;
+    }
+    if affected.sweep {
+        local::defensive_sweep(repo_root, config, opts, &mut report)?;
+    }
+    if affected.audit {
+        audit::audit_path_references(repo_root, config, &mut report)?;
+        audit::audit_broken_references(repo_root, config, &mut report)?;
+    }
+    if affected.cross_repo && !opts.local_only {
+        cross_repo::sync_repos(repo_root, config, opts, &mut report)?;
+    }
+    lockfile::merge_and_write(repo_root, opts, &report)?;
+    Ok(report)
+}
+
+/// Reload surfcontext.json, keeping the old config if the new one does not parse.
+fn reload(repo_root: &Path, config: &mut SurfConfig) -> Result<()> {
+    *config = config::load_config(repo_root)?;
+    Ok(())
+}
+
+/// Watch the repo root itself (for CONTEXT.md and surfcontext.json), all of `.context/` and
+/// of every canonical dir or cross-repo source outside it, the top level of each platform
+/// dir, and the directory of each include outside `.context/`. Platform dirs are not
+/// watched recursively: their links point back into the canonical dirs, and anything new
+/// appears at the top level first.
+fn watch_paths(
+    watcher: &mut impl Watcher,
+    repo_root: &Path,
+    config: &SurfConfig,
+    includes: &[PathBuf],
+    watched: &mut BTreeSet<PathBuf>,
+) -> Result<()> {
+    let mut paths = vec![(repo_root.to_path_buf(), RecursiveMode::NonRecursive)];
+    for dir in source_dirs(config) {
+        let full = repo_root.join(&dir);
+        if dir == ".context" || !dir.starts_with(".context/") {
+            paths.push((full, RecursiveMode::Recursive));
+        }
+    }
+    for root in farm_roots(config) {
+        paths.push((repo_root.join(root), RecursiveMode::NonRecursive));
+    }
+    for include in includes.iter().filter(|i| !i.starts_with(".context")) {
+        if let Some(dir) = include.parent() {
+            paths.push((repo_root.join(dir), RecursiveMode::NonRecursive));
+        }
+    }
+    for (path, mode) in paths {
+        if path.is_dir() && !watched.contains(&path) {
+            watcher.watch(&path, mode)?;
+            watched.insert(path);
+        }
+    }
+    Ok(())
+}
+
+/// Paths a run created, updated, migrated away or removed, relative to the repo root.
+fn written_paths(report: &SyncReport) -> Vec<String> {
+    report
+        .actions
+        .iter()
+        .map(|action| action.path.trim_end_matches('/').to_string())
+        .collect()
+}
+
+/// Whether `rel` is one of `written`, or inside one (a refreshed mirror).
+fn is_written(rel: &Path, written: &[String]) -> bool {
+    let path = rel.to_string_lossy().replace('\\', "/");
+    written
+        .iter()
+        .any(|w| path == *w || path.starts_with(&format!("{w}/")))
+}
+
+/// The files CONTEXT.md includes, directly or not; `None` if it does not expand.
+fn root_includes(repo_root: &Path, config: &SurfConfig) -> Option<Vec<PathBuf>> {
+    let root_context = &config.canonical.root_context;
+    let content = std::fs::read_to_string(repo_root.join(root_context)).ok()?;
+    directives::expand_includes(repo_root, root_context, &content)
+        .ok()
+        .map(|expanded| expanded.includes)
+}
+
+fn farm_roots(config: &SurfConfig) -> Vec<String> {
+    link_farms(config)
+        .into_iter()
+        .map(|farm| farm.root)
+        .collect()
+}
+
+/// One line per incremental run; the stages have already reported the details.
+fn report_result(report: &SyncReport, reporter: &mut dyn Reporter) {
+    let summary = report.summary();
+    let changed = summary.created
+        + summary.updated
+        + summary.redirected
+        + summary.migrated
+        + summary.removed
+        + summary.cross_repo_new
+        + summary.cross_repo_updated;
+    let mut line = if changed == 0 {
+        "up to date".to_string()
+    } else {
+        format!("{changed} change(s)")
+    };
+    if summary.warnings > 0 {
+        line.push_str(&format!(", {} warning(s)", summary.warnings));
+    }
+    if summary.errors > 0 {
+        line.push_str(&format!(", {} error(s)", summary.errors));
+    }
+    if let Some((id, _)) = report.trash.saved() {
+        line.push_str(&format!(" — trash run {id}"));
+    }
+    reporter.progress(&format!("  {} {line}", "=>".dimmed()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestDir;
+    use std::fs;
+
+    fn config() -> SurfConfig {
+        serde_json::from_str(
+            r#"{ "sync": { "docs": { "source": ".context/docs/", "targets": [] } } }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_classify_picks_stages() {
+        let config = config();
+        let roots = vec![".claude".to_string()];
+        let includes = vec![PathBuf::from("docs/shared.md")];
+        let classify = |p: &str| classify(Path::new(p), &config, &roots, &includes);
+
+        assert!(classify("surfcontext.json").config);
+        assert_eq!(
+            classify("CONTEXT.md"),
+            Affected {
+                generate: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(classify(".context/queue.md").names(), vec!["queue"]);
+        assert_eq!(classify(".claude/queue.md").names(), vec!["queue"]);
+        assert_eq!(classify(".claude/notes.md").names(), vec!["links", "sweep"]);
+        assert_eq!(
+            classify(".context/docs/a.md").names(),
+            vec!["links", "generate", "audit", "cross-repo"]
+        );
+        assert!(!classify(".context/guides/a.md").cross_repo);
+        assert!(!classify(LOCK_FILE).any());
+        assert!(!classify(REPO_LOCK).any());
+        assert!(!classify(".claude/.queue.md.surf-tmp.812.0").any());
+        assert!(!classify(".context/.surf-trash").any());
+        assert!(!classify(".context/.surf-trash/20260101-000000/journal.json").any());
+        assert!(!classify("CLAUDE.md").any());
+        assert!(!classify("src/main.rs").any());
+        assert_eq!(classify("docs/shared.md").names(), vec!["generate"]);
+        assert!(!classify("docs/other.md").any());
+    }
+
+    #[test]
+    fn test_classify_sees_sources_outside_context() {
+        let config: SurfConfig = serde_json::from_str(
+            r#"{
+                "canonical": { "docsDir": "knowledge/" },
+                "sync": { "shared": { "source": "./shared/", "targets": [] } }
+            }"#,
+        )
+        .unwrap();
+        let classify = |p: &str| classify(Path::new(p), &config, &[], &[]);
+
+        assert_eq!(
+            classify("knowledge/a.md").names(),
+            vec!["links", "generate", "audit"]
+        );
+        assert_eq!(
+            classify("shared/a.md").names(),
+            vec!["links", "generate", "audit", "cross-repo"]
+        );
+        assert!(!classify("knowledge-old/a.md").any());
+        assert_eq!(
+            source_dirs(&config),
+            vec![
+                ".context",
+                ".context/agents",
+                "knowledge",
+                ".context/skills",
+                ".context/guides",
+                "shared"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_root_includes_reach_outside_context() {
+        let repo = TestDir::new("surfcontext-test-watch-includes").with_files(&[
+            ("CONTEXT.md", "# Repo\n<!-- surf:include docs/shared.md -->\n"),
+            ("docs/shared.md", "Shared\n<!-- surf:include .context/docs/a.md -->\n"),
+            (".context/docs/a.md", "A\n"),
+        ]);
+        let config: SurfConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(
+            root_includes(&repo, &config).unwrap(),
+            vec![PathBuf::from(".context/docs/a.md"), PathBuf::from("docs/shared.md")]
+        );
+    }
+
+    #[test]
+    fn test_is_written_covers_paths_inside_written_dirs() {
+        let written = vec![".claude/queue.md".to_string(), ".claude/docs".to_string()];
+        assert!(is_written(Path::new(".claude/queue.md"), &written));
+        assert!(is_written(Path::new(".claude/docs/a.md"), &written));
+        assert!(!is_written(Path::new(".claude/docs-old"), &written));
+        assert!(!is_written(Path::new(".context/queue.md"), &written));
+    }
+
+    #[test]
+    fn test_incremental_run_keeps_other_lock_entries() {
+        let repo = TestDir::new("surfcontext-test-watch");
+        fs::create_dir_all(repo.join(".context")).unwrap();
+        fs::write(repo.join("CONTEXT.md"), "# Repo\n").unwrap();
+        fs::write(repo.join(".context/queue.md"), "# Queue\n").unwrap();
+        let config: SurfConfig =
+            serde_json::from_str(r#"{ "generation": { "claude": { "method": "copy" } } }"#)
+                .unwrap();
+        let opts = SyncOpts {
+            local_only: true,
+            quiet: true,
+            ..Default::default()
+        };
+        super::super::run_stages(&repo, &config, &opts, None).unwrap();
+        let before = lockfile::Lockfile::load(&repo).unwrap().outputs;
+
+        fs::write(repo.join(".context/queue.md"), "# Queue\n\n- task\n").unwrap();
+        let affected = Affected {
+            queue: true,
+            ..Default::default()
+        };
+        let report = run_affected(&repo, &config, &opts, affected).unwrap();
+        assert_eq!(report.summary().updated, 1);
+        assert!(
+            fs::read_to_string(repo.join(".claude/queue.md"))
+                .unwrap()
+                .ends_with("- task\n")
+        );
+
+        let after = lockfile::Lockfile::load(&repo).unwrap().outputs;
+        assert_eq!(
+            before.keys().collect::<Vec<_>>(),
+            after.keys().collect::<Vec<_>>()
+        );
+        assert_ne!(before[".claude/queue.md"], after[".claude/queue.md"]);
+        assert_eq!(before["CLAUDE.md"], after["CLAUDE.md"]);
+    }
+}
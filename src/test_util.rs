@@ -0,0 +1,53 @@
+//! Scratch directories for unit tests.
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// An empty directory under the system temp dir, unique to one test and removed on drop.
+/// Tests run in parallel, and so may two `cargo test` runs, so a fixed path would let one
+/// test delete or read another's files.
+pub struct TestDir(PathBuf);
+
+impl TestDir {
+    /// `name` only makes the directory recognizable: `<name>-<pid>-<n>`.
+    pub fn new(name: &str) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("{name}-{}-{n}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        TestDir(dir)
+    }
+
+    /// Write each `(path, content)` pair, creating parent directories.
+    pub fn with_files(self, files: &[(&str, &str)]) -> Self {
+        for (path, content) in files {
+            let full = self.0.join(path);
+            fs::create_dir_all(full.parent().unwrap()).unwrap();
+            fs::write(full, content).unwrap();
+        }
+        self
+    }
+}
+
+impl Deref for TestDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TestDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,288 @@
+//! Integration tests for using sync as a library, without the `surf` binary.
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use surfcontext_cli::sync::clean::{CleanOpts, clean};
+use surfcontext_cli::sync::reporter::Silent;
+use surfcontext_cli::sync::trash::Trash;
+use surfcontext_cli::{
+    Action, ActionKind, Code, Diagnostic, Reporter, Stage, SyncOpts, load_config, render_platform,
+    run_sync_at,
+};
+
+#[derive(Default)]
+struct Events {
+    stages: Vec<Stage>,
+    actions: Vec<String>,
+    warnings: Vec<String>,
+    progress: usize,
+}
+
+struct Collect(Rc<RefCell<Events>>);
+
+impl Reporter for Collect {
+    fn stage(&mut self, stage: Stage, _title: &str) {
+        self.0.borrow_mut().stages.push(stage);
+    }
+
+    fn progress(&mut self, _line: &str) {
+        self.0.borrow_mut().progress += 1;
+    }
+
+    fn action(&mut self, action: &Action) {
+        self.0.borrow_mut().actions.push(action.path.clone());
+    }
+
+    fn warning(&mut self, diagnostic: &Diagnostic) {
+        self.0
+            .borrow_mut()
+            .warnings
+            .push(diagnostic.code.to_string());
+    }
+}
+
+/// A fresh repo per test; the pid keeps concurrent `cargo test` runs apart.
+fn repo(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join(".claude")).unwrap();
+    fs::write(
+        dir.join("CONTEXT.md"),
+        "# Repo\n\nBuild with `cargo build`.\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("surfcontext.json"),
+        r#"{ "generation": { "claude": {} }, "sweep": { "mode": "report" } }"#,
+    )
+    .unwrap();
+    fs::write(dir.join(".claude/notes.txt"), "stray\n").unwrap();
+    dir
+}
+
+fn opts() -> SyncOpts {
+    SyncOpts {
+        local_only: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn sync_reports_through_a_custom_reporter() {
+    let root = repo("surfcontext-test-lib-sync");
+    let config = load_config(&root).unwrap();
+    let events = Rc::new(RefCell::new(Events::default()));
+
+    let report = run_sync_at(&root, &config, &opts(), Box::new(Collect(events.clone()))).unwrap();
+
+    let events = events.borrow();
+    assert_eq!(events.stages.first(), Some(&Stage::Setup));
+    assert_eq!(events.stages.last(), Some(&Stage::Lockfile));
+    assert!(events.progress > 0);
+    assert_eq!(
+        events.actions,
+        report
+            .actions
+            .iter()
+            .map(|a| a.path.clone())
+            .collect::<Vec<_>>()
+    );
+    assert!(
+        report
+            .actions
+            .iter()
+            .any(|a| a.path == "CLAUDE.md" && a.kind == ActionKind::Create)
+    );
+    assert_eq!(events.warnings, vec!["SC201"]);
+    assert!(root.join("CLAUDE.md").exists());
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn render_platform_matches_what_sync_writes() {
+    let root = repo("surfcontext-test-lib-render");
+    let config = load_config(&root).unwrap();
+    let context = fs::read_to_string(root.join("CONTEXT.md")).unwrap();
+
+    let rendered = render_platform(&root, &config, "claude", &context).unwrap();
+    let opts = SyncOpts {
+        quiet: true,
+        ..opts()
+    };
+    run_sync_at(
+        &root,
+        &config,
+        &opts,
+        Box::new(surfcontext_cli::sync::reporter::Silent),
+    )
+    .unwrap();
+    assert_eq!(
+        rendered,
+        fs::read_to_string(root.join("CLAUDE.md")).unwrap()
+    );
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn deleting_a_canonical_file_propagates_through_a_copy_mirror() {
+    let root = repo("surfcontext-test-lib-mirror-delete");
+    fs::write(
+        root.join("surfcontext.json"),
+        r#"{ "generation": { "claude": { "method": "copy" } }, "sweep": { "mode": "migrate" } }"#,
+    )
+    .unwrap();
+    fs::create_dir_all(root.join(".context/docs")).unwrap();
+    fs::write(root.join(".context/docs/old.md"), "old\n").unwrap();
+    fs::write(root.join(".context/docs/kept.md"), "kept\n").unwrap();
+    let config = load_config(&root).unwrap();
+    let opts = SyncOpts {
+        quiet: true,
+        ..opts()
+    };
+    let sync = || {
+        run_sync_at(
+            &root,
+            &config,
+            &opts,
+            Box::new(surfcontext_cli::sync::reporter::Silent),
+        )
+        .unwrap()
+    };
+
+    sync();
+    assert!(root.join(".claude/docs/old.md").exists());
+
+    fs::remove_file(root.join(".context/docs/old.md")).unwrap();
+    fs::write(
+        root.join(".claude/docs/kept.md"),
+        "kept\nedited in the mirror\n",
+    )
+    .unwrap();
+    sync();
+
+    assert!(!root.join(".context/docs/old.md").exists());
+    assert!(!root.join(".claude/docs/old.md").exists());
+    assert_eq!(
+        fs::read_to_string(root.join(".context/docs/kept.md")).unwrap(),
+        "kept\nedited in the mirror\n"
+    );
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn editing_a_canonical_file_in_place_is_not_rescued_from_a_hardlink_mirror() {
+    let root = repo("surfcontext-test-lib-mirror-hardlink");
+    fs::write(
+        root.join("surfcontext.json"),
+        r#"{ "generation": { "claude": { "method": "hardlink" } }, "sweep": { "mode": "migrate" } }"#,
+    )
+    .unwrap();
+    fs::create_dir_all(root.join(".context/docs")).unwrap();
+    fs::write(root.join(".context/docs/a.md"), "a\n").unwrap();
+    let config = load_config(&root).unwrap();
+    let opts = SyncOpts {
+        quiet: true,
+        ..opts()
+    };
+    let sync = || {
+        run_sync_at(
+            &root,
+            &config,
+            &opts,
+            Box::new(surfcontext_cli::sync::reporter::Silent),
+        )
+        .unwrap()
+    };
+
+    sync();
+    // Writing through the canonical path keeps the inode, so the mirror sees it too.
+    fs::write(root.join(".context/docs/a.md"), "a\nedited\n").unwrap();
+    let report = sync();
+
+    assert!(
+        report.actions.iter().all(|a| a.stage == Stage::Lockfile),
+        "{:?}",
+        report.actions
+    );
+    assert_eq!(
+        fs::read_to_string(root.join(".claude/docs/a.md")).unwrap(),
+        "a\nedited\n"
+    );
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn a_newer_cross_repo_target_survives_prune_and_clean() {
+    let root = repo("surfcontext-test-lib-cross-newer");
+    fs::create_dir_all(root.join("shared")).unwrap();
+    fs::create_dir_all(root.join("target-repo")).unwrap();
+    fs::write(root.join("shared/a.md"), "from source\n").unwrap();
+    let with_target = r#"{ "generation": { "claude": {} }, "sweep": { "mode": "report" },
+        "sync": { "shared": { "source": "shared",
+            "targets": [{ "repo": "target-repo", "dest": "docs" }] } } }"#;
+    fs::write(root.join("surfcontext.json"), with_target).unwrap();
+    let opts = SyncOpts {
+        quiet: true,
+        local_only: false,
+        ..opts()
+    };
+    let sync = || {
+        let config = load_config(&root).unwrap();
+        run_sync_at(
+            &root,
+            &config,
+            &opts,
+            Box::new(surfcontext_cli::sync::reporter::Silent),
+        )
+        .unwrap()
+    };
+
+    sync();
+    let target = root.join("target-repo/docs/a.md");
+    fs::write(&target, "edited at the target\n").unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&target)
+        .unwrap()
+        .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(60))
+        .unwrap();
+    let report = sync();
+    assert!(
+        report
+            .warnings
+            .iter()
+            .any(|w| w.code == Code::CrossRepoTargetNewer)
+    );
+
+    // Dropping the target makes the file a stale output; the prune must keep it.
+    fs::write(
+        root.join("surfcontext.json"),
+        r#"{ "generation": { "claude": {} }, "sweep": { "mode": "report" } }"#,
+    )
+    .unwrap();
+    sync();
+    assert_eq!(
+        fs::read_to_string(&target).unwrap(),
+        "edited at the target\n"
+    );
+
+    let config = load_config(&root).unwrap();
+    let opts = CleanOpts {
+        dry_run: false,
+        cross_repo: true,
+        quiet: true,
+    };
+    let cleaned = clean(&root, &config, &opts, &mut Trash::default(), &mut Silent).unwrap();
+    assert!(
+        cleaned
+            .kept
+            .iter()
+            .any(|(path, _)| path == "target-repo/docs/a.md")
+    );
+    assert!(target.exists());
+    fs::remove_dir_all(&root).unwrap();
+}